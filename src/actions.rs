@@ -0,0 +1,341 @@
+use std::{error::Error, sync::Arc};
+
+use crate::{
+    net::Client,
+    types::{Account, Status},
+    ui::{screen::AccountListScreen, UiMsg, UiMsgSender, WebImageCache},
+};
+
+/// Something the user can do to a status, chosen from the action menu.
+pub trait StatusAction: Send {
+    /// The label shown in the menu.
+    fn name(&self) -> &str;
+
+    /// What to tell the user once the action is done.
+    fn done_message(&self) -> &str;
+
+    /// Carry out the action. Returns false if the user backed out, so that
+    /// nothing was done.
+    fn execute(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>>;
+}
+
+/// Favourites a status, or takes the favourite back if it is already there.
+pub struct FavouriteAction {
+    undo: bool,
+}
+
+impl StatusAction for FavouriteAction {
+    fn name(&self) -> &str {
+        if self.undo {
+            "Unfavourite"
+        } else {
+            "Favourite"
+        }
+    }
+
+    fn done_message(&self) -> &str {
+        if self.undo {
+            "Removed from favourites"
+        } else {
+            "Favourited"
+        }
+    }
+
+    fn execute(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        if self.undo {
+            client.unfavourite_status(&status.id)?;
+        } else {
+            client.favourite_status(&status.id)?;
+        }
+        Ok(true)
+    }
+}
+
+/// Boosts a status, or undoes the boost if it is already boosted.
+pub struct ReblogAction {
+    undo: bool,
+}
+
+impl StatusAction for ReblogAction {
+    fn name(&self) -> &str {
+        if self.undo {
+            "Undo boost"
+        } else {
+            "Boost"
+        }
+    }
+
+    fn done_message(&self) -> &str {
+        if self.undo {
+            "Boost undone"
+        } else {
+            "Boosted"
+        }
+    }
+
+    fn execute(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        if self.undo {
+            client.unreblog_status(&status.id)?;
+        } else {
+            client.reblog_status(&status.id)?;
+        }
+        Ok(true)
+    }
+}
+
+/// Bookmarks a status, or removes the bookmark if it is already there.
+pub struct BookmarkAction {
+    undo: bool,
+}
+
+impl StatusAction for BookmarkAction {
+    fn name(&self) -> &str {
+        if self.undo {
+            "Remove bookmark"
+        } else {
+            "Bookmark"
+        }
+    }
+
+    fn done_message(&self) -> &str {
+        if self.undo {
+            "Bookmark removed"
+        } else {
+            "Bookmarked"
+        }
+    }
+
+    fn execute(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        if self.undo {
+            client.unbookmark_status(&status.id)?;
+        } else {
+            client.bookmark_status(&status.id)?;
+        }
+        Ok(true)
+    }
+}
+
+/// Replies to a status with the same visibility, asking for the text first.
+pub struct ReplyAction;
+
+impl StatusAction for ReplyAction {
+    fn name(&self) -> &str {
+        "Reply"
+    }
+
+    fn done_message(&self) -> &str {
+        "Reply posted"
+    }
+
+    fn execute(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        client.reply(&status.id, &status.account.acct, status.visibility)
+    }
+}
+
+/// Pins one of the user's own statuses to their profile, or unpins it if it
+/// is already pinned.
+pub struct PinAction {
+    undo: bool,
+}
+
+impl StatusAction for PinAction {
+    fn name(&self) -> &str {
+        if self.undo {
+            "Unpin"
+        } else {
+            "Pin"
+        }
+    }
+
+    fn done_message(&self) -> &str {
+        if self.undo {
+            "Unpinned"
+        } else {
+            "Pinned"
+        }
+    }
+
+    fn execute(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        if self.undo {
+            client.unpin_status(&status.id)?;
+        } else {
+            client.pin_status(&status.id)?;
+        }
+        Ok(true)
+    }
+}
+
+/// Deletes one of the user's own statuses.
+pub struct DeleteAction;
+
+impl StatusAction for DeleteAction {
+    fn name(&self) -> &str {
+        "Delete"
+    }
+
+    fn done_message(&self) -> &str {
+        "Deleted"
+    }
+
+    fn execute(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        client.delete_status(&status.id)?;
+        Ok(true)
+    }
+}
+
+/// Which accounts an AccountsAction lists.
+#[derive(Clone, Copy)]
+pub enum AccountsKind {
+    FavouritedBy,
+    RebloggedBy,
+}
+
+/// Lists the accounts that favourited or boosted a status.
+pub struct AccountsAction {
+    kind: AccountsKind,
+    /// The label, with how many accounts there are.
+    name: String,
+    tx: UiMsgSender,
+    /// Avatars are only shown if they are already in the cache.
+    cache: Arc<WebImageCache>,
+}
+
+impl AccountsAction {
+    pub fn new(
+        kind: AccountsKind,
+        status: &Status,
+        tx: UiMsgSender,
+        cache: Arc<WebImageCache>,
+    ) -> Self {
+        let name = match kind {
+            AccountsKind::FavouritedBy => format!("Favourited by ({})", status.favourites_count),
+            AccountsKind::RebloggedBy => format!("Boosted by ({})", status.reblogs_count),
+        };
+        Self {
+            kind,
+            name,
+            tx,
+            cache,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self.kind {
+            AccountsKind::FavouritedBy => "Favourited by",
+            AccountsKind::RebloggedBy => "Boosted by",
+        }
+    }
+
+    fn accounts(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<Vec<Account>, Box<dyn Error + Send + Sync>> {
+        match self.kind {
+            AccountsKind::FavouritedBy => client.get_favourited_by(&status.id),
+            AccountsKind::RebloggedBy => client.get_reblogged_by(&status.id),
+        }
+    }
+}
+
+impl StatusAction for AccountsAction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn done_message(&self) -> &str {
+        ""
+    }
+
+    fn execute(
+        &self,
+        client: &Client,
+        status: &Status,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let accounts = self.accounts(client, status)?;
+        let (screen, events) =
+            AccountListScreen::new(&self.tx, &self.cache, self.title().into(), accounts);
+        self.tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+        // the list is only looked at, so there is nothing to wait for but
+        // the user leaving it
+        _ = events.recv();
+        // ignore send errors, the ui may have already closed
+        _ = self.tx.send(UiMsg::PopScreen);
+        // nothing was changed, so there is nothing to tell the user
+        Ok(false)
+    }
+}
+
+/// The actions that can be taken on a status, in the order they are listed.
+/// The status should be the one shown, not a boost of it.
+pub fn status_actions(
+    client: &Client,
+    tx: &UiMsgSender,
+    cache: &Arc<WebImageCache>,
+    status: &Status,
+) -> Vec<Box<dyn StatusAction>> {
+    let own = client
+        .logged_in_account()
+        .map_or(false, |account| account.id == status.account.id);
+    let mut actions: Vec<Box<dyn StatusAction>> = vec![
+        Box::new(ReplyAction),
+        Box::new(FavouriteAction {
+            undo: status.favourited,
+        }),
+        Box::new(ReblogAction {
+            undo: status.reblogged,
+        }),
+        Box::new(BookmarkAction {
+            undo: status.bookmarked || client.is_bookmarked(&status.id),
+        }),
+        Box::new(AccountsAction::new(
+            AccountsKind::FavouritedBy,
+            status,
+            tx.clone(),
+            cache.clone(),
+        )),
+        Box::new(AccountsAction::new(
+            AccountsKind::RebloggedBy,
+            status,
+            tx.clone(),
+            cache.clone(),
+        )),
+    ];
+    // only the user's own statuses can be pinned, and only they say if
+    // they are
+    if own {
+        actions.push(Box::new(PinAction {
+            undo: status.pinned.unwrap_or(false),
+        }));
+        actions.push(Box::new(DeleteAction));
+    }
+    actions
+}
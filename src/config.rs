@@ -0,0 +1,303 @@
+use std::{error::Error, fs::File};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ui::citro2d::color32;
+
+/// Where the settings are saved between launches.
+static CONFIG_PATH: &str = "/toot-3d-config.json";
+
+/// How far the circle pad must be pushed before it scrolls, unless changed in
+/// the settings.
+pub const DEFAULT_CIRCLE_PAD_DEADZONE: i16 = 20;
+
+/// The scale of normal text, unless changed in the settings. Screens ask for
+/// text at this scale, and the UI adjusts it to the chosen font scale.
+pub const DEFAULT_FONT_SCALE: f32 = 0.5;
+
+/// The user's time zone, in minutes east of UTC, unless changed in the
+/// settings. The 3DS doesn't know its time zone, so this has to be asked for.
+pub const DEFAULT_TIMEZONE_OFFSET: i32 = 0;
+
+/// How often to check for new notifications, unless changed in the config.
+pub const DEFAULT_NOTIFICATION_POLL_SECS: u64 = 60;
+
+/// Size of the buffer given to the socket service, unless changed in the
+/// settings. Larger buffers use more memory, but cope better with large
+/// responses.
+pub const DEFAULT_SOC_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// The settings chosen by the user, and limits on the work done in the
+/// background: how large images can be decoded, so that they don't use up
+/// all of the memory, and how often to check for notifications. The New 3DS
+/// has more memory, so it allows higher image limits.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct Config {
+    /// The largest width or height of an image that will be decoded.
+    pub max_image_dimension: u32,
+    /// The most memory that decoding an image may use, in bytes.
+    pub max_image_alloc: u64,
+    /// True when running on a New 3DS. This is checked on every launch,
+    /// rather than saved, as the SD card may be moved to another model.
+    #[serde(skip)]
+    pub new_3ds: bool,
+    /// How long to wait between checks for notifications, in seconds.
+    pub notification_poll_secs: u64,
+    pub theme: Theme,
+    pub circle_pad_deadzone: i16,
+    pub font_scale: f32,
+    /// The user's time zone, in minutes east of UTC.
+    pub timezone_offset: i32,
+    /// Size of the buffer given to the socket service, in bytes. It is only
+    /// given when the app starts, so changes apply after a restart.
+    pub soc_buffer_size: usize,
+}
+
+impl Config {
+    /// The smallest limits that can be chosen.
+    pub const MIN_IMAGE_DIMENSION: u32 = 128;
+    pub const MIN_IMAGE_ALLOC: u64 = 1024 * 1024;
+
+    /// The smallest and largest socket buffers that can be chosen.
+    pub const MIN_SOC_BUFFER_SIZE: usize = 256 * 1024;
+    pub const MAX_SOC_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+    /// The default limits for the given model.
+    pub const fn new(new_3ds: bool) -> Self {
+        if new_3ds {
+            Self {
+                max_image_dimension: 1024,
+                max_image_alloc: 8 * 1024 * 1024,
+                new_3ds,
+                notification_poll_secs: DEFAULT_NOTIFICATION_POLL_SECS,
+                theme: Theme::Dark,
+                circle_pad_deadzone: DEFAULT_CIRCLE_PAD_DEADZONE,
+                font_scale: DEFAULT_FONT_SCALE,
+                timezone_offset: DEFAULT_TIMEZONE_OFFSET,
+                soc_buffer_size: DEFAULT_SOC_BUFFER_SIZE,
+            }
+        } else {
+            Self {
+                max_image_dimension: 512,
+                max_image_alloc: 4 * 1024 * 1024,
+                new_3ds,
+                notification_poll_secs: DEFAULT_NOTIFICATION_POLL_SECS,
+                theme: Theme::Dark,
+                circle_pad_deadzone: DEFAULT_CIRCLE_PAD_DEADZONE,
+                font_scale: DEFAULT_FONT_SCALE,
+                timezone_offset: DEFAULT_TIMEZONE_OFFSET,
+                soc_buffer_size: DEFAULT_SOC_BUFFER_SIZE,
+            }
+        }
+    }
+
+    /// Load the saved settings. If there aren't any, or they can't be read,
+    /// the defaults for the given model are returned.
+    pub fn load(new_3ds: bool) -> Self {
+        let saved = File::open(CONFIG_PATH)
+            .ok()
+            .and_then(|file| serde_json::from_reader::<_, Self>(file).ok());
+        match saved {
+            Some(mut config) => {
+                config.new_3ds = new_3ds;
+                // the settings may have been saved on a New 3DS
+                config.max_image_dimension = config
+                    .max_image_dimension
+                    .min(config.image_dimension_limit());
+                config.max_image_alloc = config.max_image_alloc.min(config.image_alloc_limit());
+                config.soc_buffer_size = config
+                    .soc_buffer_size
+                    .clamp(Self::MIN_SOC_BUFFER_SIZE, Self::MAX_SOC_BUFFER_SIZE);
+                config
+            }
+            None => Self::new(new_3ds),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let file = File::create(CONFIG_PATH)?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// The largest image dimension that can be chosen. Textures can't be
+    /// larger than 1024 pixels, and the Old 3DS is kept to its default.
+    pub const fn image_dimension_limit(&self) -> u32 {
+        if self.new_3ds {
+            1024
+        } else {
+            512
+        }
+    }
+
+    /// The most image memory that can be chosen. The Old 3DS is kept to its
+    /// default.
+    pub const fn image_alloc_limit(&self) -> u64 {
+        if self.new_3ds {
+            16 * 1024 * 1024
+        } else {
+            4 * 1024 * 1024
+        }
+    }
+}
+
+/// A named set of colors for the user interface.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    Oled,
+    Solarized,
+    Nord,
+}
+
+impl Theme {
+    const ALL: [Theme; 5] = [
+        Theme::Dark,
+        Theme::Light,
+        Theme::Oled,
+        Theme::Solarized,
+        Theme::Nord,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+            Theme::Oled => "OLED",
+            Theme::Solarized => "Solarized",
+            Theme::Nord => "Nord",
+        }
+    }
+
+    /// The theme after this one, wrapping around at the end.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&theme| theme == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// The theme before this one, wrapping around at the start.
+    pub fn previous(self) -> Self {
+        let index = Self::ALL.iter().position(|&theme| theme == self).unwrap();
+        Self::ALL[(index + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// The colors used to draw screens, as given by color32.
+#[derive(Clone, Copy)]
+pub struct ThemeColors {
+    /// The background at the top of the screen.
+    pub background: u32,
+    /// The background at the bottom of the screen, blended from the top.
+    pub background_bottom: u32,
+    /// Most text.
+    pub foreground: u32,
+    /// Borders, highlights, and the loading bar.
+    pub accent: u32,
+    /// Hints and other less important text.
+    pub muted: u32,
+    pub error: u32,
+    pub success: u32,
+    /// Behind the selected item of a list.
+    pub selection: u32,
+    /// Code in statuses.
+    pub code: u32,
+    /// Behind code in statuses.
+    pub code_background: u32,
+    /// Struck-out text in statuses.
+    pub strikethrough: u32,
+    /// Mentions and hashtags in statuses.
+    pub mention: u32,
+    /// Other links in statuses.
+    pub link: u32,
+    /// The hashtags listed below statuses.
+    pub tag: u32,
+}
+
+impl ThemeColors {
+    pub const fn from_theme(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark => Self {
+                background: color32(10, 10, 20, 255),
+                background_bottom: color32(0, 0, 5, 255),
+                foreground: color32(255, 255, 255, 255),
+                accent: color32(85, 85, 255, 255),
+                muted: color32(170, 170, 170, 255),
+                error: color32(255, 85, 85, 255),
+                success: color32(85, 255, 85, 255),
+                selection: color32(40, 40, 60, 255),
+                code: color32(180, 200, 180, 255),
+                code_background: color32(64, 64, 64, 255),
+                strikethrough: color32(128, 128, 128, 255),
+                mention: color32(140, 170, 255, 255),
+                link: color32(85, 200, 255, 255),
+                tag: color32(100, 149, 237, 255),
+            },
+            Theme::Light => Self {
+                background: color32(255, 255, 255, 255),
+                background_bottom: color32(255, 255, 255, 255),
+                foreground: color32(20, 20, 20, 255),
+                accent: color32(40, 80, 200, 255),
+                muted: color32(110, 110, 110, 255),
+                error: color32(200, 40, 40, 255),
+                success: color32(30, 140, 30, 255),
+                selection: color32(210, 215, 235, 255),
+                code: color32(40, 100, 40, 255),
+                code_background: color32(230, 230, 230, 255),
+                strikethrough: color32(150, 150, 150, 255),
+                mention: color32(40, 80, 200, 255),
+                link: color32(0, 110, 170, 255),
+                tag: color32(50, 90, 190, 255),
+            },
+            Theme::Oled => Self {
+                background: color32(0, 0, 0, 255),
+                background_bottom: color32(0, 0, 0, 255),
+                foreground: color32(255, 255, 255, 255),
+                accent: color32(0, 200, 255, 255),
+                muted: color32(200, 200, 200, 255),
+                error: color32(255, 60, 60, 255),
+                success: color32(60, 255, 60, 255),
+                selection: color32(50, 50, 50, 255),
+                code: color32(180, 200, 180, 255),
+                code_background: color32(40, 40, 40, 255),
+                strikethrough: color32(128, 128, 128, 255),
+                mention: color32(140, 170, 255, 255),
+                link: color32(0, 200, 255, 255),
+                tag: color32(100, 149, 237, 255),
+            },
+            Theme::Solarized => Self {
+                background: color32(0, 43, 54, 255),
+                background_bottom: color32(0, 43, 54, 255),
+                foreground: color32(147, 161, 161, 255),
+                accent: color32(38, 139, 210, 255),
+                muted: color32(101, 123, 131, 255),
+                error: color32(220, 50, 47, 255),
+                success: color32(133, 153, 0, 255),
+                selection: color32(7, 54, 66, 255),
+                code: color32(133, 153, 0, 255),
+                code_background: color32(7, 54, 66, 255),
+                strikethrough: color32(88, 110, 117, 255),
+                mention: color32(108, 113, 196, 255),
+                link: color32(42, 161, 152, 255),
+                tag: color32(38, 139, 210, 255),
+            },
+            Theme::Nord => Self {
+                background: color32(46, 52, 64, 255),
+                background_bottom: color32(46, 52, 64, 255),
+                foreground: color32(236, 239, 244, 255),
+                accent: color32(136, 192, 208, 255),
+                muted: color32(129, 140, 160, 255),
+                error: color32(191, 97, 106, 255),
+                success: color32(163, 190, 140, 255),
+                selection: color32(67, 76, 94, 255),
+                code: color32(163, 190, 140, 255),
+                code_background: color32(59, 66, 82, 255),
+                strikethrough: color32(97, 110, 136, 255),
+                mention: color32(129, 161, 193, 255),
+                link: color32(136, 192, 208, 255),
+                tag: color32(94, 129, 172, 255),
+            },
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::{error::Error, fmt::Display};
+
+/// An error, along with what was being done when it happened.
+#[derive(Debug)]
+pub struct ContextError {
+    context: String,
+    source: Box<dyn Error + Send + Sync>,
+}
+
+impl Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.context)
+    }
+}
+
+impl Error for ContextError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Adds context to the error of a Result.
+pub trait ResultExt<T> {
+    fn context(self, context: &str) -> Result<T, ContextError>;
+
+    /// Like context, but the context is only worked out if there is an error.
+    fn with_context<F>(self, f: F) -> Result<T, ContextError>
+    where
+        F: FnOnce() -> String;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn context(self, context: &str) -> Result<T, ContextError> {
+        self.with_context(|| context.into())
+    }
+
+    fn with_context<F>(self, f: F) -> Result<T, ContextError>
+    where
+        F: FnOnce() -> String,
+    {
+        self.map_err(|e| ContextError {
+            context: f(),
+            source: e.into(),
+        })
+    }
+}
+
+/// Describe an error and everything that caused it, one per line, with the
+/// outermost first.
+pub fn error_chain(e: &(dyn Error + 'static)) -> String {
+    let mut result = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        result.push('\n');
+        result.push_str(&e.to_string());
+        source = e.source();
+    }
+    result
+}
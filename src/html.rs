@@ -0,0 +1,222 @@
+use std::error::Error;
+
+use quick_xml::events::{BytesStart, Event};
+use serde::{Deserialize, Serialize};
+
+/// How a span of text should be displayed.
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SpanKind {
+    Normal,
+    Bold,
+    Italic,
+    Code,
+    Strikethrough,
+    /// A mention of an account, with the URL of its profile.
+    Mention(String),
+    /// A hashtag, with the URL of its timeline.
+    Hashtag(String),
+    /// A link, with the URL it points to.
+    Link(String),
+    /// Text that should not be shown, like the scheme of a shortened URL.
+    Invisible,
+    /// A custom emoji with the given shortcode, shown as EMOJI_PLACEHOLDER.
+    Emoji(String),
+}
+
+/// Stands in for a custom emoji in text, so that it takes up space when wrapping.
+pub const EMOJI_PLACEHOLDER: char = '\u{fffc}';
+
+/// A piece of text with a single style.
+pub struct HtmlSpan {
+    pub text: String,
+    pub kind: SpanKind,
+}
+
+impl HtmlSpan {
+    /// Create a span of unstyled text.
+    pub fn plain(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            kind: SpanKind::Normal,
+        }
+    }
+}
+
+/// An element that has been opened but not yet closed.
+struct OpenElement {
+    name: Vec<u8>,
+    kind: SpanKind,
+    /// If true, the text inside has been truncated and needs an ellipsis.
+    ellipsis: bool,
+}
+
+fn attribute(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref() == name)
+        .and_then(|attr| attr.unescape_value().ok())
+        .map(|value| value.into_owned())
+}
+
+fn has_class(e: &BytesStart, class: &str) -> bool {
+    match attribute(e, b"class") {
+        Some(classes) => classes.split_whitespace().any(|c| c == class),
+        None => false,
+    }
+}
+
+fn push_text(spans: &mut Vec<HtmlSpan>, text: &str, kind: &SpanKind) {
+    // merge with the previous span if it looks the same
+    if let Some(last) = spans.last_mut() {
+        if last.kind == *kind {
+            last.text.push_str(text);
+            return;
+        }
+    }
+    spans.push(HtmlSpan {
+        text: text.into(),
+        kind: kind.clone(),
+    });
+}
+
+/// Parse the HTML used in statuses into styled spans of text.
+pub fn parse(html: &str) -> Result<Vec<HtmlSpan>, Box<dyn Error + Send + Sync>> {
+    let mut reader = quick_xml::reader::Reader::from_str(html);
+    reader.check_end_names(false);
+    let mut spans = vec![];
+    let mut stack: Vec<OpenElement> = vec![];
+
+    loop {
+        let current = match stack.last() {
+            Some(element) => element.kind.clone(),
+            None => SpanKind::Normal,
+        };
+
+        match reader.read_event()? {
+            Event::Eof => break,
+
+            // br is a void element, so it may never be closed
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"br" => {
+                push_text(&mut spans, "\n", &current);
+            }
+
+            Event::Start(e) => {
+                let mut ellipsis = false;
+                let kind = match e.name().as_ref() {
+                    // invisible text stays invisible, whatever is inside it
+                    _ if current == SpanKind::Invisible => SpanKind::Invisible,
+                    // preformatted text is shown as written, so tags inside don't change it
+                    _ if stack.iter().any(|element| element.name == b"pre") => current,
+                    b"a" => {
+                        let href = attribute(&e, b"href").unwrap_or_default();
+                        if has_class(&e, "hashtag") {
+                            SpanKind::Hashtag(href)
+                        } else if has_class(&e, "mention") {
+                            SpanKind::Mention(href)
+                        } else {
+                            SpanKind::Link(href)
+                        }
+                    }
+                    b"span" if has_class(&e, "invisible") => SpanKind::Invisible,
+                    b"span" => {
+                        ellipsis = has_class(&e, "ellipsis");
+                        current
+                    }
+                    b"strong" | b"b" => SpanKind::Bold,
+                    b"em" | b"i" => SpanKind::Italic,
+                    b"code" | b"pre" => SpanKind::Code,
+                    b"s" | b"del" => SpanKind::Strikethrough,
+                    b"blockquote" => {
+                        push_text(&mut spans, "> ", &current);
+                        SpanKind::Italic
+                    }
+                    _ => current,
+                };
+                stack.push(OpenElement {
+                    name: e.name().as_ref().to_vec(),
+                    kind,
+                    ellipsis,
+                });
+            }
+
+            Event::End(e) => {
+                // close the most recent matching element, and anything left open inside it
+                if let Some(index) = stack
+                    .iter()
+                    .rposition(|element| element.name == e.name().as_ref())
+                {
+                    for element in stack.drain(index..).rev() {
+                        if element.ellipsis {
+                            push_text(&mut spans, "\u{2026}", &element.kind);
+                        }
+                    }
+                }
+                if matches!(e.name().as_ref(), b"p" | b"blockquote" | b"pre") {
+                    push_text(&mut spans, "\n", &SpanKind::Normal);
+                }
+            }
+
+            Event::Text(e) => {
+                push_text(&mut spans, &e.unescape()?, &current);
+            }
+
+            _ => {}
+        }
+    }
+
+    Ok(spans)
+}
+
+/// Parse HTML, replacing each :shortcode: for which is_emoji returns true
+/// with an emoji span.
+pub fn parse_with_emojis<F>(
+    html: &str,
+    is_emoji: F,
+) -> Result<Vec<HtmlSpan>, Box<dyn Error + Send + Sync>>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut spans = vec![];
+    for span in parse(html)? {
+        // shortcodes in code are meant to be read literally
+        if matches!(span.kind, SpanKind::Invisible | SpanKind::Code) {
+            push_text(&mut spans, &span.text, &span.kind);
+            continue;
+        }
+        let mut rest = span.text.as_str();
+        while let Some(start) = rest.find(':') {
+            let after = &rest[start + 1..];
+            let end = match after.find(':') {
+                Some(end) => end,
+                None => break,
+            };
+            let shortcode = &after[..end];
+            if is_emoji(shortcode) {
+                push_text(&mut spans, &rest[..start], &span.kind);
+                push_text(
+                    &mut spans,
+                    &EMOJI_PLACEHOLDER.to_string(),
+                    &SpanKind::Emoji(shortcode.into()),
+                );
+                rest = &after[end + 1..];
+            } else {
+                // the closing colon may start another shortcode
+                push_text(&mut spans, &rest[..start + 1 + end], &span.kind);
+                rest = &after[end..];
+            }
+        }
+        if !rest.is_empty() {
+            push_text(&mut spans, rest, &span.kind);
+        }
+    }
+    Ok(spans)
+}
+
+/// Parse HTML into plain text, leaving out invisible text.
+pub fn to_plain_text(html: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    Ok(parse(html)?
+        .into_iter()
+        .filter(|span| span.kind != SpanKind::Invisible)
+        .map(|span| span.text)
+        .collect())
+}
@@ -1,35 +1,492 @@
-use std::{error::Error, sync::Arc, thread::spawn};
+use std::{error::Error, sync::Arc, thread::spawn, time::Duration};
 
+use actions::status_actions;
+use config::Config;
 use ctru::prelude::*;
-use net::curl;
+use error::error_chain;
+use net::{curl, Client, NotificationPoller, StatusPoster};
 use ui::{
     citro2d::Citro2d,
-    screen::{ErrorScreen, TimelineScreen},
-    LogicImgPool, Ui, UiMsg, UiMsgSender,
+    get_input,
+    screen::{
+        ActionMenuEvent, ActionMenuScreen, ComposerEvent, ComposerScreen, ComposerUpdate,
+        DirectMessageEvent, DirectMessageScreen, DirectMessagesScreen, ErrorEvent, ErrorScreen,
+        HashtagTimelineEvent, HashtagTimelineScreen, HashtagTimelineUpdate, ProfileEditEvent,
+        ProfileEditScreen, ProfileField, ProfileFieldUpdate, QrScreen, ScheduledStatusesEvent,
+        ScheduledStatusesScreen, ScheduledStatusesUpdate, SettingsEvent, SettingsScreen,
+        TimelineEvent, TimelineScreen, TimelineUpdate, TrendingStatusesEvent,
+        TrendingStatusesScreen, TrendingStatusesUpdate,
+    },
+    EmptyScreen, KeyboardKind, LogicImgPool, ScreenId, Ui, UiMsg, UiMsgSender, WebImageCache,
 };
 
+mod actions;
+mod config;
+mod error;
+mod html;
 mod net;
 mod types;
 mod ui;
+mod util;
+
+/// Briefly tell the user that something went wrong, for errors that don't
+/// need to close the current screen.
+fn toast_error(tx: &UiMsgSender, what: &str, e: &(dyn Error + 'static)) {
+    // ignore send errors, the ui may have already closed
+    _ = tx.send(UiMsg::Toast(format!("{}: {}", what, error_chain(e))));
+}
+
+fn handle_direct_message_events(
+    client: &Client,
+    tx: &UiMsgSender,
+    events: std::sync::mpsc::Receiver<DirectMessageEvent>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    while let Ok(event) = events.recv() {
+        match event {
+            DirectMessageEvent::Back => break,
+            DirectMessageEvent::OpenThread(id) => {
+                let (screen, events) = DirectMessageScreen::new(client, tx.clone(), &id)?;
+                tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+                handle_direct_message_events(client, tx, events)?;
+            }
+            DirectMessageEvent::Reply { id, acct } => {
+                // stay on the messages, so the user can try again
+                if let Err(e) = client.reply_direct(&id, &acct) {
+                    toast_error(tx, "Couldn't send the reply", &*e);
+                }
+            }
+        }
+    }
+    // ignore send errors, the ui may have already closed
+    _ = tx.send(UiMsg::PopScreen);
+    Ok(())
+}
+
+fn handle_scheduled_statuses_events(
+    client: &Client,
+    tx: &UiMsgSender,
+    events: std::sync::mpsc::Receiver<ScheduledStatusesEvent>,
+    updates: std::sync::mpsc::Sender<ScheduledStatusesUpdate>,
+) {
+    while let Ok(event) = events.recv() {
+        // stay on the list if something fails, so the user can try again
+        match event {
+            ScheduledStatusesEvent::Back => break,
+            ScheduledStatusesEvent::Schedule => match client.scheduled_toot() {
+                Ok(Some(_)) => {}
+                // the user changed their mind, so the list is the same
+                Ok(None) => continue,
+                Err(e) => {
+                    toast_error(tx, "Couldn't schedule the post", &*e);
+                    continue;
+                }
+            },
+            ScheduledStatusesEvent::Cancel(id) => {
+                if let Err(e) = client.cancel_scheduled_status(&id) {
+                    toast_error(tx, "Couldn't cancel the post", &*e);
+                    continue;
+                }
+            }
+        }
+        // show the list as the server now has it
+        match client.get_scheduled_statuses() {
+            Ok(statuses) => {
+                // ignore send errors, the screen may have already closed
+                _ = updates.send(ScheduledStatusesUpdate::new(tx, statuses));
+            }
+            Err(e) => toast_error(tx, "Couldn't reload the scheduled posts", &*e),
+        }
+    }
+    // ignore send errors, the ui may have already closed
+    _ = tx.send(UiMsg::PopScreen);
+}
+
+fn handle_profile_edit_events(
+    client: &Client,
+    cache: &WebImageCache,
+    tx: &UiMsgSender,
+    events: std::sync::mpsc::Receiver<ProfileEditEvent>,
+    updates: std::sync::mpsc::Sender<ProfileFieldUpdate>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    while let Ok(event) = events.recv() {
+        match event {
+            ProfileEditEvent::Back => break,
+            ProfileEditEvent::Edit(field) => {
+                let (hint, kind) = match field {
+                    ProfileField::DisplayName => ("Display name", KeyboardKind::Qwerty),
+                    ProfileField::Note => ("Bio", KeyboardKind::Normal),
+                };
+                match get_input(tx, hint, kind, true) {
+                    Ok(value) => {
+                        // ignore send errors, the screen may have already closed
+                        _ = updates.send(ProfileFieldUpdate::new(tx, field, value));
+                    }
+                    // cancelling the keyboard leaves the field as it was
+                    Err(e) if e.is_cancelled() => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            ProfileEditEvent::Save { display_name, note } => {
+                match client.update_credentials(cache, &display_name, &note) {
+                    Ok(_) => break,
+                    // the server says what was wrong, such as a name that is
+                    // too long, so show it and let the user fix it
+                    Err(e) => toast_error(tx, "Couldn't save the profile", &*e),
+                }
+            }
+        }
+    }
+    // ignore send errors, the ui may have already closed
+    _ = tx.send(UiMsg::PopScreen);
+    Ok(())
+}
+
+fn handle_trending_statuses_events(
+    client: &Client,
+    tx: &UiMsgSender,
+    events: std::sync::mpsc::Receiver<TrendingStatusesEvent>,
+    updates: std::sync::mpsc::Sender<TrendingStatusesUpdate>,
+) {
+    while let Ok(event) = events.recv() {
+        match event {
+            TrendingStatusesEvent::Back => break,
+            TrendingStatusesEvent::LoadMore(offset) => {
+                let update = client
+                    .get_trending_statuses(None, Some(offset.to_string()))
+                    .and_then(|statuses| TrendingStatusesUpdate::new(tx, statuses));
+                // keep what is already shown, and let the user try again
+                let update = update.unwrap_or_else(|e| {
+                    toast_error(tx, "Couldn't load more toots", &*e);
+                    TrendingStatusesUpdate::default()
+                });
+                // ignore send errors, the screen may have already closed
+                _ = updates.send(update);
+            }
+        }
+    }
+    // ignore send errors, the ui may have already closed
+    _ = tx.send(UiMsg::PopScreen);
+}
+
+fn handle_hashtag_timeline_events(
+    client: &Client,
+    tx: &UiMsgSender,
+    hashtag: &str,
+    events: std::sync::mpsc::Receiver<HashtagTimelineEvent>,
+    updates: std::sync::mpsc::Sender<HashtagTimelineUpdate>,
+) {
+    while let Ok(event) = events.recv() {
+        match event {
+            HashtagTimelineEvent::Back => break,
+            HashtagTimelineEvent::LoadMore(max_id) => {
+                let update = client
+                    .get_hashtag_timeline(hashtag, Some(max_id.clone()))
+                    .and_then(|statuses| HashtagTimelineUpdate::new(tx, statuses));
+                // keep what is already shown, and let the user try again
+                let update = update.unwrap_or_else(|e| {
+                    toast_error(tx, "Couldn't load more toots", &*e);
+                    HashtagTimelineUpdate::retry(max_id)
+                });
+                // ignore send errors, the screen may have already closed
+                _ = updates.send(update);
+            }
+        }
+    }
+    // ignore send errors, the ui may have already closed
+    _ = tx.send(UiMsg::PopScreen);
+}
+
+fn handle_composer_events(
+    poster: &StatusPoster,
+    tx: &UiMsgSender,
+    id: ScreenId,
+    events: std::sync::mpsc::Receiver<ComposerEvent>,
+    updates: std::sync::mpsc::Sender<ComposerUpdate>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    while let Ok(event) = events.recv() {
+        match event {
+            ComposerEvent::Back => break,
+            ComposerEvent::Edit => {
+                match get_input(tx, "Toot to post?", KeyboardKind::Normal, true) {
+                    Ok(status) => {
+                        // ignore send errors, the screen may have already closed
+                        _ = updates.send(ComposerUpdate::new(tx, status));
+                    }
+                    // cancelling the keyboard leaves the toot as it was
+                    Err(e) if e.is_cancelled() => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            ComposerEvent::Post { status, reply_to } => {
+                poster.post(&status, reply_to.as_deref())?;
+                break;
+            }
+        }
+    }
+    // ignore send errors, the ui may have already closed
+    _ = tx.send(UiMsg::CloseScreen(id));
+    Ok(())
+}
+
+/// Pass on the settings that the timeline uses.
+fn update_timeline_settings(updates: &std::sync::mpsc::Sender<TimelineUpdate>, config: &Config) {
+    let deadzone = config.circle_pad_deadzone;
+    // ignore send errors, the timeline may have already closed
+    _ = updates.send(TimelineUpdate::CirclePadDeadzone(deadzone));
+    _ = updates.send(TimelineUpdate::TimezoneOffset(config.timezone_offset));
+}
+
+/// Let the user choose something to do to a status, then do it. Returns true
+/// if something was done, as the status may look different now.
+fn open_actions(
+    client: &Client,
+    cache: &Arc<WebImageCache>,
+    tx: &UiMsgSender,
+    id: &str,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let status = client.get_status(id)?;
+    // act on the original status for boosts
+    let status = status.effective_status();
+    let actions = status_actions(client, tx, cache, status);
+    let names = actions.iter().map(|action| action.name().into()).collect();
+    let (screen, events) = ActionMenuScreen::new(tx.clone(), names);
+    tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+    let event = events.recv();
+    // ignore send errors, the ui may have already closed
+    _ = tx.send(UiMsg::PopScreen);
+    if let Ok(ActionMenuEvent::Choose(index)) = event {
+        let action = &actions[index];
+        if action.execute(client, status)? {
+            // ignore send errors, the ui may have already closed
+            _ = tx.send(UiMsg::Toast(action.done_message().into()));
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Open the composer whenever the user presses the quick toot shortcut. Runs
+/// on its own thread, as the logic thread may be waiting on another screen.
+/// Its screens are tagged, so that the logic thread pushing and popping its
+/// own screens at the same time doesn't close them.
+fn quick_toot_main(poster: StatusPoster, tx: UiMsgSender, requests: std::sync::mpsc::Receiver<()>) {
+    while let Ok(()) = requests.recv() {
+        let (screen, events, updates) = ComposerScreen::new(tx.clone(), None);
+        let id = ScreenId::unique();
+        // ignore send errors, the ui may have already closed
+        _ = tx.send(UiMsg::PushTaggedScreen(id, Box::new(screen)));
+        if let Err(e) = handle_composer_events(&poster, &tx, id, events, updates) {
+            let (screen, rx) = ErrorScreen::new(error_chain(&*e), tx.clone(), None);
+            let id = ScreenId::unique();
+            _ = tx.send(UiMsg::PushTaggedScreen(id, Box::new(screen)));
+            // nothing to retry, so quitting just closes the error
+            _ = rx.recv();
+            _ = tx.send(UiMsg::CloseScreen(id));
+        }
+        // ignore presses made while the composer was open
+        while requests.try_recv().is_ok() {}
+    }
+}
+
+fn notification_poll_main(mut poller: NotificationPoller, tx: UiMsgSender, interval: Duration) {
+    // cURL handles can't be sent between threads, so this one is made here
+    let escaper = curl::Easy::new();
+    // there is no notifications screen to read them on, so this counts every
+    // notification since the app started and is never reset
+    let mut unread = 0;
+    loop {
+        std::thread::sleep(interval);
+        if poller.is_closed() {
+            break;
+        }
+        // the badge is only a hint, so if checking fails, try again next time
+        if let Ok(count) = poller.poll(&escaper) {
+            if count == 0 {
+                continue;
+            }
+            unread += count;
+            // stop if the ui has closed
+            if tx.send(UiMsg::SetNotificationBadge(unread)).is_err() {
+                break;
+            }
+        }
+    }
+}
 
 fn logic_main(tx: UiMsgSender) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // the New 3DS has more memory, so it can decode larger images
+    let mut config = Config::load(check_new_3ds().unwrap_or(false));
+
     // need the socket service open, or we'll not have socket access
-    let _soc = Soc::init()?;
+    let _soc = Soc::init_with_buffer_size(config.soc_buffer_size)?;
     // initialize cURL globals
     let _global = curl::Global::new();
 
     let pool = LogicImgPool::new(tx.clone());
-    let client = net::Client::new(tx.clone(), pool.clone())?;
+    let client = Client::new(tx.clone(), pool.clone())?;
+
+    let cache = Arc::new(WebImageCache::new(config));
 
-    let cache = Arc::new(ui::WebImageCache::new());
+    let (quick_toot, quick_toot_requests) = std::sync::mpsc::channel();
+    tx.send(UiMsg::SetQuickToot(quick_toot)).unwrap();
+    let poster = client.status_poster();
+    let quick_toot_tx = tx.clone();
+    spawn(move || quick_toot_main(poster, quick_toot_tx, quick_toot_requests));
 
-    tx.send(UiMsg::SetScreen(Box::new(TimelineScreen::new(
-        &cache,
-        &client,
-        &pool,
-        tx.clone(),
-    )?)))
-    .unwrap();
+    let poller = client.notification_poller();
+    let poll_tx = tx.clone();
+    let poll_interval = Duration::from_secs(config.notification_poll_secs);
+    spawn(move || notification_poll_main(poller, poll_tx, poll_interval));
+
+    // the settings screen starts from whichever theme is being shown
+    tx.send(UiMsg::SetTheme(config.theme)).unwrap();
+    tx.send(UiMsg::SetFontScale(config.font_scale)).unwrap();
+
+    let (screen, mut events, mut updates) =
+        TimelineScreen::new(&cache, &client, &pool, tx.clone(), config.font_scale)?;
+    tx.send(UiMsg::SetScreen(Box::new(screen))).unwrap();
+    update_timeline_settings(&updates, &config);
+
+    // handle requests from the timeline until it is closed
+    while let Ok(event) = events.recv() {
+        match event {
+            TimelineEvent::DismissAnnouncement(id) => {
+                // the announcement is already gone from the screen, so the
+                // worst that can happen is it coming back next time
+                if let Err(e) = client.dismiss_announcement(&id) {
+                    toast_error(&tx, "Couldn't dismiss the announcement", &*e);
+                }
+            }
+            TimelineEvent::OpenDirectMessages => {
+                let (screen, events) = DirectMessagesScreen::new(&client, tx.clone())?;
+                tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+                handle_direct_message_events(&client, &tx, events)?;
+            }
+            TimelineEvent::OpenSettings => {
+                let (screen, events) = SettingsScreen::new(
+                    tx.clone(),
+                    config,
+                    cache.entry_count(),
+                    pool.live_image_count(),
+                    client.retriever().stats(),
+                );
+                tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+                // the theme and font scale are already shown, so just remember
+                // them for next time
+                if let Ok(SettingsEvent::Back(new_config)) = events.recv() {
+                    config = new_config;
+                    cache.set_config(new_config);
+                    update_timeline_settings(&updates, &config);
+                    if let Err(e) = config.save() {
+                        toast_error(&tx, "Couldn't save the settings", &*e);
+                    }
+                }
+                // ignore send errors, the ui may have already closed
+                _ = tx.send(UiMsg::PopScreen);
+            }
+            TimelineEvent::ToggleReaction {
+                announcement_id,
+                name,
+                add,
+            } => {
+                let result = if add {
+                    client.add_announcement_reaction(&announcement_id, &name)
+                } else {
+                    client.remove_announcement_reaction(&announcement_id, &name)
+                };
+                if let Err(e) = result {
+                    toast_error(&tx, "Couldn't change the reaction", &*e);
+                    // ignore send errors, the timeline may have already closed
+                    _ = updates.send(TimelineUpdate::UndoReaction {
+                        announcement_id,
+                        name,
+                    });
+                }
+            }
+            TimelineEvent::OpenActions(id) => match open_actions(&client, &cache, &tx, &id) {
+                Ok(true) => {
+                    // reload the timeline to show the change, keeping the
+                    // user where they were
+                    let (screen, new_events, new_updates) = TimelineScreen::new_focused(
+                        &cache,
+                        &client,
+                        &pool,
+                        tx.clone(),
+                        config.font_scale,
+                        Some(&id),
+                    )?;
+                    tx.send(UiMsg::SetScreen(Box::new(screen))).unwrap();
+                    events = new_events;
+                    updates = new_updates;
+                    update_timeline_settings(&updates, &config);
+                }
+                Ok(false) => {}
+                Err(e) => toast_error(&tx, "Couldn't act on the toot", &*e),
+            },
+            TimelineEvent::OpenLink(url) => {
+                // there is nowhere to show profiles or hashtags yet, so let
+                // the user open them on their phone instead
+                let (screen, on_continue) = QrScreen::new(
+                    &url,
+                    "Scan with your phone to open the link\nPress A to go back",
+                    pool.clone(),
+                    tx.clone(),
+                    None,
+                )?;
+                tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+                _ = on_continue.recv();
+                // ignore send errors, the ui may have already closed
+                _ = tx.send(UiMsg::PopScreen);
+            }
+            TimelineEvent::OpenTrending => {
+                // instances can turn trends off, in which case this fails
+                let first = client
+                    .get_trending_statuses(None, None)
+                    .and_then(|statuses| TrendingStatusesUpdate::new(&tx, statuses));
+                match first {
+                    Ok(first) => {
+                        let (screen, events, updates) = TrendingStatusesScreen::new(&tx, first);
+                        tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+                        handle_trending_statuses_events(&client, &tx, events, updates);
+                    }
+                    Err(e) => toast_error(&tx, "Couldn't load trending toots", &*e),
+                }
+            }
+            TimelineEvent::OpenScheduled => match client.get_scheduled_statuses() {
+                Ok(statuses) => {
+                    let first = ScheduledStatusesUpdate::new(&tx, statuses);
+                    let (screen, events, updates) = ScheduledStatusesScreen::new(&tx, first);
+                    tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+                    handle_scheduled_statuses_events(&client, &tx, events, updates);
+                }
+                Err(e) => toast_error(&tx, "Couldn't load scheduled posts", &*e),
+            },
+            TimelineEvent::OpenHashtag(hashtag) => {
+                let first = client
+                    .get_hashtag_timeline(&hashtag, None)
+                    .and_then(|statuses| HashtagTimelineUpdate::new(&tx, statuses));
+                match first {
+                    Ok(first) => {
+                        let (screen, events, updates) =
+                            HashtagTimelineScreen::new(&tx, &hashtag, first);
+                        tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+                        handle_hashtag_timeline_events(&client, &tx, &hashtag, events, updates);
+                    }
+                    Err(e) => toast_error(&tx, &format!("Couldn't load #{}", hashtag), &*e),
+                }
+            }
+            TimelineEvent::EditProfile => {
+                let account = client.get_own_account()?;
+                let (screen, events, updates) = ProfileEditScreen::new(&account, tx.clone())?;
+                tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+                handle_profile_edit_events(&client, &cache, &tx, events, updates)?;
+            }
+        }
+        if client.rate_limit_remaining() == Some(0) {
+            // ignore send errors, the ui may have already closed
+            _ = tx.send(UiMsg::Toast("Rate limited".into()));
+        }
+    }
 
     client.close();
 
@@ -58,17 +515,31 @@ fn main() {
     }
 
     let (tx, rx) = std::sync::mpsc::channel();
-    let mut ui = Ui::new(&c2d, rx).unwrap();
+    let mut ui = Ui::new(&c2d, tx.clone(), rx).unwrap();
 
     let logic = spawn(move || {
         let tx = tx;
-        if let Err(e) = logic_main(tx.clone()) {
-            let (screen, rx) = ErrorScreen::new(format!("{}", e), tx.clone());
+        // most errors are network trouble, so let the user start over
+        while let Err(e) = logic_main(tx.clone()) {
+            let retry_tx = tx.clone();
+            let (screen, rx) = ErrorScreen::new(
+                error_chain(&*e),
+                tx.clone(),
+                Some(Box::new(move || {
+                    // clear the error while we try again
+                    _ = retry_tx.send(UiMsg::SetScreen(Box::new(EmptyScreen)));
+                })),
+            );
             tx.send(UiMsg::SetScreen(Box::new(screen))).unwrap();
-            // wait for screen to request close
-            rx.recv().unwrap();
-            // send quit message
-            tx.send(UiMsg::Quit).unwrap();
+            // wait for screen to request close or retry
+            match rx.recv().unwrap() {
+                ErrorEvent::Retry(action) => action(),
+                ErrorEvent::Quit => {
+                    // send quit message
+                    tx.send(UiMsg::Quit).unwrap();
+                    break;
+                }
+            }
         }
         // if no error, just keep screen open
     });
@@ -79,6 +550,9 @@ fn main() {
         }
     }
 
+    // drop the ui so that the logic thread sees its screens close
+    drop(ui);
+
     // TODO handling quit request from main thread
     logic.join().unwrap();
 }
@@ -1,15 +1,38 @@
-use std::{borrow::Cow, error::Error, fs::File};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    fs::File,
+    sync::{Arc, Mutex},
+};
 
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    types::{Account, Application, Status, Token},
-    ui::{get_input, screen::QrScreen, LogicImgPool, UiMsg, UiMsgSender},
+    error::ResultExt,
+    types::{
+        Account, Announcement, Application, Context, CustomEmoji, InstanceDirectoryEntry,
+        InstanceDirectoryList, InstanceV2, Notification, PushAlerts, ScheduledStatus,
+        SearchResults, Status, Token, Visibility, WebPushSubscription,
+    },
+    ui::{
+        get_input, get_number_input,
+        screen::{InstancePickerEvent, InstancePickerScreen, QrScreen},
+        EmptyScreen, KeyboardKind, LogicImgPool, UiMsg, UiMsgSender, WebImageCache,
+    },
 };
 
-use super::retriever::{HttpError, Method, Request, Retriever};
+use super::{
+    curl::Easy,
+    oauth,
+    retriever::{
+        HttpError, MastodonError, Method, Request, RequestAuth, Retriever, RetrieverHandle,
+    },
+};
 
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize)]
 struct ClientData {
     instance: String,
     id: String,
@@ -19,28 +42,211 @@ struct ClientData {
 
 static CLIENT_DATA_PATH: &str = "/toot-3d.json";
 
+/// Where the list of popular instances is saved after it is first downloaded.
+static INSTANCE_LIST_PATH: &str = "/toot-3d-instances.json";
+static INSTANCE_LIST_URL: &str =
+    "https://instances.social/api/1.0/instances/list?sort_by=active_users&count=10";
+
 static REDIRECT_URI: &str = "urn:ietf:wg:oauth:2.0:oob";
 static SCOPES: &str = "read write push";
 static WEBSITE: &str = "https://github.com/spazzylemons/toot-3d";
 
+/// How many bookmarks to download. Only the most recent ones are shown as
+/// bookmarked, as the rest are on pages linked from the response headers.
+const BOOKMARKS_LIMIT: u32 = 40;
+
+/// How long to show the authorization QR code before opening the keyboard.
+const QR_TIMEOUT_FRAMES: u32 = 60 * 60;
+
+/// The token stopped working partway through, and logging in again failed.
+#[derive(Debug)]
+pub struct SessionExpiredError(Box<dyn Error + Send + Sync>);
+
+impl Display for SessionExpiredError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Your login has expired, and logging in again failed")
+    }
+}
+
+impl Error for SessionExpiredError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.0)
+    }
+}
+
+/// A request needed the user to be logged in, but the client was made with
+/// Client::new_unauthenticated.
+#[derive(Debug)]
+pub struct LoginRequiredError;
+
+impl Display for LoginRequiredError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "You need to log in to do that")
+    }
+}
+
+impl Error for LoginRequiredError {}
+
+/// The HTTP status of an error response, if the error is one.
+fn error_status(e: &(dyn Error + Send + Sync + 'static)) -> Option<u16> {
+    // the server may or may not explain what went wrong
+    match e.downcast_ref::<MastodonError>() {
+        Some(e) => Some(e.status),
+        None => e.downcast_ref::<HttpError>().map(|e| e.0),
+    }
+}
+
+/// True if the server refused a request because of the token.
+fn is_unauthorized(e: &(dyn Error + Send + Sync + 'static)) -> bool {
+    error_status(e) == Some(401)
+}
+
 pub struct Client {
     retriever: Retriever,
     data: ClientData,
+    /// The account we are logged in as, saved when verifying credentials.
+    logged_in_account: Mutex<Option<Arc<Account>>>,
+    /// The instance's custom emojis by shortcode, saved after the first request.
+    custom_emojis: Mutex<Option<Arc<HashMap<String, CustomEmoji>>>>,
+    /// The IDs of statuses the logged-in account has bookmarked.
+    bookmarked_ids: Mutex<HashSet<String>>,
+    /// Accounts looked up by their handles, by handle.
+    accounts_by_acct: Mutex<HashMap<String, Arc<Account>>>,
+    /// True while logging in again after the token stops working, and
+    /// before the first login is done. Refused requests aren't retried then.
+    refreshing: Mutex<bool>,
+    /// True if made without logging in, so only public endpoints can be used.
+    anonymous: bool,
+    /// Only used to escape URL parameters, never to make requests.
+    escaper: Easy,
 
     tx: UiMsgSender,
     pool: LogicImgPool,
 }
 
+/// Posts statuses from another thread than the one the Client is on.
+pub struct StatusPoster {
+    retriever: RetrieverHandle,
+    url: String,
+}
+
+impl StatusPoster {
+    pub fn post(
+        &self,
+        status: &str,
+        in_reply_to_id: Option<&str>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut fields = vec![("status", status.as_bytes().to_vec())];
+        if let Some(id) = in_reply_to_id {
+            fields.push(("in_reply_to_id", id.as_bytes().to_vec()));
+        }
+        let rx = self.retriever.request(vec![Request {
+            method: Method::Post(fields),
+            url: self.url.clone(),
+            max_size: None,
+            auth: RequestAuth::UseGlobal,
+        }]);
+        rx.recv()??;
+        Ok(())
+    }
+}
+
+/// Pops the top screen when dropped, so that a screen pushed while logging
+/// in is gone however logging in ends.
+struct PopScreenGuard<'a>(&'a UiMsgSender);
+
+impl Drop for PopScreenGuard<'_> {
+    fn drop(&mut self) {
+        // ignore send errors, the ui may have already closed
+        _ = self.0.send(UiMsg::PopScreen);
+    }
+}
+
+/// Checks for new notifications from another thread.
+pub struct NotificationPoller {
+    retriever: RetrieverHandle,
+    instance: String,
+    /// True once a check has succeeded, so there is something to count from.
+    primed: bool,
+    /// The newest notification seen so far, to only ask for newer ones.
+    last_notification_id: Option<String>,
+}
+
+impl NotificationPoller {
+    /// Count the notifications that arrived since the last check, with a
+    /// single request. The first successful check only finds the newest
+    /// notification to count from, so it always returns zero. The escaper is
+    /// only used to escape URL parameters.
+    pub fn poll(&mut self, escaper: &Easy) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let params = match (&self.last_notification_id, self.primed) {
+            (Some(id), _) => vec![("since_id", Cow::from(id.as_str()))],
+            // there were no notifications at all when primed, so every one
+            // is new
+            (None, true) => vec![],
+            (None, false) => vec![("limit", Cow::from("1"))],
+        };
+        let url = api_url(escaper, &self.instance, 1, "notifications", &params)?;
+        let rx = self.retriever.request(vec![Request {
+            method: Method::Get,
+            url,
+            max_size: None,
+            auth: RequestAuth::UseGlobal,
+        }]);
+        let notifications = serde_json::from_slice::<Vec<Notification>>(&rx.recv()??)?;
+        let primed = std::mem::replace(&mut self.primed, true);
+        // notifications are sent newest first
+        if let Some(newest) = notifications.first() {
+            self.last_notification_id = Some(newest.id.clone());
+        }
+        Ok(if primed { notifications.len() } else { 0 })
+    }
+
+    /// True once the client has closed, so no more checks can be made.
+    pub fn is_closed(&self) -> bool {
+        self.retriever.is_closed()
+    }
+}
+
+/// The URL of an endpoint in the given version of the API on an instance,
+/// with the given query parameters escaped and added.
+fn api_url(
+    escaper: &Easy,
+    instance: &str,
+    version: u8,
+    path: &str,
+    params: &[(&str, Cow<str>)],
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let mut url = format!("https://{}/api/v{}/{}", instance, version, path);
+    let mut sep = '?';
+    for (name, value) in params {
+        url.push(sep);
+        sep = '&';
+        url.push_str(name);
+        url.push('=');
+        url.push_str(escaper.escape(value)?.as_ref());
+    }
+    Ok(url)
+}
+
 trait AsFormParts {
     fn as_form_parts<'a>(&'a self, name: &'static str, fields: &mut Vec<(&'static str, &'a [u8])>);
 }
 
-impl<T> AsFormParts for T
+impl AsFormParts for str {
+    fn as_form_parts<'a>(&'a self, name: &'static str, fields: &mut Vec<(&'static str, &'a [u8])>) {
+        fields.push((name, self.as_bytes()));
+    }
+}
+
+// the field is left out entirely if there is no value
+impl<T> AsFormParts for Option<T>
 where
-    T: AsRef<[u8]>,
+    T: AsRef<str>,
 {
     fn as_form_parts<'a>(&'a self, name: &'static str, fields: &mut Vec<(&'static str, &'a [u8])>) {
-        fields.push((name, self.as_ref()));
+        if let Some(t) = self {
+            t.as_ref().as_form_parts(name, fields);
+        }
     }
 }
 
@@ -57,6 +263,12 @@ where
 
 trait AsQueryParams {
     fn as_query_params<'a>(&'a self) -> Vec<Cow<'a, str>>;
+
+    /// If true, the parameter name is given a [] suffix, as the API expects
+    /// for arrays.
+    fn is_array(&self) -> bool {
+        false
+    }
 }
 
 impl AsQueryParams for str {
@@ -77,40 +289,172 @@ where
     }
 }
 
+impl AsQueryParams for [String] {
+    fn as_query_params<'a>(&'a self) -> Vec<Cow<'a, str>> {
+        self.iter().map(|s| Cow::Borrowed(s.as_str())).collect()
+    }
+
+    fn is_array(&self) -> bool {
+        true
+    }
+}
+
+impl AsQueryParams for bool {
+    fn as_query_params<'a>(&'a self) -> Vec<Cow<'a, str>> {
+        vec![Cow::Borrowed(if *self { "true" } else { "false" })]
+    }
+}
+
+impl AsQueryParams for u64 {
+    fn as_query_params<'a>(&'a self) -> Vec<Cow<'a, str>> {
+        vec![Cow::Owned(self.to_string())]
+    }
+}
+
 macro_rules! get_gen {
-    ($path:literal $name:ident ($($param:ident: $typ:ty,)*) -> $ret:ty) => {
+    (@api $version:literal, $auth:expr; $path:literal $name:ident ($($param:ident: $typ:ty,)*) -> $ret:ty) => {
+        get_gen! { @api $version, $auth; $path $name [] ($($param: $typ,)*) -> $ret }
+    };
+
+    // parameters in square brackets are substituted into the path
+    (@api $version:literal, $auth:expr; $path:literal $name:ident [$($path_param:ident: $path_typ:ty,)*] ($($param:ident: $typ:ty,)*) -> $ret:ty) => {
         #[allow(unused_mut)]
         #[allow(unused_variables)]
-        fn $name(&self, $($param: $typ,)*) -> Result<$ret, Box<dyn Error + Send + Sync>> {
-            let mut url = format!("https://{}/api/v1/{}", self.data.instance, $path);
-            let mut sep = '?';
+        fn $name(&self, $($path_param: $path_typ,)* $($param: $typ,)*) -> Result<$ret, Box<dyn Error + Send + Sync>> {
+            let path = format!($path, $($path_param = self.escape($path_param)?,)*);
+            let mut params: Vec<(&str, Cow<str>)> = vec![];
             $(
+                // the API expects a [] suffix on the names of arrays
+                let name = if $param.is_array() {
+                    concat!(stringify!($param), "[]")
+                } else {
+                    stringify!($param)
+                };
                 for p in $param.as_query_params() {
-                    url.push(sep);
-                    sep = '&';
-                    url.push_str(&urlencoding::encode(&p));
+                    params.push((name, p));
                 }
             )*
-            let buffer = self.get(&url)?;
+            let url = self.build_api_url($version, &path, &params)?;
+            let buffer = self.get_with_auth(&url, $auth)?;
             Ok(serde_json::from_slice(&buffer)?)
         }
-    }
+    };
+
+    // endpoints marked public are requested without the token
+    (public $($rest:tt)*) => {
+        get_gen! { @api 1, RequestAuth::None; $($rest)* }
+    };
+
+    ($($rest:tt)*) => {
+        get_gen! { @api 1, RequestAuth::UseGlobal; $($rest)* }
+    };
+}
+
+/// Like get_gen, but for endpoints that are only in version 2 of the API.
+macro_rules! get_gen_v2 {
+    (public $($rest:tt)*) => {
+        get_gen! { @api 2, RequestAuth::None; $($rest)* }
+    };
+
+    ($($rest:tt)*) => {
+        get_gen! { @api 2, RequestAuth::UseGlobal; $($rest)* }
+    };
 }
 
 macro_rules! post_gen {
+    // the response body is ignored if there is no return type
+    ($path:literal $name:ident ($($param:ident: $typ:ty,)*) -> ()) => {
+        post_gen_ignore! { $path $name [] ($($param: $typ,)*) }
+    };
+
+    ($path:literal $name:ident [$($path_param:ident: $path_typ:ty,)*] ($($param:ident: $typ:ty,)*) -> ()) => {
+        post_gen_ignore! { $path $name [$($path_param: $path_typ,)*] ($($param: $typ,)*) }
+    };
+
     ($path:literal $name:ident ($($param:ident: $typ:ty,)*) -> $ret:ty) => {
-        fn $name(&self, $($param: $typ,)*) -> Result<$ret, Box<dyn Error + Send + Sync>> {
+        post_gen! { $path $name [] ($($param: $typ,)*) -> $ret }
+    };
+
+    // parameters in square brackets are substituted into the path
+    ($path:literal $name:ident [$($path_param:ident: $path_typ:ty,)*] ($($param:ident: $typ:ty,)*) -> $ret:ty) => {
+        #[allow(unused_mut)]
+        fn $name(&self, $($path_param: $path_typ,)* $($param: $typ,)*) -> Result<$ret, Box<dyn Error + Send + Sync>> {
             let mut fields = vec![];
             $(
                 $param.as_form_parts(stringify!($param), &mut fields);
             )*
-            let url = format!("https://{}/api/v1/{}", self.data.instance, $path);
+            let path = format!($path, $($path_param = self.escape($path_param)?,)*);
+            let url = self.build_api_url(1, &path, &[])?;
             let buffer = self.post(&url, &fields)?;
             Ok(serde_json::from_slice(&buffer)?)
         }
     }
 }
 
+/// Like post_gen, but the response body is thrown away.
+macro_rules! post_gen_ignore {
+    ($path:literal $name:ident ($($param:ident: $typ:ty,)*)) => {
+        post_gen_ignore! { $path $name [] ($($param: $typ,)*) }
+    };
+
+    ($path:literal $name:ident [$($path_param:ident: $path_typ:ty,)*] ($($param:ident: $typ:ty,)*)) => {
+        #[allow(unused_mut)]
+        fn $name(&self, $($path_param: $path_typ,)* $($param: $typ,)*) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let mut fields = vec![];
+            $(
+                $param.as_form_parts(stringify!($param), &mut fields);
+            )*
+            let path = format!($path, $($path_param = self.escape($path_param)?,)*);
+            let url = self.build_api_url(1, &path, &[])?;
+            self.post(&url, &fields)?;
+            Ok(())
+        }
+    }
+}
+
+macro_rules! delete_gen {
+    // the response body is ignored if there is no return type
+    ($path:literal $name:ident [$($path_param:ident: $path_typ:ty,)*] -> ()) => {
+        fn $name(&self, $($path_param: $path_typ,)*) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let path = format!($path, $($path_param = self.escape($path_param)?,)*);
+            let url = self.build_api_url(1, &path, &[])?;
+            self.delete(&url)?;
+            Ok(())
+        }
+    };
+
+    ($path:literal $name:ident [$($path_param:ident: $path_typ:ty,)*] -> $ret:ty) => {
+        fn $name(&self, $($path_param: $path_typ,)*) -> Result<$ret, Box<dyn Error + Send + Sync>> {
+            let path = format!($path, $($path_param = self.escape($path_param)?,)*);
+            let url = self.build_api_url(1, &path, &[])?;
+            let buffer = self.delete(&url)?;
+            Ok(serde_json::from_slice(&buffer)?)
+        }
+    }
+}
+
+/// Like delete_gen, but for PUT requests.
+macro_rules! put_gen {
+    // the response body is ignored if there is no return type
+    ($path:literal $name:ident [$($path_param:ident: $path_typ:ty,)*] -> ()) => {
+        fn $name(&self, $($path_param: $path_typ,)*) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let path = format!($path, $($path_param = self.escape($path_param)?,)*);
+            let url = self.build_api_url(1, &path, &[])?;
+            self.put(&url)?;
+            Ok(())
+        }
+    };
+
+    ($path:literal $name:ident [$($path_param:ident: $path_typ:ty,)*] -> $ret:ty) => {
+        fn $name(&self, $($path_param: $path_typ,)*) -> Result<$ret, Box<dyn Error + Send + Sync>> {
+            let path = format!($path, $($path_param = self.escape($path_param)?,)*);
+            let url = self.build_api_url(1, &path, &[])?;
+            let buffer = self.put(&url)?;
+            Ok(serde_json::from_slice(&buffer)?)
+        }
+    }
+}
+
 impl Client {
     pub fn new(tx: UiMsgSender, pool: LogicImgPool) -> Result<Self, Box<dyn Error + Send + Sync>> {
         // attempt to load the client data
@@ -128,6 +472,14 @@ impl Client {
         let mut result = Self {
             retriever,
             data,
+            logged_in_account: Mutex::new(None),
+            custom_emojis: Mutex::new(None),
+            bookmarked_ids: Mutex::new(HashSet::new()),
+            accounts_by_acct: Mutex::new(HashMap::new()),
+            // verify needs to see refused requests while logging in
+            refreshing: Mutex::new(true),
+            anonymous: false,
+            escaper: Easy::new(),
             tx,
             pool,
         };
@@ -147,15 +499,118 @@ impl Client {
         if !result.verify()? {
             return Err("Unauthorized".into());
         }
+        *result.refreshing.get_mut().unwrap() = false;
+        // bookmarks are only marked on statuses, so go without them if they
+        // can't be loaded
+        _ = result.refresh_bookmarks();
         Ok(result)
     }
 
+    /// Make a client for browsing an instance without logging in. Only the
+    /// endpoints documented as working without logging in can be used, and
+    /// the rest fail with LoginRequiredError without making a request.
+    pub fn new_unauthenticated(instance: &str, tx: UiMsgSender, pool: LogicImgPool) -> Self {
+        let data = ClientData {
+            instance: instance.into(),
+            ..Default::default()
+        };
+        let retriever = Retriever::new();
+        retriever.set_instance(data.instance.clone());
+        Self {
+            retriever,
+            data,
+            logged_in_account: Mutex::new(None),
+            custom_emojis: Mutex::new(None),
+            bookmarked_ids: Mutex::new(HashSet::new()),
+            accounts_by_acct: Mutex::new(HashMap::new()),
+            refreshing: Mutex::new(false),
+            anonymous: true,
+            escaper: Easy::new(),
+            tx,
+            pool,
+        }
+    }
+
+    /// False if the client was made without logging in.
+    pub fn is_logged_in(&self) -> bool {
+        !self.anonymous
+    }
+
+    /// Make a request. If the server refuses the token, log in again and
+    /// retry the request once.
+    fn send_request(&self, request: Request) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        if self.anonymous && matches!(request.auth, RequestAuth::UseGlobal) {
+            return Err(Box::new(LoginRequiredError));
+        }
+        // spread requests out when close to the rate limit, rather than
+        // running out and having them all refused until it resets
+        if self.retriever.should_backoff() {
+            if let Some(state) = self.retriever.rate_limit() {
+                let delay = state.delay();
+                if !delay.is_zero() {
+                    // let the user know why nothing is happening
+                    let message = format!("Rate limited, waiting {:.1}s", delay.as_secs_f32());
+                    // ignore send errors, the ui may have already closed
+                    _ = self.tx.send(UiMsg::Toast(message));
+                    std::thread::sleep(delay);
+                }
+            }
+        }
+        // only requests made with the token can be fixed by logging in again
+        let retry = matches!(request.auth, RequestAuth::UseGlobal).then(|| request.clone());
+        let result = self.retriever.request(vec![request]).recv().unwrap();
+        match (result, retry) {
+            (Err(e), Some(retry)) if is_unauthorized(&*e) && self.start_refresh() => {
+                let refreshed = self.refresh_token();
+                *self.refreshing.lock().unwrap() = false;
+                refreshed.map_err(SessionExpiredError)?;
+                self.retriever.request(vec![retry]).recv().unwrap()
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Mark the token as being refreshed. Returns false if it already is.
+    fn start_refresh(&self) -> bool {
+        let mut refreshing = self.refreshing.lock().unwrap();
+        !std::mem::replace(&mut *refreshing, true)
+    }
+
+    /// Log in again and save the new token, for when the old one stops
+    /// working partway through, such as when the user revokes it elsewhere.
+    fn refresh_token(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let token = self.request_token()?;
+        self.retriever.set_token(token.clone());
+        let data = ClientData {
+            token,
+            ..self.data.clone()
+        };
+        let file = File::create(CLIENT_DATA_PATH)?;
+        serde_json::to_writer(file, &data)?;
+        Ok(())
+    }
+
+    /// How many more requests can be made before the rate limit resets, if
+    /// the instance has said.
+    pub fn rate_limit_remaining(&self) -> Option<u32> {
+        self.retriever.rate_limit().map(|state| state.remaining)
+    }
+
     pub fn get(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
-        let rx = self.retriever.request(vec![Request {
+        self.get_with_auth(url, RequestAuth::UseGlobal)
+    }
+
+    pub fn get_with_auth(
+        &self,
+        url: &str,
+        auth: RequestAuth,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self.send_request(Request {
             method: Method::Get,
             url: url.into(),
-        }]);
-        rx.recv().unwrap()
+            max_size: None,
+            auth,
+        })
     }
 
     pub fn post(
@@ -163,7 +618,7 @@ impl Client {
         url: &str,
         fields: &[(&'static str, &[u8])],
     ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
-        let rx = self.retriever.request(vec![Request {
+        self.send_request(Request {
             method: Method::Post(
                 fields
                     .iter()
@@ -171,8 +626,82 @@ impl Client {
                     .collect(),
             ),
             url: url.into(),
-        }]);
-        rx.recv().unwrap()
+            max_size: None,
+            auth: RequestAuth::UseGlobal,
+        })
+    }
+
+    pub fn post_json(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self.send_request(Request {
+            method: Method::PostJson(serde_json::to_vec(body)?),
+            url: url.into(),
+            max_size: None,
+            auth: RequestAuth::UseGlobal,
+        })
+    }
+
+    pub fn patch_json(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self.send_request(Request {
+            method: Method::PatchJson(serde_json::to_vec(body)?),
+            url: url.into(),
+            max_size: None,
+            auth: RequestAuth::UseGlobal,
+        })
+    }
+
+    pub fn put(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self.send_request(Request {
+            method: Method::Put,
+            url: url.into(),
+            max_size: None,
+            auth: RequestAuth::UseGlobal,
+        })
+    }
+
+    pub fn put_json(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self.send_request(Request {
+            method: Method::PutJson(serde_json::to_vec(body)?),
+            url: url.into(),
+            max_size: None,
+            auth: RequestAuth::UseGlobal,
+        })
+    }
+
+    pub fn delete(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self.send_request(Request {
+            method: Method::Delete,
+            url: url.into(),
+            max_size: None,
+            auth: RequestAuth::UseGlobal,
+        })
+    }
+
+    /// The URL of an endpoint in the given version of the API, with the
+    /// given query parameters escaped and added.
+    fn build_api_url(
+        &self,
+        version: u8,
+        path: &str,
+        params: &[(&str, Cow<str>)],
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        api_url(&self.escaper, &self.data.instance, version, path, params)
+    }
+
+    /// Percent-encode a string for use in a URL, the same way cURL would.
+    pub fn escape(&self, s: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        Ok(self.escaper.escape(s)?.to_string())
     }
 
     get_gen! { "accounts/verify_credentials" verify_credentials() -> Account }
@@ -184,6 +713,25 @@ impl Client {
         limit: Option<String>,
     ) -> Vec<Status> }
 
+    get_gen! { "timelines/direct" direct_timeline(
+        max_id: Option<String>,
+        limit: Option<String>,
+    ) -> Vec<Status> }
+
+    get_gen! { "statuses/{id}" status[id: &str,]() -> Status }
+
+    get_gen! { public "accounts/lookup" account_lookup(acct: &str,) -> Account }
+
+    get_gen! { "bookmarks" bookmarks(
+        limit: Option<String>,
+    ) -> Vec<Status> }
+
+    get_gen! { "statuses/{id}/context" status_context[id: &str,]() -> Context }
+
+    get_gen! { "statuses/{id}/favourited_by" status_favourited_by[id: &str,]() -> Vec<Account> }
+
+    get_gen! { "statuses/{id}/reblogged_by" status_reblogged_by[id: &str,]() -> Vec<Account> }
+
     post_gen! { "apps" create_app(
         client_name: &str,
         redirect_uris: &str,
@@ -191,11 +739,86 @@ impl Client {
         website: &str,
     ) -> Application }
 
-    post_gen! { "statuses" post_status(status: &str,) -> () }
+    post_gen! { "statuses" post_status(status: &str,) -> Status }
+
+    post_gen! { "statuses" post_reply(
+        status: &str,
+        in_reply_to_id: &str,
+        visibility: &str,
+    ) -> Status }
+
+    post_gen! { "statuses/{id}/pin" status_pin[id: &str,]() -> Status }
+
+    post_gen! { "statuses/{id}/unpin" status_unpin[id: &str,]() -> Status }
+
+    post_gen! { "statuses/{id}/favourite" status_favourite[id: &str,]() -> Status }
+
+    post_gen! { "statuses/{id}/unfavourite" status_unfavourite[id: &str,]() -> Status }
+
+    post_gen! { "statuses/{id}/reblog" status_reblog[id: &str,]() -> Status }
+
+    post_gen! { "statuses/{id}/unreblog" status_unreblog[id: &str,]() -> Status }
+
+    post_gen! { "statuses/{id}/bookmark" status_bookmark[id: &str,]() -> Status }
+
+    post_gen! { "statuses/{id}/unbookmark" status_unbookmark[id: &str,]() -> Status }
+
+    // the deleted status is returned, with its source text so it can be redrafted
+    delete_gen! { "statuses/{id}" status_delete[id: &str,] -> Status }
+
+    post_gen! { "statuses" post_scheduled_status(
+        status: &str,
+        scheduled_at: &str,
+    ) -> ScheduledStatus }
+
+    get_gen! { "scheduled_statuses" scheduled_statuses() -> Vec<ScheduledStatus> }
+
+    // the response is an empty object
+    delete_gen! { "scheduled_statuses/{id}" scheduled_status_delete[id: &str,] -> () }
+
+    get_gen! { public "trends/statuses" trends_statuses(
+        limit: Option<String>,
+        offset: Option<String>,
+    ) -> Vec<Status> }
+
+    get_gen_v2! { "search" search(
+        q: &str,
+        resolve: bool,
+        limit: Option<String>,
+    ) -> SearchResults }
+
+    get_gen_v2! { public "instance" instance() -> InstanceV2 }
+
+    get_gen! { public "timelines/public" public_timeline(
+        local: bool,
+        limit: Option<String>,
+    ) -> Vec<Status> }
+
+    get_gen! { "timelines/tag/{hashtag}" tag_timeline[hashtag: &str,](
+        max_id: Option<String>,
+    ) -> Vec<Status> }
+
+    get_gen! { "announcements" announcements() -> Vec<Announcement> }
+
+    get_gen! { public "custom_emojis" custom_emojis() -> Vec<CustomEmoji> }
+
+    // the response is an empty object
+    post_gen! { "announcements/{id}/dismiss" announcement_dismiss[id: &str,]() -> () }
+
+    // the responses are empty objects
+    put_gen! { "announcements/{id}/reactions/{name}" announcement_react[id: &str, name: &str,] -> () }
+
+    delete_gen! { "announcements/{id}/reactions/{name}" announcement_unreact[id: &str, name: &str,] -> () }
+
+    get_gen! { "push/subscription" push_subscription() -> WebPushSubscription }
 
     fn authorize(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
-        self.data.instance = get_input(&self.tx, "Which instance?", true, false)?;
+        self.data.instance = self.pick_instance()?;
         self.retriever.set_instance(self.data.instance.clone());
+        // the authorization screens are pushed, so don't leave the picker under them
+        self.tx
+            .send(UiMsg::SetScreen(Box::new(EmptyScreen)))
+            .unwrap();
 
         let app = self.create_app("Toot 3D", REDIRECT_URI, SCOPES, WEBSITE)?;
         if app.client_id.is_none() || app.client_secret.is_none() {
@@ -209,37 +832,129 @@ impl Client {
         Ok(())
     }
 
+    /// Get a list of popular instances, downloading it if it hasn't been saved.
+    fn popular_instances(
+        &self,
+    ) -> Result<Vec<InstanceDirectoryEntry>, Box<dyn Error + Send + Sync>> {
+        if let Ok(file) = File::open(INSTANCE_LIST_PATH) {
+            if let Ok(list) = serde_json::from_reader::<_, InstanceDirectoryList>(file) {
+                return Ok(list.instances);
+            }
+        }
+        let buffer = self.get_with_auth(INSTANCE_LIST_URL, RequestAuth::None)?;
+        let list = serde_json::from_slice::<InstanceDirectoryList>(&buffer)?;
+        // saving the list is only to avoid downloading it again, so ignore errors
+        _ = std::fs::write(INSTANCE_LIST_PATH, &buffer);
+        Ok(list.instances)
+    }
+
+    /// Ask the user which instance to log in to.
+    fn pick_instance(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        // if we can't show a list, just ask for the instance
+        let instances = match self.popular_instances() {
+            Ok(instances) if !instances.is_empty() => instances,
+            _ => {
+                return Ok(get_input(
+                    &self.tx,
+                    "Which instance?",
+                    KeyboardKind::Qwerty,
+                    false,
+                )?)
+            }
+        };
+        loop {
+            let (screen, instance_picker_done) =
+                InstancePickerScreen::new(instances.clone(), self.tx.clone());
+            self.tx.send(UiMsg::SetScreen(Box::new(screen))).unwrap();
+            match instance_picker_done.recv()? {
+                InstancePickerEvent::Select(instance) => return Ok(instance),
+                InstancePickerEvent::Custom => {
+                    match get_input(&self.tx, "Which instance?", KeyboardKind::Qwerty, false) {
+                        Ok(instance) => return Ok(instance),
+                        // go back to the list
+                        Err(e) if e.is_cancelled() => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+        }
+    }
+
     fn verify(&self) -> Result<bool, Box<dyn Error + Send + Sync>> {
         match self.verify_credentials() {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                if let Some(HttpError(401)) = e.downcast_ref::<HttpError>() {
-                    Ok(false)
-                } else {
-                    Err(e)
-                }
+            Ok(account) => {
+                *self.logged_in_account.lock().unwrap() = Some(Arc::new(account));
+                Ok(true)
             }
+            Err(e) if is_unauthorized(&*e) => Ok(false),
+            Err(e) => Err(e),
         }
     }
 
-    fn obtain_token(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
-        // authorize user here
-        let request_url = format!(
-            concat!(
-                "https://{}/oauth/authorize?client_id={}",
-                "&scope=read+write+push",
-                "&redirect_uri=urn:ietf:wg:oauth:2.0:oob",
-                "&response_type=code",
-            ),
-            self.data.instance, self.data.id,
-        );
+    /// Show the authorization QR code, then ask for the code the user was
+    /// given. Some instances give numeric codes, so the number pad is shown
+    /// first. Cancelling it opens the full keyboard for codes with letters,
+    /// and cancelling that shows the QR code again.
+    fn get_auth_code(&self, verifier: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        loop {
+            // authorize user here
+            let request_url = format!(
+                concat!(
+                    "https://{}/oauth/authorize?client_id={}",
+                    "&scope=read+write+push",
+                    "&redirect_uri=urn:ietf:wg:oauth:2.0:oob",
+                    "&response_type=code",
+                    "&code_challenge={}",
+                    "&code_challenge_method=S256",
+                ),
+                self.data.instance,
+                self.data.id,
+                oauth::code_challenge(verifier),
+            );
+
+            let (screen, on_continue) = QrScreen::new(
+                &request_url,
+                "Scan with your phone to authorize\nThen enter the code below\nPress A to continue",
+                self.pool.clone(),
+                self.tx.clone(),
+                Some(QR_TIMEOUT_FRAMES),
+            )?;
+            // pushed rather than set, so that logging in again partway
+            // through goes back to where the user was
+            self.tx.send(UiMsg::PushScreen(Box::new(screen))).unwrap();
+            let _pop_qr = PopScreenGuard(&self.tx);
+            // give the user a chance to scan the code before opening the keyboard
+            on_continue.recv()?;
+
+            // the user will need to manually type the code in, but only once!
+            let input = match get_number_input(&self.tx, "Enter code, or cancel for letters") {
+                Err(e) if e.is_cancelled() => get_input(
+                    &self.tx,
+                    "Scan QR, authorize, and enter code",
+                    KeyboardKind::Qwerty,
+                    false,
+                ),
+                input => input,
+            };
+            match input {
+                Ok(auth_code) => return Ok(auth_code),
+                Err(e) if e.is_cancelled() => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 
-        let screen = QrScreen::new(request_url.as_bytes(), self.pool.clone())?;
-        self.tx.send(UiMsg::SetScreen(Box::new(screen))).unwrap();
-        self.tx.send(UiMsg::Flush).unwrap();
+    fn obtain_token(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.data.token = self.request_token()?;
+        self.retriever.set_token(self.data.token.clone());
+        Ok(())
+    }
 
-        // the user will need to manually type the code in, but only once!
-        let auth_code = get_input(&self.tx, "Scan QR, authorize, and enter code", true, false)?;
+    /// Ask the user to authorize the app, and get a new token.
+    fn request_token(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        // some instances require PKCE, so always use it
+        let verifier = oauth::generate_code_verifier()?;
+        let auth_code = self.get_auth_code(&verifier)?;
 
         // we do this one without a generated endpoint, because it is the only
         // time we need to access an oauth endpoint instead of an api endpoint
@@ -251,31 +966,446 @@ impl Client {
                 ("redirect_uri", REDIRECT_URI.as_bytes()),
                 ("grant_type", b"authorization_code"),
                 ("code", auth_code.as_bytes()),
+                ("code_verifier", verifier.as_bytes()),
                 ("scope", b"read write push"),
             ],
         )?;
 
         let token = serde_json::from_slice::<Token>(&buffer)?;
-        self.data.token = token.access_token;
-        self.retriever.set_token(self.data.token.clone());
+        Ok(token.access_token)
+    }
 
-        Ok(())
+    pub fn get_own_account(&self) -> Result<Account, Box<dyn Error + Send + Sync>> {
+        self.verify_credentials()
+    }
+
+    /// The account we are logged in as, without making a request.
+    pub fn logged_in_account(&self) -> Option<Arc<Account>> {
+        self.logged_in_account.lock().unwrap().clone()
+    }
+
+    /// The domain of the instance we are using.
+    pub fn logged_in_instance(&self) -> &str {
+        &self.data.instance
     }
 
     pub fn get_home_timeline(&self) -> Result<Vec<Status>, Box<dyn Error + Send + Sync>> {
-        self.home_timeline(None, None, None, None)
+        Ok(self
+            .home_timeline(None, None, None, None)
+            .context("couldn't load the home timeline")?)
+    }
+
+    pub fn get_announcements(&self) -> Result<Vec<Announcement>, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .announcements()
+            .context("couldn't load the announcements")?)
+    }
+
+    /// Get the instance's custom emojis by shortcode. They rarely change, so
+    /// they are only requested once. This works without logging in.
+    pub fn get_custom_emojis(
+        &self,
+    ) -> Result<Arc<HashMap<String, CustomEmoji>>, Box<dyn Error + Send + Sync>> {
+        let mut custom_emojis = self.custom_emojis.lock().unwrap();
+        if let Some(emojis) = &*custom_emojis {
+            return Ok(emojis.clone());
+        }
+        let emojis = Arc::new(
+            self.custom_emojis()?
+                .into_iter()
+                .map(|emoji| (emoji.shortcode.clone(), emoji))
+                .collect::<HashMap<_, _>>(),
+        );
+        *custom_emojis = Some(emojis.clone());
+        Ok(emojis)
+    }
+
+    /// Find an account from its handle, such as user@example.com, as when
+    /// only a mention is known. Accounts are only looked up once. Instances
+    /// without the lookup endpoint, or that don't know of the account yet,
+    /// are asked to find it with a search instead.
+    pub fn get_account_by_acct(
+        &self,
+        acct: &str,
+    ) -> Result<Arc<Account>, Box<dyn Error + Send + Sync>> {
+        let acct = acct.trim_start_matches('@');
+        if let Some(account) = self.accounts_by_acct.lock().unwrap().get(acct) {
+            return Ok(account.clone());
+        }
+        let account = match self.account_lookup(acct) {
+            Ok(account) => account,
+            Err(e) if error_status(e.as_ref()) == Some(404) => self
+                .search(acct, true, Some("1".into()))?
+                .accounts
+                .into_iter()
+                .next()
+                .ok_or_else(|| format!("couldn't find @{}", acct))?,
+            Err(e) => return Err(e),
+        };
+        let account = Arc::new(account);
+        self.accounts_by_acct
+            .lock()
+            .unwrap()
+            .insert(acct.into(), account.clone());
+        Ok(account)
+    }
+
+    pub fn dismiss_announcement(&self, id: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.announcement_dismiss(id)
+    }
+
+    /// React to an announcement. The name is either a Unicode emoji or the
+    /// shortcode of a custom emoji.
+    pub fn add_announcement_reaction(
+        &self,
+        announcement_id: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.announcement_react(announcement_id, name)
+    }
+
+    pub fn remove_announcement_reaction(
+        &self,
+        announcement_id: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.announcement_unreact(announcement_id, name)
+    }
+
+    /// The statuses that are popular right now. This works without logging in.
+    pub fn get_trending_statuses(
+        &self,
+        limit: Option<String>,
+        offset: Option<String>,
+    ) -> Result<Vec<Status>, Box<dyn Error + Send + Sync>> {
+        self.trends_statuses(limit, offset)
+    }
+
+    /// The latest public statuses, from only this instance if local is true
+    /// or from every instance it knows of if not. This works without logging
+    /// in.
+    pub fn get_public_timeline(
+        &self,
+        local: bool,
+    ) -> Result<Vec<Status>, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .public_timeline(local, None)
+            .context("couldn't load the public timeline")?)
+    }
+
+    /// The latest statuses with the given hashtag, older than the status with
+    /// the given ID if there is one. The token is sent, so that statuses the
+    /// user can see but that aren't public are included.
+    pub fn get_hashtag_timeline(
+        &self,
+        hashtag: &str,
+        max_id: Option<String>,
+    ) -> Result<Vec<Status>, Box<dyn Error + Send + Sync>> {
+        self.tag_timeline(hashtag, max_id)
+    }
+
+    /// Search for accounts, statuses, and hashtags. If resolve is true, the
+    /// instance looks up accounts and statuses it doesn't know of yet.
+    pub fn search_all(
+        &self,
+        query: &str,
+        resolve: bool,
+    ) -> Result<SearchResults, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .search(query, resolve, None)
+            .with_context(|| format!("couldn't search for {}", query))?)
+    }
+
+    /// Information about the instance, such as its rules and how many people
+    /// use it. This works without logging in.
+    pub fn get_instance_v2(&self) -> Result<InstanceV2, Box<dyn Error + Send + Sync>> {
+        self.instance()
+    }
+
+    pub fn get_direct_timeline(
+        &self,
+        max_id: Option<String>,
+        limit: Option<String>,
+    ) -> Result<Vec<Status>, Box<dyn Error + Send + Sync>> {
+        self.direct_timeline(max_id, limit)
+    }
+
+    pub fn get_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .status(id)
+            .with_context(|| format!("couldn't load status {}", id))?)
+    }
+
+    pub fn get_status_context(&self, id: &str) -> Result<Context, Box<dyn Error + Send + Sync>> {
+        self.status_context(id)
+    }
+
+    /// The accounts that favourited a status.
+    pub fn get_favourited_by(
+        &self,
+        status_id: &str,
+    ) -> Result<Vec<Account>, Box<dyn Error + Send + Sync>> {
+        self.status_favourited_by(status_id)
+    }
+
+    /// The accounts that boosted a status.
+    pub fn get_reblogged_by(
+        &self,
+        status_id: &str,
+    ) -> Result<Vec<Account>, Box<dyn Error + Send + Sync>> {
+        self.status_reblogged_by(status_id)
+    }
+
+    pub fn pin_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        self.status_pin(id)
+    }
+
+    pub fn unpin_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        self.status_unpin(id)
+    }
+
+    pub fn delete_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        self.status_delete(id)
+    }
+
+    pub fn favourite_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        self.status_favourite(id)
+    }
+
+    pub fn unfavourite_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        self.status_unfavourite(id)
+    }
+
+    /// Boost a status. The boost itself is returned, with the status inside.
+    pub fn reblog_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        self.status_reblog(id)
+    }
+
+    pub fn unreblog_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        self.status_unreblog(id)
+    }
+
+    pub fn get_bookmarks(&self) -> Result<Vec<Status>, Box<dyn Error + Send + Sync>> {
+        self.bookmarks(Some(BOOKMARKS_LIMIT.to_string()))
+    }
+
+    /// Download the bookmarks again, replacing the set of bookmarked IDs.
+    pub fn refresh_bookmarks(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let ids = self
+            .get_bookmarks()?
+            .into_iter()
+            .map(|status| status.id)
+            .collect();
+        *self.bookmarked_ids.lock().unwrap() = ids;
+        Ok(())
+    }
+
+    pub fn bookmark_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        let status = self.status_bookmark(id)?;
+        self.bookmarked_ids
+            .lock()
+            .unwrap()
+            .insert(status.id.clone());
+        Ok(status)
+    }
+
+    pub fn unbookmark_status(&self, id: &str) -> Result<Status, Box<dyn Error + Send + Sync>> {
+        let status = self.status_unbookmark(id)?;
+        self.bookmarked_ids.lock().unwrap().remove(&status.id);
+        Ok(status)
+    }
+
+    /// True if the status with the given ID is bookmarked, as of the last
+    /// refresh. Unlike Status::bookmarked, this works for statuses fetched
+    /// without authentication.
+    pub fn is_bookmarked(&self, id: &str) -> bool {
+        self.bookmarked_ids.lock().unwrap().contains(id)
+    }
+
+    /// Reply privately to a status, mentioning its author. Nothing is posted
+    /// if the user cancels.
+    pub fn reply_direct(&self, id: &str, acct: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.reply(id, acct, Visibility::Direct)?;
+        Ok(())
+    }
+
+    /// Reply to a status, mentioning its author. Returns false if the user
+    /// cancels, in which case nothing is posted.
+    pub fn reply(
+        &self,
+        id: &str,
+        acct: &str,
+        visibility: Visibility,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let message = match get_input(&self.tx, "Reply?", KeyboardKind::Normal, false) {
+            Ok(message) => message,
+            Err(e) if e.is_cancelled() => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        // the keyboard can't be given initial text, so add the mention here
+        self.post_reply(&format!("@{} {}", acct, message), id, visibility.as_str())?;
+        Ok(true)
     }
 
     pub fn basic_toot(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let message = get_input(&self.tx, "Toot to post?", false, false)?;
-        self.post_status(&message)
+        match get_input(&self.tx, "Toot to post?", KeyboardKind::Normal, false) {
+            Ok(message) => {
+                self.post_status(&message)?;
+                Ok(())
+            }
+            Err(e) if e.is_cancelled() => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Schedule a toot for later. Returns None if the user cancels.
+    pub fn scheduled_toot(&self) -> Result<Option<ScheduledStatus>, Box<dyn Error + Send + Sync>> {
+        let message = match get_input(&self.tx, "Toot to post?", KeyboardKind::Normal, false) {
+            Ok(message) => message,
+            Err(e) if e.is_cancelled() => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let time = match get_input(
+            &self.tx,
+            "Post when? (YYYY-MM-DD HH:MM, UTC)",
+            KeyboardKind::Qwerty,
+            false,
+        ) {
+            Ok(time) => time,
+            Err(e) if e.is_cancelled() => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let scheduled_at = DateTime::<Utc>::from_utc(
+            NaiveDateTime::parse_from_str(time.trim(), "%Y-%m-%d %H:%M")?,
+            Utc,
+        );
+        let status = self.post_scheduled_status(&message, &scheduled_at.to_rfc3339())?;
+        Ok(Some(status))
+    }
+
+    pub fn get_scheduled_statuses(
+        &self,
+    ) -> Result<Vec<ScheduledStatus>, Box<dyn Error + Send + Sync>> {
+        self.scheduled_statuses()
+    }
+
+    pub fn cancel_scheduled_status(&self, id: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.scheduled_status_delete(id)
+    }
+
+    /// Change the profile of the logged-in account. Images from the home
+    /// instance are evicted from the cache, as they may now be out of date.
+    pub fn update_credentials(
+        &self,
+        cache: &WebImageCache,
+        display_name: &str,
+        note: &str,
+    ) -> Result<Arc<Account>, Box<dyn Error + Send + Sync>> {
+        let buffer = self.patch_json(
+            &format!(
+                "https://{}/api/v1/accounts/update_credentials",
+                self.data.instance
+            ),
+            &serde_json::json!({
+                "display_name": display_name,
+                "note": note,
+            }),
+        )?;
+        let account = Arc::new(serde_json::from_slice::<Account>(&buffer)?);
+        cache.remove_pattern(&format!("https://{}/", self.data.instance));
+        // keep the saved account up to date with the changes
+        *self.logged_in_account.lock().unwrap() = Some(account.clone());
+        Ok(account)
+    }
+
+    fn push_subscription_url(&self) -> String {
+        format!("https://{}/api/v1/push/subscription", self.data.instance)
+    }
+
+    /// Subscribe to Web Push notifications, replacing any existing
+    /// subscription for this token. The keys are those of the push endpoint,
+    /// base64 encoded. Notifications can't be received while the app is
+    /// closed, so this is only useful along with a relay server.
+    pub fn subscribe_push(
+        &self,
+        endpoint: &str,
+        p256dh: &str,
+        auth: &str,
+        alerts: PushAlerts,
+    ) -> Result<WebPushSubscription, Box<dyn Error + Send + Sync>> {
+        let buffer = self.post_json(
+            &self.push_subscription_url(),
+            &serde_json::json!({
+                "subscription": {
+                    "endpoint": endpoint,
+                    "keys": {
+                        "p256dh": p256dh,
+                        "auth": auth,
+                    },
+                },
+                "data": {
+                    "alerts": alerts,
+                },
+            }),
+        )?;
+        Ok(serde_json::from_slice(&buffer)?)
+    }
+
+    pub fn get_push_subscription(
+        &self,
+    ) -> Result<WebPushSubscription, Box<dyn Error + Send + Sync>> {
+        self.push_subscription()
+    }
+
+    /// Change which notifications are pushed, keeping the same endpoint.
+    pub fn update_push_subscription(
+        &self,
+        alerts: PushAlerts,
+    ) -> Result<WebPushSubscription, Box<dyn Error + Send + Sync>> {
+        let buffer = self.put_json(
+            &self.push_subscription_url(),
+            &serde_json::json!({
+                "data": {
+                    "alerts": alerts,
+                },
+            }),
+        )?;
+        Ok(serde_json::from_slice(&buffer)?)
+    }
+
+    pub fn delete_push_subscription(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.delete(&self.push_subscription_url())?;
+        Ok(())
     }
 
     pub fn close(self) {
         self.retriever.close();
     }
 
+    pub fn status_poster(&self) -> StatusPoster {
+        StatusPoster {
+            retriever: self.retriever.handle(),
+            url: format!("https://{}/api/v1/statuses", self.data.instance),
+        }
+    }
+
+    /// Get a poller for checking notifications from another thread.
+    pub fn notification_poller(&self) -> NotificationPoller {
+        NotificationPoller {
+            retriever: self.retriever.handle(),
+            instance: self.data.instance.clone(),
+            primed: false,
+            last_notification_id: None,
+        }
+    }
+
     pub fn retriever(&self) -> &Retriever {
         &self.retriever
     }
 }
+
+fn assert_send<T: Send>(_: T) {}
+
+// API results, errors included, are sent between threads, so check that
+// they can be when compiling
+const _: fn(&Client) = |client| assert_send(client.get_home_timeline());
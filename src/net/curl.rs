@@ -26,6 +26,25 @@ impl Display for CurlError {
 
 impl Error for CurlError {}
 
+impl CurlError {
+    /// True if the transfer was stopped for exceeding the maximum file size.
+    pub fn is_file_size_exceeded(&self) -> bool {
+        self.0 == c::CURLcode_CURLE_FILESIZE_EXCEEDED
+    }
+}
+
+/// An error from reading a response header.
+#[derive(Debug)]
+pub struct CurlHeaderError(pub c::CURLHcode);
+
+impl Display for CurlHeaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to read response header (error {})", self.0)
+    }
+}
+
+impl Error for CurlHeaderError {}
+
 pub struct Global(());
 
 impl Global {
@@ -41,11 +60,43 @@ impl Drop for Global {
     }
 }
 
+/// The kind of information given to a debug callback.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DebugKind {
+    /// Informational text.
+    Text,
+    HeaderIn,
+    HeaderOut,
+    DataIn,
+    DataOut,
+    SslDataIn,
+    SslDataOut,
+}
+
+impl DebugKind {
+    fn from_raw(kind: c::curl_infotype) -> Option<Self> {
+        Some(match kind {
+            c::curl_infotype_CURLINFO_TEXT => Self::Text,
+            c::curl_infotype_CURLINFO_HEADER_IN => Self::HeaderIn,
+            c::curl_infotype_CURLINFO_HEADER_OUT => Self::HeaderOut,
+            c::curl_infotype_CURLINFO_DATA_IN => Self::DataIn,
+            c::curl_infotype_CURLINFO_DATA_OUT => Self::DataOut,
+            c::curl_infotype_CURLINFO_SSL_DATA_IN => Self::SslDataIn,
+            c::curl_infotype_CURLINFO_SSL_DATA_OUT => Self::SslDataOut,
+            _ => return None,
+        })
+    }
+}
+
+type DebugCallback = Box<dyn FnMut(DebugKind, &[u8])>;
+
 pub struct Easy {
     // reference to cURL easy session
     curl: *mut c::CURL,
     // pinned write buffer for getting response body
     write_buffer: Pin<Box<RefCell<Vec<u8>>>>,
+    // pinned debug callback, if one is set
+    debug_callback: Pin<Box<RefCell<Option<DebugCallback>>>>,
 }
 
 extern "C" fn write_callback(
@@ -61,6 +112,26 @@ extern "C" fn write_callback(
     nmemb
 }
 
+extern "C" fn debug_callback(
+    _handle: *mut c::CURL,
+    kind: c::curl_infotype,
+    data: *mut std::ffi::c_char,
+    size: usize,
+    userdata: *mut std::ffi::c_void,
+) -> std::ffi::c_int {
+    let callback = unsafe { &*(userdata as *const RefCell<Option<DebugCallback>>) };
+    let kind = match DebugKind::from_raw(kind) {
+        Some(kind) => kind,
+        None => return 0,
+    };
+    if let Some(callback) = callback.borrow_mut().as_mut() {
+        callback(kind, unsafe {
+            std::slice::from_raw_parts(data as *const u8, size)
+        });
+    }
+    0
+}
+
 impl Easy {
     pub fn new() -> Self {
         // get curl pointer
@@ -89,7 +160,72 @@ impl Easy {
                 write_buffer.as_ref().get_ref(),
             );
         }
-        Self { curl, write_buffer }
+        Self {
+            curl,
+            write_buffer,
+            debug_callback: Box::pin(RefCell::new(None)),
+        }
+    }
+
+    /// Turn cURL's debug output on or off. Unless a debug callback is set, the
+    /// output goes to stderr.
+    pub fn set_verbose(&self, on: bool) -> Result<(), CurlError> {
+        let res = unsafe {
+            c::curl_easy_setopt(
+                self.curl,
+                c::CURLoption_CURLOPT_VERBOSE,
+                std::ffi::c_long::from(on),
+            )
+        };
+        if res != c::CURLcode_CURLE_OK {
+            return Err(CurlError(res));
+        }
+        Ok(())
+    }
+
+    /// Receive debug output with the given function instead of stderr, or
+    /// return to stderr if None. Only used while verbose output is on.
+    pub fn set_debug_callback<F>(&self, callback: Option<F>) -> Result<(), CurlError>
+    where
+        F: FnMut(DebugKind, &[u8]) + 'static,
+    {
+        let enabled = callback.is_some();
+        *self.debug_callback.as_ref().get_ref().borrow_mut() =
+            callback.map(|f| Box::new(f) as DebugCallback);
+        let res = unsafe {
+            if enabled {
+                let res = c::curl_easy_setopt(
+                    self.curl,
+                    c::CURLoption_CURLOPT_DEBUGDATA,
+                    self.debug_callback.as_ref().get_ref(),
+                );
+                if res != c::CURLcode_CURLE_OK {
+                    return Err(CurlError(res));
+                }
+                c::curl_easy_setopt(
+                    self.curl,
+                    c::CURLoption_CURLOPT_DEBUGFUNCTION,
+                    debug_callback
+                        as extern "C" fn(
+                            *mut c::CURL,
+                            c::curl_infotype,
+                            *mut std::ffi::c_char,
+                            usize,
+                            *mut std::ffi::c_void,
+                        ) -> std::ffi::c_int,
+                )
+            } else {
+                c::curl_easy_setopt(
+                    self.curl,
+                    c::CURLoption_CURLOPT_DEBUGFUNCTION,
+                    std::ptr::null::<std::ffi::c_void>(),
+                )
+            }
+        };
+        if res != c::CURLcode_CURLE_OK {
+            return Err(CurlError(res));
+        }
+        Ok(())
     }
 
     pub fn no_verify(&self) -> Result<(), CurlError> {
@@ -116,6 +252,21 @@ impl Easy {
         Ok(())
     }
 
+    /// Abort transfers with a body larger than the given number of bytes.
+    pub fn set_max_filesize(&self, bytes: u64) -> Result<(), CurlError> {
+        let res = unsafe {
+            c::curl_easy_setopt(
+                self.curl,
+                c::CURLoption_CURLOPT_MAXFILESIZE_LARGE,
+                bytes as c::curl_off_t,
+            )
+        };
+        if res != c::CURLcode_CURLE_OK {
+            return Err(CurlError(res));
+        }
+        Ok(())
+    }
+
     pub fn url(&self, url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
         let url = CString::new(url)?;
         let res =
@@ -198,6 +349,89 @@ impl Easy {
         Ok(result)
     }
 
+    pub fn post_json(&self, body: &[u8]) -> Result<(), CurlError> {
+        let headers = unsafe {
+            c::curl_slist_append(
+                std::ptr::null_mut(),
+                b"Content-Type: application/json\0".as_ptr() as _,
+            )
+        };
+        if headers.is_null() {
+            panic!("curl_slist_append() failed");
+        }
+        unsafe {
+            c::curl_easy_setopt(self.curl, c::CURLoption_CURLOPT_HTTPHEADER, headers);
+            c::curl_easy_setopt(
+                self.curl,
+                c::CURLoption_CURLOPT_POSTFIELDSIZE_LARGE,
+                body.len() as c::curl_off_t,
+            );
+            c::curl_easy_setopt(self.curl, c::CURLoption_CURLOPT_POSTFIELDS, body.as_ptr());
+        }
+        let result = self.perform();
+        // reset even if the request failed, so the next request has no body
+        unsafe {
+            c::curl_easy_setopt(
+                self.curl,
+                c::CURLoption_CURLOPT_HTTPHEADER,
+                std::ptr::null::<std::ffi::c_void>(),
+            );
+            c::curl_easy_setopt(
+                self.curl,
+                c::CURLoption_CURLOPT_POSTFIELDS,
+                std::ptr::null::<std::ffi::c_void>(),
+            );
+            c::curl_easy_setopt(
+                self.curl,
+                c::CURLoption_CURLOPT_POSTFIELDSIZE_LARGE,
+                -1 as c::curl_off_t,
+            );
+            c::curl_easy_setopt(self.curl, c::CURLoption_CURLOPT_POST, 0 as std::ffi::c_long);
+            c::curl_slist_free_all(headers);
+        }
+        result
+    }
+
+    pub fn patch_json(&self, body: &[u8]) -> Result<(), CurlError> {
+        self.with_custom_request(b"PATCH\0", || self.post_json(body))
+    }
+
+    pub fn perform_put(&self) -> Result<(), CurlError> {
+        self.with_custom_request(b"PUT\0", || self.perform())
+    }
+
+    pub fn perform_put_json(&self, body: &[u8]) -> Result<(), CurlError> {
+        self.with_custom_request(b"PUT\0", || self.post_json(body))
+    }
+
+    pub fn perform_delete(&self) -> Result<(), CurlError> {
+        self.with_custom_request(b"DELETE\0", || self.perform())
+    }
+
+    /// Run a request with the given method name, which must be nul-terminated.
+    fn with_custom_request<F>(&self, method: &'static [u8], f: F) -> Result<(), CurlError>
+    where
+        F: FnOnce() -> Result<(), CurlError>,
+    {
+        unsafe {
+            c::curl_easy_setopt(
+                self.curl,
+                c::CURLoption_CURLOPT_CUSTOMREQUEST,
+                method.as_ptr(),
+            )
+        };
+        let result = f();
+        // reset even if the request failed, so the next request uses the default method
+        unsafe {
+            c::curl_easy_setopt(
+                self.curl,
+                c::CURLoption_CURLOPT_CUSTOMREQUEST,
+                std::ptr::null::<std::ffi::c_void>(),
+            )
+        };
+        result
+    }
+
     pub fn response_code(&self) -> Result<u16, CurlError> {
         let mut result = 0 as std::ffi::c_long;
         let res = unsafe {
@@ -209,12 +443,90 @@ impl Easy {
         Ok(result as _)
     }
 
+    pub fn response_bytes_received(&self) -> Result<u64, CurlError> {
+        let mut result = 0 as c::curl_off_t;
+        let res = unsafe {
+            c::curl_easy_getinfo(self.curl, c::CURLINFO_CURLINFO_SIZE_DOWNLOAD_T, &mut result)
+        };
+        if res != c::CURLcode_CURLE_OK {
+            return Err(CurlError(res));
+        }
+        Ok(result as _)
+    }
+
+    /// The value of the given header in the last response, or None if it
+    /// wasn't sent. If it was sent more than once, the last one is used.
+    pub fn response_header_value(
+        &self,
+        name: &str,
+    ) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+        let name = CString::new(name)?;
+        let mut header = std::ptr::null_mut::<c::curl_header>();
+        let res = unsafe {
+            c::curl_easy_header(
+                self.curl,
+                name.as_ptr(),
+                0,
+                c::CURLH_HEADER,
+                -1,
+                &mut header,
+            )
+        };
+        match res {
+            c::CURLHcode_CURLHE_OK => {
+                let value = unsafe { CStr::from_ptr((*header).value) };
+                Ok(Some(value.to_string_lossy().into_owned()))
+            }
+            c::CURLHcode_CURLHE_MISSING | c::CURLHcode_CURLHE_NOHEADERS => Ok(None),
+            res => Err(Box::new(CurlHeaderError(res))),
+        }
+    }
+
     pub fn buffer(&self) -> Vec<u8> {
         let mut result = vec![];
         let mut mine = self.write_buffer.as_ref().get_ref().borrow_mut();
         std::mem::swap(&mut result, &mut mine);
         result
     }
+
+    /// Percent-encode a string for use in a URL.
+    pub fn escape(&self, s: &str) -> Result<CurlString, CurlError> {
+        let ptr = unsafe {
+            c::curl_easy_escape(
+                self.curl,
+                s.as_ptr() as *const std::ffi::c_char,
+                s.len() as std::ffi::c_int,
+            )
+        };
+        if ptr.is_null() {
+            return Err(CurlError(c::CURLcode_CURLE_OUT_OF_MEMORY));
+        }
+        Ok(CurlString(ptr))
+    }
+}
+
+/// A string allocated by cURL, freed when dropped.
+pub struct CurlString(*mut std::ffi::c_char);
+
+impl AsRef<str> for CurlString {
+    fn as_ref(&self) -> &str {
+        let bytes = unsafe { CStr::from_ptr(self.0) }.to_bytes();
+        // SAFETY: the only CurlStrings are escaped by cURL, which leaves
+        // nothing but ASCII
+        unsafe { std::str::from_utf8_unchecked(bytes) }
+    }
+}
+
+impl Display for CurlString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+impl Drop for CurlString {
+    fn drop(&mut self) {
+        unsafe { c::curl_free(self.0 as *mut std::ffi::c_void) };
+    }
 }
 
 impl Drop for Easy {
@@ -2,5 +2,6 @@ pub mod curl;
 pub mod retriever;
 
 mod client;
+mod oauth;
 
-pub use client::Client;
+pub use client::{Client, NotificationPoller, StatusPoster};
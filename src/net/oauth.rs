@@ -0,0 +1,35 @@
+use std::error::Error;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+
+/// Characters allowed in a code verifier. Only alphanumerics are used, which
+/// are a subset of the unreserved characters allowed by RFC 7636.
+static VERIFIER_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// The length of generated code verifiers, which must be from 43 to 128.
+const VERIFIER_LEN: usize = 64;
+
+/// Generate a random code verifier for the PKCE flow.
+pub fn generate_code_verifier() -> Result<String, Box<dyn Error + Send + Sync>> {
+    // the largest multiple of the alphabet size that fits in a byte, any bytes
+    // at or above this are rerolled so that every character is equally likely
+    let limit = 256 - 256 % VERIFIER_CHARS.len();
+    let mut result = String::with_capacity(VERIFIER_LEN);
+    let mut buffer = [0u8; VERIFIER_LEN];
+    while result.len() < VERIFIER_LEN {
+        getrandom::getrandom(&mut buffer)?;
+        for byte in buffer {
+            let byte = usize::from(byte);
+            if byte < limit && result.len() < VERIFIER_LEN {
+                result.push(char::from(VERIFIER_CHARS[byte % VERIFIER_CHARS.len()]));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Get the S256 code challenge for a code verifier.
+pub fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
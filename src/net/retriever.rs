@@ -3,26 +3,100 @@ use std::{
     fmt::Display,
     str::FromStr,
     sync::{
-        mpsc::{channel, Receiver, Sender},
-        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, TrySendError},
+        Arc, Mutex, Weak,
     },
     thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
-use super::curl::Easy;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 
+use super::curl::{DebugKind, Easy};
+
+#[derive(Clone)]
 pub enum Method {
     Get,
     Post(Vec<(&'static str, Vec<u8>)>),
+    /// A POST request with a JSON body.
+    PostJson(Vec<u8>),
+    /// A PATCH request with a JSON body.
+    PatchJson(Vec<u8>),
+    /// A PUT request with no body.
+    Put,
+    /// A PUT request with a JSON body.
+    PutJson(Vec<u8>),
+    Delete,
 }
 
+/// A request with this URL tells the worker thread to stop.
+static CLOSE_URL: &str = "__close__";
+
+/// The default limit on the size of a response body.
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// The most requests that can wait for the worker thread at once.
+const QUEUE_CAPACITY: usize = 64;
+
+/// The most requests that should be given to batch_request at once. This
+/// leaves room in the queue for requests from other threads.
+pub const MAX_BATCH_SIZE: usize = QUEUE_CAPACITY / 2;
+
+/// Requests are slowed down once fewer than this many are left before the
+/// rate limit resets.
+const BACKOFF_REMAINING: u32 = 10;
+
+/// The longest to wait before a single request when backing off, so that the
+/// app doesn't seem to hang if the server's reset time is far off.
+const MAX_BACKOFF_DELAY: Duration = Duration::from_secs(5);
+
+/// The credentials to send with a request.
+#[derive(Clone)]
+pub enum RequestAuth {
+    /// Use the token set with set_token, but only for the logged-in instance.
+    UseGlobal,
+    /// Never send a token.
+    None,
+    /// Send the given token, wherever the request goes.
+    Bearer(String),
+}
+
+#[derive(Clone)]
 pub struct Request {
     pub method: Method,
     pub url: String,
+    /// The largest response body to accept, or None for the default.
+    pub max_size: Option<u64>,
+    pub auth: RequestAuth,
 }
 
 pub type Response = Result<Vec<u8>, Box<dyn Error + Send + Sync>>;
 
+/// Identifies a request made with Retriever::batch_request. Assigned by the caller.
+pub type RequestId = usize;
+
+/// Where the worker thread sends the response to a request.
+enum Reply {
+    Plain(Sender<Response>),
+    Tagged(RequestId, Sender<(RequestId, Response)>),
+}
+
+impl Reply {
+    fn send(self, response: Response) {
+        // ignore send errors, the caller may have stopped waiting
+        match self {
+            Reply::Plain(tx) => {
+                _ = tx.send(response);
+            }
+            Reply::Tagged(id, tx) => {
+                _ = tx.send((id, response));
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct HttpError(pub u16);
 
@@ -34,12 +108,197 @@ impl Display for HttpError {
 
 impl Error for HttpError {}
 
+/// Returned when a request is made while the queue is already full.
+#[derive(Debug)]
+pub struct QueueFullError;
+
+impl Display for QueueFullError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "too many requests are waiting to be sent")
+    }
+}
+
+impl Error for QueueFullError {}
+
+/// The response to a request that was still queued when the Retriever gave
+/// up waiting to close.
+#[derive(Debug)]
+pub struct CancelledError;
+
+impl Display for CancelledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the request was cancelled while closing")
+    }
+}
+
+impl Error for CancelledError {}
+
+/// An error response from the server that explains what went wrong.
+#[derive(Debug)]
+pub struct MastodonError {
+    pub status: u16,
+    pub error: String,
+    pub description: Option<String>,
+}
+
+/// The body of an error response, as sent by Mastodon.
+#[derive(Deserialize)]
+struct MastodonErrorBody {
+    error: String,
+    error_description: Option<String>,
+}
+
+impl MastodonError {
+    /// Read the error from a response body, if it has one.
+    fn from_response(status: u16, buffer: &[u8]) -> Option<Self> {
+        let body = serde_json::from_slice::<MastodonErrorBody>(buffer).ok()?;
+        Some(Self {
+            status,
+            error: body.error,
+            description: body.error_description,
+        })
+    }
+}
+
+impl Display for MastodonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // OAuth errors put a code in error and the explanation in the description
+        match &self.description {
+            Some(description) => write!(f, "{}", description),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+impl Error for MastodonError {}
+
+/// Counters for diagnosing network performance.
+#[derive(Default)]
+pub struct RetrieverStats {
+    pub requests_sent: AtomicU64,
+    pub bytes_received: AtomicU64,
+    pub requests_failed: AtomicU64,
+    pub total_latency_ms: AtomicU64,
+}
+
+impl Display for RetrieverStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let requests = self.requests_sent.load(Ordering::Relaxed);
+        let bytes = self.bytes_received.load(Ordering::Relaxed);
+        let failed = self.requests_failed.load(Ordering::Relaxed);
+        let latency = self.total_latency_ms.load(Ordering::Relaxed);
+        write!(
+            f,
+            "Total requests: {}, Total MB: {:.2}, Failed: {}, Avg latency: {}ms",
+            requests,
+            bytes as f64 / (1024.0 * 1024.0),
+            failed,
+            latency.checked_div(requests).unwrap_or(0),
+        )
+    }
+}
+
+/// How many more requests the server will accept before it starts refusing
+/// them, as of the last response.
+#[derive(Clone, Copy)]
+pub struct RateLimitState {
+    pub remaining: u32,
+    /// When the server will accept a full set of requests again.
+    pub reset_at: DateTime<Utc>,
+    /// The server's time when the response was sent. The 3DS clock is in
+    /// local time, so this is used instead to work out how long is left.
+    pub server_time: DateTime<Utc>,
+    /// When the response was received, to count the time since then.
+    pub received_at: Instant,
+}
+
+impl RateLimitState {
+    /// Read the rate limit from the headers of the last response, if they
+    /// were sent. Instances can turn rate limits off, so they may not be.
+    fn from_response(easy: &Easy) -> Option<Self> {
+        let header = |name| easy.response_header_value(name).ok().flatten();
+        let remaining = header("X-RateLimit-Remaining")?.trim().parse().ok()?;
+        let reset_at = DateTime::parse_from_rfc3339(header("X-RateLimit-Reset")?.trim()).ok()?;
+        let server_time = DateTime::parse_from_rfc2822(header("Date")?.trim()).ok()?;
+        Some(Self {
+            remaining,
+            reset_at: reset_at.with_timezone(&Utc),
+            server_time: server_time.with_timezone(&Utc),
+            received_at: Instant::now(),
+        })
+    }
+
+    /// How long to wait before each request to spread the remaining ones
+    /// out until the limit resets, up to MAX_BACKOFF_DELAY.
+    pub fn delay(&self) -> Duration {
+        let until_reset = (self.reset_at - self.server_time)
+            .to_std()
+            .unwrap_or_default();
+        let left = until_reset.saturating_sub(self.received_at.elapsed());
+        (left / self.remaining.max(1)).min(MAX_BACKOFF_DELAY)
+    }
+}
+
+/// The sending end of the queue of requests for the worker thread.
+#[derive(Clone)]
+struct RequestQueue {
+    tx: SyncSender<(Request, Reply)>,
+}
+
+impl RequestQueue {
+    /// Queue a request, waiting for room if the queue is full. If the worker
+    /// thread has stopped, the reply is dropped.
+    fn push(&self, request: Request, reply: Reply) {
+        _ = self.tx.send((request, reply));
+    }
+
+    /// Queue a request without waiting. If the queue is full, the reply is a
+    /// QueueFullError. If the worker thread has stopped, the reply is dropped.
+    fn try_push(&self, request: Request, reply: Reply) {
+        if let Err(TrySendError::Full((_, reply))) = self.tx.try_send((request, reply)) {
+            reply.send(Err(Box::new(QueueFullError)));
+        }
+    }
+}
+
+/// Queues requests on a Retriever from another thread.
+pub struct RetrieverHandle {
+    requests: RequestQueue,
+    /// Gone once the Retriever has been closed or dropped.
+    alive: Weak<()>,
+}
+
+impl RetrieverHandle {
+    /// Like Retriever::request. If the retriever has been closed, the
+    /// returned Receiver will fail.
+    pub fn request(&self, requests: Vec<Request>) -> Receiver<Response> {
+        let (tx, rx) = channel();
+        for request in requests {
+            self.requests.push(request, Reply::Plain(tx.clone()));
+        }
+        rx
+    }
+
+    /// True once the Retriever has been closed or dropped. Requests may
+    /// still be answered until the queue is empty, but no more should be
+    /// made.
+    pub fn is_closed(&self) -> bool {
+        self.alive.upgrade().is_none()
+    }
+}
+
 /// Queues and performs network operations.
 pub struct Retriever {
-    requests: Sender<(Request, Sender<Response>)>,
+    requests: RequestQueue,
 
     instance: Arc<Mutex<String>>,
     token: Arc<Mutex<String>>,
+    stats: Arc<RetrieverStats>,
+    rate_limit: Arc<Mutex<Option<RateLimitState>>>,
+    /// Lets handles tell when the Retriever is gone.
+    alive: Arc<()>,
+    /// Set when closing takes too long, so that queued requests are skipped.
+    cancelled: Arc<AtomicBool>,
 
     thread: JoinHandle<()>,
 }
@@ -49,36 +308,60 @@ fn make_request(
     request: Request,
     instance: &Mutex<String>,
     token: &Mutex<String>,
+    stats: &RetrieverStats,
+    rate_limit: &Mutex<Option<RateLimitState>>,
 ) -> Response {
     // get the response
     easy.url(&request.url)?;
+    // don't let a misbehaving server make us run out of memory
+    easy.set_max_filesize(request.max_size.unwrap_or(DEFAULT_MAX_SIZE))?;
     // TODO we probably want to consider TLS verification?
     easy.no_verify()?;
     // decide if we need to authenticate
     easy.bearer(None)?;
-    let token = token.lock().unwrap();
-    if !token.is_empty() {
-        if let Some(s) = url::Url::from_str(&request.url)?.domain() {
-            if s == *instance.lock().unwrap() {
-                easy.bearer(Some(&token))?;
+    match &request.auth {
+        RequestAuth::UseGlobal => {
+            let token = token.lock().unwrap();
+            if !token.is_empty() {
+                if let Some(s) = url::Url::from_str(&request.url)?.domain() {
+                    if s == *instance.lock().unwrap() {
+                        easy.bearer(Some(&token))?;
+                    }
+                }
             }
         }
+        RequestAuth::None => {}
+        RequestAuth::Bearer(token) => easy.bearer(Some(token))?,
     }
-    drop(token);
-    // if it's a post request, add the fields
-    if let Method::Post(fields) = request.method {
-        let mime = easy.mime();
-        for (name, data) in fields {
-            mime.add_part(name, &data)?;
+    match request.method {
+        Method::Get => easy.perform()?,
+        // if it's a post request, add the fields
+        Method::Post(fields) => {
+            let mime = easy.mime();
+            for (name, data) in fields {
+                mime.add_part(name, &data)?;
+            }
+            easy.perform_with_mime(mime)?;
         }
-        easy.perform_with_mime(mime)?;
-    } else {
-        easy.perform()?;
+        Method::PostJson(body) => easy.post_json(&body)?,
+        Method::PatchJson(body) => easy.patch_json(&body)?,
+        Method::Put => easy.perform_put()?,
+        Method::PutJson(body) => easy.perform_put_json(&body)?,
+        Method::Delete => easy.perform_delete()?,
+    }
+    stats
+        .bytes_received
+        .fetch_add(easy.response_bytes_received()?, Ordering::Relaxed);
+    if let Some(state) = RateLimitState::from_response(easy) {
+        *rate_limit.lock().unwrap() = Some(state);
     }
     let response = easy.response_code()?;
     let buffer = easy.buffer();
     if response != 200 {
-        Err(Box::new(HttpError(response)))
+        match MastodonError::from_response(response, &buffer) {
+            Some(e) => Err(Box::new(e)),
+            None => Err(Box::new(HttpError(response))),
+        }
     } else {
         Ok(buffer)
     }
@@ -86,52 +369,176 @@ fn make_request(
 
 impl Retriever {
     pub fn new() -> Self {
-        let (req_tx, req_rx) = channel::<(Request, Sender<Response>)>();
+        let (req_tx, req_rx) = sync_channel::<(Request, Reply)>(QUEUE_CAPACITY);
 
         let instance = Arc::new(Mutex::new(String::new()));
         let token = Arc::new(Mutex::new(String::new()));
 
+        let stats = Arc::new(RetrieverStats::default());
+        let rate_limit = Arc::new(Mutex::new(None));
+
         let instance_clone = instance.clone();
         let token_clone = token.clone();
+        let stats_clone = stats.clone();
+        let rate_limit_clone = rate_limit.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancelled_clone = cancelled.clone();
 
         let thread = std::thread::spawn(move || {
             // create curl instance
             let easy = Easy::new();
+            // in debug builds, show what cURL is doing on the console. this is
+            // only for diagnosing problems, so ignore errors
+            if cfg!(debug_assertions) {
+                _ = easy.set_verbose(true);
+                _ = easy.set_debug_callback(Some(|kind, data: &[u8]| {
+                    // the other kinds are request and response data, which would flood the console
+                    if kind == DebugKind::Text {
+                        print!("{}", String::from_utf8_lossy(data));
+                    }
+                }));
+            }
             // wait for requests to come through, stop when the other end disconnects
             while let Ok((request, res)) = req_rx.recv() {
+                // requests sent before closing have been handled, so we can stop
+                if request.url == CLOSE_URL {
+                    break;
+                }
+                // closing has timed out, so answer without making the request
+                if cancelled_clone.load(Ordering::Relaxed) {
+                    res.send(Err(Box::new(CancelledError)));
+                    continue;
+                }
                 // make a request
-                res.send(make_request(&easy, request, &instance_clone, &token_clone))
-                    .unwrap();
+                let start = unsafe { ctru_sys::osGetTime() };
+                let response = make_request(
+                    &easy,
+                    request,
+                    &instance_clone,
+                    &token_clone,
+                    &stats_clone,
+                    &rate_limit_clone,
+                );
+                let end = unsafe { ctru_sys::osGetTime() };
+                stats_clone.requests_sent.fetch_add(1, Ordering::Relaxed);
+                stats_clone
+                    .total_latency_ms
+                    .fetch_add(end - start, Ordering::Relaxed);
+                if response.is_err() {
+                    stats_clone.requests_failed.fetch_add(1, Ordering::Relaxed);
+                }
+                res.send(response);
             }
         });
 
         Self {
-            requests: req_tx,
+            requests: RequestQueue { tx: req_tx },
 
             instance,
             token,
+            stats,
+            rate_limit,
+            alive: Arc::new(()),
+            cancelled,
 
             thread,
         }
     }
 
     /// Enqueue a series of requests. Returns a Receiver which will return the
-    /// responses to those requests, in order.
+    /// responses to those requests, in order. If the queue is full, this waits
+    /// for room.
     pub fn request(&self, requests: Vec<Request>) -> Receiver<Response> {
         let (tx, rx) = channel();
         for request in requests {
-            self.requests.send((request, tx.clone())).unwrap();
+            self.requests.push(request, Reply::Plain(tx.clone()));
         }
         rx
     }
 
+    /// Enqueue a series of requests, each with an ID. Returns a Receiver
+    /// which will return each response along with the ID of its request.
+    /// Callers should not rely on the responses arriving in order. Requests
+    /// that don't fit in the queue get a QueueFullError as their response,
+    /// so at most MAX_BATCH_SIZE should be given at once.
+    pub fn batch_request(
+        &self,
+        requests: Vec<(RequestId, Request)>,
+    ) -> Receiver<(RequestId, Response)> {
+        let (tx, rx) = channel();
+        for (id, request) in requests {
+            self.requests
+                .try_push(request, Reply::Tagged(id, tx.clone()));
+        }
+        rx
+    }
+
+    /// Get a handle for queueing requests from another thread.
+    pub fn handle(&self) -> RetrieverHandle {
+        RetrieverHandle {
+            requests: self.requests.clone(),
+            alive: Arc::downgrade(&self.alive),
+        }
+    }
+
     // we can't move out of self during a drop, so we use a method to manually
     // close the sender and join the thread
     pub fn close(self) {
-        // drop requests early
+        self.close_graceful(5000);
+    }
+
+    /// Finish all queued requests, then stop the worker thread. If that takes
+    /// longer than the given number of milliseconds, requests that haven't
+    /// been started are cancelled instead, and false is returned. Either way,
+    /// the worker thread has stopped when this returns, so the socket service
+    /// and cURL globals can be released afterwards.
+    pub fn close_graceful(self, timeout_ms: u64) -> bool {
+        // the worker stops when it reaches this, after everything queued before it
+        let (tx, _) = channel();
+        let close = Request {
+            method: Method::Get,
+            url: CLOSE_URL.into(),
+            max_size: None,
+            auth: RequestAuth::None,
+        };
+        // wait for room in the queue, as this has to get through. if sending
+        // fails, the thread has already stopped
+        self.requests.push(close, Reply::Plain(tx));
         drop(self.requests);
-        // now join the thread, since it now knows we're done
-        self.thread.join().unwrap();
+        // join on another thread, so that we can stop waiting on the queue
+        let (done_tx, done_rx) = channel();
+        let thread = self.thread;
+        std::thread::spawn(move || {
+            // a panic in the worker still means it has stopped
+            _ = thread.join();
+            // ignore send errors, the closing thread may have stopped waiting
+            _ = done_tx.send(());
+        });
+        if done_rx
+            .recv_timeout(Duration::from_millis(timeout_ms))
+            .is_ok()
+        {
+            return true;
+        }
+        // skip whatever is left, but only return once the request in
+        // progress has finished
+        self.cancelled.store(true, Ordering::Relaxed);
+        _ = done_rx.recv();
+        false
+    }
+
+    pub fn stats(&self) -> Arc<RetrieverStats> {
+        self.stats.clone()
+    }
+
+    /// The rate limit as of the last response that gave one.
+    pub fn rate_limit(&self) -> Option<RateLimitState> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    /// True if few enough requests are left that they should be slowed down.
+    pub fn should_backoff(&self) -> bool {
+        matches!(self.rate_limit(), Some(state) if state.remaining < BACKOFF_REMAINING)
     }
 
     pub fn set_token(&self, token: String) {
@@ -1,8 +1,12 @@
+use std::fmt::{Debug, Display};
+
 use chrono::{DateTime, NaiveDate, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Deserialize)]
+use crate::html;
+
+#[derive(Debug, Deserialize)]
 pub struct Account {
     pub id: String,
     pub username: String,
@@ -34,7 +38,27 @@ pub struct Account {
     pub role: Option<Role>,
 }
 
-#[derive(Deserialize)]
+impl Account {
+    /// The account's handle with a leading @, leaving out the domain if it is
+    /// on the given instance. Accounts on the instance the request was made
+    /// to already have no domain, but those from elsewhere might.
+    pub fn short_acct(&self, home_instance: &str) -> String {
+        let acct = self
+            .acct
+            .strip_suffix(home_instance)
+            .and_then(|acct| acct.strip_suffix('@'))
+            .unwrap_or(&self.acct);
+        format!("@{}", acct)
+    }
+}
+
+impl Display for Account {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (@{})", self.display_name, self.acct)
+    }
+}
+
+#[derive(Debug, Deserialize)]
 pub struct AccountSource {
     pub note: String,
     pub fields: Vec<Field>,
@@ -44,6 +68,25 @@ pub struct AccountSource {
     pub follow_requests_count: u64,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Announcement {
+    pub id: String,
+    #[serde(rename = "content")]
+    pub text: String,
+    pub published_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub reactions: Vec<AnnouncementReaction>,
+    pub read: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnnouncementReaction {
+    pub name: String,
+    pub count: u64,
+    pub me: bool,
+    pub url: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct Application {
     pub name: String,
@@ -53,7 +96,29 @@ pub struct Application {
     pub client_secret: Option<String>,
 }
 
-#[derive(Deserialize)]
+// the secret is left out, so that printing an application doesn't leak it
+impl Debug for Application {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Application")
+            .field("name", &self.name)
+            .field("website", &self.website)
+            .field("vapid_key", &self.vapid_key)
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Context {
+    pub ancestors: Vec<Status>,
+    pub descendants: Vec<Status>,
+}
+
+#[derive(Debug, Deserialize)]
 pub struct CustomEmoji {
     pub shortcode: String,
     pub url: String,
@@ -62,14 +127,22 @@ pub struct CustomEmoji {
     pub category: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct Field {
     pub name: String,
     pub value: String,
     pub verified_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Deserialize)]
+impl Field {
+    /// True if the instance has checked that the link in the value points
+    /// back to the account.
+    pub fn is_verified(&self) -> bool {
+        self.verified_at.is_some()
+    }
+}
+
+#[derive(Debug, Deserialize)]
 pub struct Filter {
     pub id: String,
     pub title: String,
@@ -80,7 +153,7 @@ pub struct Filter {
     pub statuses: Vec<FilterStatus>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub enum FilterAction {
     #[serde(rename = "warn")]
     Warn,
@@ -88,7 +161,7 @@ pub enum FilterAction {
     Hide,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub enum FilterContext {
     #[serde(rename = "home")]
     Home,
@@ -102,27 +175,90 @@ pub enum FilterContext {
     Account,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct FilterKeyword {
     pub id: String,
     pub keyword: String,
     pub whole_word: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct FilterResult {
     pub filter: Filter,
     pub keyword_matches: Option<Vec<String>>,
     pub status_matches: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct FilterStatus {
     pub id: String,
     pub status_id: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
+pub struct InstanceContact {
+    pub email: String,
+    pub account: Option<Account>,
+}
+
+/// An instance listed by instances.social.
+#[derive(Clone, Debug, Deserialize)]
+pub struct InstanceDirectoryEntry {
+    pub name: String,
+    pub active_users: Option<u64>,
+    pub info: Option<InstanceDirectoryInfo>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct InstanceDirectoryInfo {
+    pub short_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstanceDirectoryList {
+    pub instances: Vec<InstanceDirectoryEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstanceRegistrations {
+    pub enabled: bool,
+    pub approval_required: bool,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstanceThumbnail {
+    pub url: String,
+    pub blurhash: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstanceUsage {
+    pub users: InstanceUsageUsers,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstanceUsageUsers {
+    pub active_month: u64,
+}
+
+/// Information about an instance, as given by the v2 API.
+#[derive(Debug, Deserialize)]
+pub struct InstanceV2 {
+    pub domain: String,
+    pub title: String,
+    pub version: String,
+    pub source_url: String,
+    pub description: String,
+    pub usage: InstanceUsage,
+    pub thumbnail: InstanceThumbnail,
+    pub languages: Vec<String>,
+    pub registrations: InstanceRegistrations,
+    pub contact: InstanceContact,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
 pub struct MediaAttachment {
     pub id: String,
     #[serde(rename = "type")]
@@ -135,7 +271,7 @@ pub struct MediaAttachment {
     pub blurhash: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub enum MediaType {
     #[serde(rename = "unknown")]
     Unknown,
@@ -149,7 +285,13 @@ pub enum MediaType {
     Audio,
 }
 
-#[derive(Deserialize)]
+/// Only the ID is needed to count new notifications, so the rest is skipped.
+#[derive(Debug, Deserialize)]
+pub struct Notification {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
 pub struct Poll {
     pub id: String,
     pub expires_at: Option<DateTime<Utc>>,
@@ -163,13 +305,13 @@ pub struct Poll {
     pub own_votes: Vec<usize>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct PollOption {
     pub title: String,
     pub votes_count: Option<u64>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct PreviewCard {
     pub url: String,
     pub title: String,
@@ -189,14 +331,14 @@ pub struct PreviewCard {
     pub history: Option<Vec<TrendsHistory>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct TrendsHistory {
     pub day: String,
     pub accounts: String,
     pub uses: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub enum PreviewCardType {
     #[serde(rename = "link")]
     Link,
@@ -208,7 +350,21 @@ pub enum PreviewCardType {
     Rich,
 }
 
-#[derive(Deserialize)]
+/// Which kinds of notification are pushed to a Web Push subscription.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PushAlerts {
+    pub mention: bool,
+    pub status: bool,
+    pub reblog: bool,
+    pub follow: bool,
+    pub follow_request: bool,
+    pub favourite: bool,
+    pub poll: bool,
+    pub update: bool,
+}
+
+#[derive(Debug, Deserialize)]
 pub struct Role {
     pub id: String,
     pub name: String,
@@ -220,7 +376,38 @@ pub struct Role {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduledStatus {
+    pub id: String,
+    pub scheduled_at: DateTime<Utc>,
+    pub params: ScheduledStatusParams,
+    pub media_attachments: Vec<MediaAttachment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduledStatusParams {
+    pub text: String,
+    pub media_ids: Option<Vec<String>>,
+    pub sensitive: Option<bool>,
+    pub spoiler_text: Option<String>,
+    pub visibility: Visibility,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchResults {
+    pub accounts: Vec<Account>,
+    pub statuses: Vec<Status>,
+    pub hashtags: Vec<StatusTag>,
+}
+
+#[derive(Debug, Deserialize)]
 pub struct Status {
     pub id: String,
     pub uri: String,
@@ -255,13 +442,35 @@ pub struct Status {
     pub filter: Option<Vec<FilterResult>>,
 }
 
-#[derive(Deserialize)]
+impl Status {
+    /// True if this status is a boost of another status.
+    pub fn is_reblog(&self) -> bool {
+        self.reblog.is_some()
+    }
+
+    /// The status whose content should be shown: the boosted status for a
+    /// boost, or this status otherwise.
+    pub fn effective_status(&self) -> &Status {
+        self.reblog.as_deref().unwrap_or(self)
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // this is only for logging, so show the HTML if it can't be parsed
+        let content = html::to_plain_text(&self.content);
+        let content = content.as_deref().unwrap_or(self.content.as_str());
+        write!(f, "{}: {}", self.account.display_name, content.trim())
+    }
+}
+
+#[derive(Debug, Deserialize)]
 pub struct StatusApplication {
     pub name: String,
     pub website: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct StatusMention {
     pub id: String,
     pub username: String,
@@ -269,7 +478,7 @@ pub struct StatusMention {
     pub acct: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct StatusTag {
     pub name: String,
     pub url: String,
@@ -283,7 +492,19 @@ pub struct Token {
     pub created_at: u64,
 }
 
-#[derive(Deserialize)]
+// the access token is left out, so that printing a token doesn't leak it
+impl Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Token")
+            .field("access_token", &"<redacted>")
+            .field("token_type", &self.token_type)
+            .field("scope", &self.scope)
+            .field("created_at", &self.created_at)
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
 pub enum Visibility {
     #[serde(rename = "public")]
     Public,
@@ -294,3 +515,23 @@ pub enum Visibility {
     #[serde(rename = "direct")]
     Direct,
 }
+
+impl Visibility {
+    /// The name the API uses for the visibility.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+            Visibility::Direct => "direct",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebPushSubscription {
+    pub id: String,
+    pub endpoint: String,
+    pub alerts: PushAlerts,
+    pub server_key: String,
+}
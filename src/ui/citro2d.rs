@@ -8,7 +8,7 @@ use std::{
     pin::Pin,
     rc::Rc,
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Mutex, MutexGuard,
     },
 };
@@ -35,6 +35,18 @@ impl Display for C2dMemError {
 
 impl Error for C2dMemError {}
 
+/// Returned when the GPU isn't ready to start drawing a frame.
+#[derive(Debug)]
+pub struct FrameError;
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GPU not ready for a new frame")
+    }
+}
+
+impl Error for FrameError {}
+
 /// The handle to the Citro2D instance.
 pub struct Citro2d(Gfx);
 
@@ -44,6 +56,34 @@ static CITRO_COUNT: AtomicUsize = AtomicUsize::new(0);
 /// Ensures we don't create multiple frames at once.
 static FRAME_LOCK: Mutex<()> = Mutex::new(());
 
+/// The number of frames begun so far.
+static FRAME_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// The number of frames that have been drawn, for use in animations and
+/// timing. Unlike Citro2d::frame_count, this can be called from any thread.
+pub fn frame_count() -> u64 {
+    FRAME_COUNT.load(Ordering::Relaxed)
+}
+
+/// The number of textures allocated by AnyTexture::new and not yet freed.
+static TEXTURE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of textures in GPU memory, including font and atlas textures.
+/// Textures made with AnyTexture::raw aren't counted, as they don't own
+/// their memory.
+pub fn texture_count() -> usize {
+    TEXTURE_COUNT.load(Ordering::Relaxed)
+}
+
+/// The number of bytes of GPU memory used by the textures in TEXTURE_COUNT.
+static TEXTURE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of bytes of GPU memory used by textures, counting the same
+/// textures as texture_count.
+pub fn texture_bytes() -> usize {
+    TEXTURE_BYTES.load(Ordering::Relaxed)
+}
+
 impl Citro2d {
     pub fn new(gfx: Gfx) -> Result<Self, C2dMemError> {
         // check count, only initialize if zero
@@ -65,16 +105,23 @@ impl Citro2d {
         Ok(Self(gfx))
     }
 
-    pub fn begin_frame(&self) -> Frame<'_> {
+    /// Start drawing a frame. If the GPU isn't ready, nothing is started, and
+    /// the frame should be tried again later.
+    pub fn begin_frame(&self) -> Result<Frame<'_>, FrameError> {
         let lock = FRAME_LOCK.lock().unwrap();
-        unsafe {
-            // TODO handle return value
-            c::C3D_FrameBegin(c::C3D_FRAME_SYNCDRAW as _);
+        if !unsafe { c::C3D_FrameBegin(c::C3D_FRAME_SYNCDRAW as _) } {
+            return Err(FrameError);
         }
-        Frame {
+        FRAME_COUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Frame {
             _lock: lock,
             _phantom: PhantomData,
-        }
+        })
+    }
+
+    /// The number of frames that have been drawn, for use in animations.
+    pub fn frame_count(&self) -> u64 {
+        frame_count()
     }
 
     pub fn gfx(&self) -> &Gfx {
@@ -150,6 +197,23 @@ impl<'gfx, 'screen> RenderTarget<'gfx, 'screen> {
         }
     }
 
+    /// Clear the target, then fill it with a vertical gradient. Needs a scene
+    /// to draw the gradient in.
+    pub fn clear_gradient(&self, ctx: &Scene2d, top_color: u32, bottom_color: u32) {
+        self.clear(top_color);
+        // large enough for either screen
+        ctx.rect(
+            0.0,
+            0.0,
+            400.0,
+            240.0,
+            top_color,
+            top_color,
+            bottom_color,
+            bottom_color,
+        );
+    }
+
     // lifetime ensures scene context doesn't leave the function
     // frame argument requires a frame to be active
     pub fn scene_2d<F>(&self, _frame: &Frame<'gfx>, f: F)
@@ -197,6 +261,40 @@ impl Scene2d {
     pub fn rect_solid(&self, x: f32, y: f32, w: f32, h: f32, color: u32) {
         self.rect(x, y, w, h, color, color, color, color);
     }
+
+    /// Cover a rectangle with copies of an image, starting at the top left,
+    /// in a single quad. Tiles at the right and bottom edges are clipped. The
+    /// image must cover its whole texture, and the texture must be set to
+    /// repeat, as the texture coordinates run past its edges.
+    pub fn image_tiled(&self, img: &Image<'_>, x: f32, y: f32, w: f32, h: f32) {
+        let (tile_w, tile_h) = img.size();
+        let columns = w / f32::from(tile_w);
+        let rows = h / f32::from(tile_h);
+        img.repeated(columns, rows).draw(self, x, y, columns, rows);
+    }
+
+    /// Draw an image split by NineSlice stretched over a rectangle, without
+    /// stretching its corners. The corners are drawn unscaled, the edges are
+    /// stretched along their length, and the center is stretched both ways.
+    pub fn draw_nine_slice(&self, slices: &NineSlice<'_>, x: f32, y: f32, w: f32, h: f32) {
+        let edge = f32::from(slices.border);
+        // where the pieces go, as a position and a size
+        let columns = [(x, edge), (x + edge, w - edge * 2.0), (x + w - edge, edge)];
+        let rows = [(y, edge), (y + edge, h - edge * 2.0), (y + h - edge, edge)];
+        for (pieces, (dst_y, dst_h)) in slices.pieces.iter().zip(rows) {
+            for (piece, (dst_x, dst_w)) in pieces.iter().zip(columns) {
+                // skip pieces that are empty or would be turned inside out
+                let piece = match piece {
+                    Some(piece) if dst_w > 0.0 && dst_h > 0.0 => piece,
+                    _ => continue,
+                };
+                let (src_w, src_h) = piece.size();
+                let scale_x = dst_w / f32::from(src_w);
+                let scale_y = dst_h / f32::from(src_h);
+                piece.draw(self, dst_x, dst_y, scale_x, scale_y);
+            }
+        }
+    }
 }
 
 #[inline]
@@ -258,6 +356,60 @@ impl TextureFormat for Luminance4 {
     }
 }
 
+/// An 8-bit luminance texture format.
+pub struct Luminance8;
+
+impl TextureFormat for Luminance8 {
+    type Pixel = u8;
+
+    const FORMAT: c::GPU_TEXCOLOR = c::GPU_TEXCOLOR_GPU_L8;
+
+    unsafe fn set(data: *mut std::ffi::c_void, x: u16, y: u16, width: u16, pixel: Self::Pixel) {
+        let index = buffer_offset(x.into(), y.into(), width.into(), 2);
+        let byte_ptr = (data as *mut u8).add(index);
+        *byte_ptr = pixel;
+    }
+}
+
+/// An 8-bit luminance and 8-bit alpha texture format. Pixels are packed as
+/// `(luminance << 8) | alpha`.
+pub struct LA8;
+
+impl TextureFormat for LA8 {
+    type Pixel = u16;
+
+    const FORMAT: c::GPU_TEXCOLOR = c::GPU_TEXCOLOR_GPU_LA8;
+
+    unsafe fn set(data: *mut std::ffi::c_void, x: u16, y: u16, width: u16, pixel: Self::Pixel) {
+        let index = buffer_offset(x.into(), y.into(), width.into(), 4);
+        let byte_ptr = (data as *mut u8).add(index) as *mut u16;
+        *byte_ptr = pixel;
+    }
+}
+
+/// A 16-bit RGB texture format with no alpha, for opaque images such as
+/// photos. Pixels are packed with 5 bits of red, 6 of green, and 5 of blue.
+pub struct RGB565;
+
+impl TextureFormat for RGB565 {
+    type Pixel = u16;
+
+    const FORMAT: c::GPU_TEXCOLOR = c::GPU_TEXCOLOR_GPU_RGB565;
+
+    unsafe fn set(data: *mut std::ffi::c_void, x: u16, y: u16, width: u16, pixel: Self::Pixel) {
+        let index = buffer_offset(x.into(), y.into(), width.into(), 4);
+        let byte_ptr = (data as *mut u8).add(index) as *mut u16;
+        *byte_ptr = pixel;
+    }
+}
+
+impl RGB565 {
+    /// Pack 8-bit color channels into a pixel, dropping the lowest bits.
+    pub fn pack(r: u8, g: u8, b: u8) -> u16 {
+        (u16::from(r >> 3) << 11) | (u16::from(g >> 2) << 5) | u16::from(b >> 3)
+    }
+}
+
 /// An 8-bit RGBA texture format. The highest quality, but most expensive.
 pub struct RGBA8;
 
@@ -273,6 +425,25 @@ impl TextureFormat for RGBA8 {
     }
 }
 
+/// The number of bits used by each pixel of a texture format.
+fn bits_per_pixel(format: c::GPU_TEXCOLOR) -> usize {
+    match format {
+        c::GPU_TEXCOLOR_GPU_RGBA8 => 32,
+        c::GPU_TEXCOLOR_GPU_RGB8 => 24,
+        c::GPU_TEXCOLOR_GPU_RGBA5551
+        | c::GPU_TEXCOLOR_GPU_RGB565
+        | c::GPU_TEXCOLOR_GPU_RGBA4
+        | c::GPU_TEXCOLOR_GPU_LA8
+        | c::GPU_TEXCOLOR_GPU_HILO8 => 16,
+        c::GPU_TEXCOLOR_GPU_L8
+        | c::GPU_TEXCOLOR_GPU_A8
+        | c::GPU_TEXCOLOR_GPU_LA4
+        | c::GPU_TEXCOLOR_GPU_ETC1A4 => 8,
+        // the remaining formats are L4, A4, and ETC1
+        _ => 4,
+    }
+}
+
 /// A verified texture dimension.
 #[derive(Clone, Copy)]
 pub struct TexDim(NonZeroU16);
@@ -288,6 +459,19 @@ impl Display for TexDimError {
 
 impl Error for TexDimError {}
 
+/// A region given to upload to a texture didn't fit in it, didn't match the
+/// number of pixels, or the pixels were in the wrong format.
+#[derive(Debug)]
+pub struct TexRegionError;
+
+impl Display for TexRegionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "texture region does not match texture")
+    }
+}
+
+impl Error for TexRegionError {}
+
 impl TexDim {
     // maximum size permitted by citro2d
     const MAX: u16 = 1024;
@@ -309,6 +493,28 @@ impl TexDim {
         Ok(unsafe { Self::assume_valid(result) })
     }
 
+    /// Get the texture dimensions needed to fit an image of the given size.
+    pub fn for_image(width: u16, height: u16) -> Result<(Self, Self), TexDimError> {
+        Ok((Self::to_fit(width)?, Self::to_fit(height)?))
+    }
+
+    /// Check that a dimension is a power of two in the valid range. Usable in
+    /// const contexts, for dimensions known ahead of time.
+    pub const fn from_power_of_two(n: u32) -> Option<Self> {
+        if n < 8 || n > Self::MAX as u32 || !n.is_power_of_two() {
+            return None;
+        }
+        match NonZeroU16::new(n as u16) {
+            Some(dim) => Some(Self(dim)),
+            None => None,
+        }
+    }
+
+    /// The number of bytes used by a texture of the given size and format.
+    pub fn area_bytes(width: Self, height: Self, format: c::GPU_TEXCOLOR) -> usize {
+        usize::from(width.0.get()) * usize::from(height.0.get()) * bits_per_pixel(format) / 8
+    }
+
     pub unsafe fn assume_valid(dim: u16) -> Self {
         Self(NonZeroU16::new_unchecked(dim))
     }
@@ -345,6 +551,31 @@ impl<'gfx, T: TextureFormat> Texture<'gfx, T> {
     pub fn set_filter(&mut self, filter: bool) {
         self.any.set_filter(filter);
     }
+
+    #[inline]
+    pub fn set_wrap(&mut self, u_wrap: WrapParam, v_wrap: WrapParam) {
+        self.any.set_wrap(u_wrap, v_wrap);
+    }
+}
+
+/// What a texture shows outside of its bounds.
+#[derive(Clone, Copy)]
+pub enum WrapParam {
+    ClampToEdge,
+    ClampToBorder,
+    Repeat,
+    MirroredRepeat,
+}
+
+impl From<WrapParam> for c::GPU_TEXTURE_WRAP_PARAM {
+    fn from(value: WrapParam) -> Self {
+        match value {
+            WrapParam::ClampToEdge => c::GPU_TEXTURE_WRAP_PARAM_GPU_CLAMP_TO_EDGE,
+            WrapParam::ClampToBorder => c::GPU_TEXTURE_WRAP_PARAM_GPU_CLAMP_TO_BORDER,
+            WrapParam::Repeat => c::GPU_TEXTURE_WRAP_PARAM_GPU_REPEAT,
+            WrapParam::MirroredRepeat => c::GPU_TEXTURE_WRAP_PARAM_GPU_MIRRORED_REPEAT,
+        }
+    }
 }
 
 /// A format-agnostic texture reference.
@@ -380,6 +611,8 @@ impl<'gfx> AnyTexture<'gfx> {
                 c::GPU_TEXTURE_WRAP_PARAM_GPU_CLAMP_TO_BORDER,
             );
         }
+        TEXTURE_COUNT.fetch_add(1, Ordering::Relaxed);
+        TEXTURE_BYTES.fetch_add(TexDim::area_bytes(width, height, format), Ordering::Relaxed);
         Ok(Self {
             tex,
             owned: true,
@@ -425,6 +658,12 @@ impl<'gfx> AnyTexture<'gfx> {
         }
     }
 
+    pub fn set_wrap(&mut self, u_wrap: WrapParam, v_wrap: WrapParam) {
+        unsafe {
+            c::C3D_TexSetWrap_NotInlined(&mut self.tex, u_wrap.into(), v_wrap.into());
+        }
+    }
+
     pub fn width(&self) -> u16 {
         // SAFETY: This union exists only as a convenience to group width and height
         // as a single integer. Both of its variants are always valid.
@@ -443,9 +682,73 @@ impl<'gfx> AnyTexture<'gfx> {
     }
 
     /// Flush the GPU cache for this texture. Only valid if not a cubemap.
-    pub fn flush(&mut self) {
+    pub fn flush(&self) {
+        // C3D_TexFlush does not modify the texture, it just isn't declared
+        // as taking a constant pointer.
+        unsafe {
+            c::C3D_TexFlush(&self.tex as *const _ as *mut _);
+        }
+    }
+
+    /// Copy pixels into part of the texture, row by row, and flush only that
+    /// part. Cheaper than rebuilding the texture when little has changed.
+    pub fn upload_region<T>(
+        &self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        pixels: &[T::Pixel],
+    ) -> Result<(), TexRegionError>
+    where
+        T: TextureFormat,
+        T::Pixel: Copy,
+    {
+        let in_bounds = u32::from(x) + u32::from(w) <= u32::from(self.width())
+            && u32::from(y) + u32::from(h) <= u32::from(self.height());
+        if self.tex.fmt() != T::FORMAT
+            || !in_bounds
+            || pixels.len() != usize::from(w) * usize::from(h)
+        {
+            return Err(TexRegionError);
+        }
+        let mut pixels = pixels.iter();
+        for py in 0..h {
+            for px in 0..w {
+                // SAFETY: the region and pixel count were checked above
+                unsafe {
+                    T::set(
+                        self.data_ptr(),
+                        x + px,
+                        y + py,
+                        self.width(),
+                        *pixels.next().unwrap_unchecked(),
+                    );
+                }
+            }
+        }
+        self.flush_region(x, y, w, h);
+        Ok(())
+    }
+
+    /// Flush the GPU cache for the part of the texture holding the given
+    /// region. Textures are stored in rows of 8x8 tiles, so every tile row
+    /// the region touches is flushed. Only valid if not a cubemap.
+    pub fn flush_region(&self, _x: u16, y: u16, _w: u16, h: u16) {
+        if h == 0 {
+            return;
+        }
+        let row_bytes = usize::from(self.width()) * bits_per_pixel(self.tex.fmt()) / 8;
+        let top = usize::from(y) & !7;
+        let bottom = ((usize::from(y) + usize::from(h) + 7) & !7).min(self.height().into());
+        let start = top * row_bytes;
+        let size = (bottom - top) * row_bytes;
+        // SAFETY: the range is clamped to the texture's rows
         unsafe {
-            c::C3D_TexFlush(&mut self.tex);
+            c::GSPGPU_FlushDataCache(
+                (self.data_ptr() as *const u8).add(start) as *const _,
+                size as u32,
+            );
         }
     }
 }
@@ -456,6 +759,16 @@ impl<'gfx> Drop for AnyTexture<'gfx> {
             unsafe {
                 c::C3D_TexDelete(&mut self.tex);
             }
+            TEXTURE_COUNT.fetch_sub(1, Ordering::Relaxed);
+            // SAFETY: owned textures were made from valid dimensions in new
+            let (width, height) = unsafe {
+                (
+                    TexDim::assume_valid(self.width()),
+                    TexDim::assume_valid(self.height()),
+                )
+            };
+            let bytes = TexDim::area_bytes(width, height, self.tex.fmt());
+            TEXTURE_BYTES.fetch_sub(bytes, Ordering::Relaxed);
         }
     }
 }
@@ -487,6 +800,17 @@ impl<'gfx> Image<'gfx> {
         Self::new_texcoord(texture, width, height, left, top, right, bottom)
     }
 
+    /// Create an image from a region of a texture, in pixels.
+    pub fn atlas_region(
+        texture: Pin<Rc<AnyTexture<'gfx>>>,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+    ) -> Self {
+        Self::new(texture, f32::from(x), f32::from(y), width, height)
+    }
+
     pub fn new_texcoord(
         texture: Pin<Rc<AnyTexture<'gfx>>>,
         width: u16,
@@ -518,19 +842,85 @@ impl<'gfx> Image<'gfx> {
         }
     }
 
+    /// Create another image of the same region of the same texture, without
+    /// copying the texture. The clone gets its own Tex3DS_SubTexture rather
+    /// than sharing this one, as each Image frees its subtexture when dropped.
+    pub fn clone_ref(&self) -> Image<'gfx> {
+        // SAFETY: subtex was leaked from a Box in new_texcoord, and is only
+        // freed when self is dropped
+        let subtex = unsafe { &*self.image.subtex };
+        Self::new_texcoord(
+            self._texture.clone(),
+            subtex.width,
+            subtex.height,
+            subtex.left,
+            subtex.top,
+            subtex.right,
+            subtex.bottom,
+        )
+    }
+
+    /// Create an image of part of this image, in pixels from its top left.
+    /// This works for images that are themselves regions of an atlas.
+    pub fn region(&self, x: u16, y: u16, width: u16, height: u16) -> Image<'gfx> {
+        // SAFETY: subtex was leaked from a Box in new_texcoord, and is only
+        // freed when self is dropped
+        let subtex = unsafe { &*self.image.subtex };
+        let w = f32::from(subtex.width);
+        let h = f32::from(subtex.height);
+        let lerp_x = |x: u16| subtex.left + (subtex.right - subtex.left) * f32::from(x) / w;
+        let lerp_y = |y: u16| subtex.top + (subtex.bottom - subtex.top) * f32::from(y) / h;
+        Self::new_texcoord(
+            self._texture.clone(),
+            width,
+            height,
+            lerp_x(x),
+            lerp_y(y),
+            lerp_x(x + width),
+            lerp_y(y + height),
+        )
+    }
+
+    /// Create an image whose texture coordinates cover this image the given
+    /// number of times across and down, for drawing it tiled. The size stays
+    /// that of one tile, so it should be drawn scaled by the counts.
+    pub fn repeated(&self, columns: f32, rows: f32) -> Image<'gfx> {
+        // SAFETY: subtex was leaked from a Box in new_texcoord, and is only
+        // freed when self is dropped
+        let subtex = unsafe { &*self.image.subtex };
+        Self::new_texcoord(
+            self._texture.clone(),
+            subtex.width,
+            subtex.height,
+            subtex.left,
+            subtex.top,
+            subtex.left + (subtex.right - subtex.left) * columns,
+            subtex.top + (subtex.bottom - subtex.top) * rows,
+        )
+    }
+
+    /// The width and height of the image, in pixels.
+    pub fn size(&self) -> (u16, u16) {
+        // SAFETY: subtex was leaked from a Box in new_texcoord, and is only
+        // freed when self is dropped
+        let subtex = unsafe { &*self.image.subtex };
+        (subtex.width, subtex.height)
+    }
+
     /// Helper function to build texture and turn into image.
     pub fn build<T, F>(
         c2d: &'gfx Citro2d,
         width: u16,
         height: u16,
         f: F,
-    ) -> Result<Self, Box<dyn Error>>
+    ) -> Result<Self, Box<dyn Error + Send + Sync>>
     where
         T: TextureFormat,
         F: FnOnce(&mut Texture<'gfx, T>),
     {
         // create texture
-        let mut texture = Texture::new(c2d, TexDim::to_fit(width)?, TexDim::to_fit(height)?)?;
+        let (tex_width, tex_height) = TexDim::for_image(width, height)?;
+        let mut texture = Texture::new(c2d, tex_width, tex_height)?;
         // initialize it
         f(&mut texture);
         // flush cache automatically
@@ -588,3 +978,31 @@ impl<'gfx> Drop for Image<'gfx> {
         }
     }
 }
+
+/// An image split into a 3x3 grid, to be drawn at any size by
+/// Scene2d::draw_nine_slice.
+pub struct NineSlice<'gfx> {
+    /// The pieces of the image in rows from the top, each from the left, or
+    /// None for pieces that are empty.
+    pieces: [[Option<Image<'gfx>>; 3]; 3],
+    /// The width of the edges, in pixels.
+    border: u16,
+}
+
+impl<'gfx> NineSlice<'gfx> {
+    /// Split an image into a 3x3 grid with edges `border` pixels wide.
+    pub fn new(img: &Image<'gfx>, border: u16) -> Self {
+        let (img_w, img_h) = img.size();
+        let inner_w = img_w.saturating_sub(border * 2);
+        let inner_h = img_h.saturating_sub(border * 2);
+        // the pieces of the image, as an offset and a size in pixels
+        let columns = [(0, border), (border, inner_w), (border + inner_w, border)];
+        let rows = [(0, border), (border, inner_h), (border + inner_h, border)];
+        let pieces = rows.map(|(src_y, src_h)| {
+            columns.map(|(src_x, src_w)| {
+                (src_w != 0 && src_h != 0).then(|| img.region(src_x, src_y, src_w, src_h))
+            })
+        });
+        Self { pieces, border }
+    }
+}
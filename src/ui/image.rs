@@ -1,28 +1,294 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     error::Error,
+    fmt::Display,
+    hash::{Hash, Hasher},
     io::Cursor,
+    pin::Pin,
+    rc::Rc,
     sync::{Arc, Mutex},
 };
 
-use crate::net::retriever::{Method, Request, Retriever};
+use image::ColorType;
+
+use crate::{
+    config::Config,
+    net::{
+        curl::CurlError,
+        retriever::{Method, QueueFullError, Request, RequestAuth, Retriever, MAX_BATCH_SIZE},
+    },
+};
 
 use super::{
-    citro2d::{Image, RGBA8},
-    LogicImgPool, OpaqueImg,
+    citro2d::{
+        AnyTexture, C2dMemError, Citro2d, Image, Luminance8, TexDim, TextureFormat, LA8, RGB565,
+        RGBA8,
+    },
+    LogicImgPool, OpaqueImg, WeakImg,
+};
+
+/// The width and height of the avatar atlas texture.
+const ATLAS_SIZE: u16 = 256;
+
+/// The atlas size as a texture dimension.
+const ATLAS_DIM: TexDim = match TexDim::from_power_of_two(ATLAS_SIZE as u32) {
+    Some(dim) => dim,
+    None => panic!("atlas size is not a valid texture dimension"),
 };
 
+/// Images no larger than this in either dimension are packed into the atlas.
+const ATLAS_MAX_IMAGE: u16 = 64;
+
+/// JPEG files start with these bytes.
+const JPEG_MAGIC: [u8; 2] = [0xff, 0xd8];
+
+/// The largest image file that will be downloaded.
+const MAX_DOWNLOAD_SIZE: u64 = 4 * 1024 * 1024;
+
+/// An image couldn't be added to the atlas, because it was too large or
+/// there was no memory for another page.
+#[derive(Debug)]
+pub struct AtlasFullError;
+
+impl Display for AtlasFullError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no room in the avatar atlas")
+    }
+}
+
+impl Error for AtlasFullError {}
+
+/// One texture of the atlas, filled a shelf at a time.
+struct AtlasPage<'gfx> {
+    texture: Pin<Rc<AnyTexture<'gfx>>>,
+    /// The x position of the next image on the current shelf.
+    x: u16,
+    /// The top of the current shelf.
+    shelf_y: u16,
+    /// The height of the tallest image on the current shelf.
+    shelf_height: u16,
+}
+
+impl<'gfx> AtlasPage<'gfx> {
+    fn new(c2d: &'gfx Citro2d) -> Result<Self, C2dMemError> {
+        let texture = AnyTexture::new(c2d, ATLAS_DIM, ATLAS_DIM, RGBA8::FORMAT)?;
+        Ok(Self {
+            texture: Rc::pin(texture),
+            x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+        })
+    }
+
+    /// Find room for an image, returning its top left corner, or None if the
+    /// page is full.
+    fn place(&mut self, w: u16, h: u16) -> Option<(u16, u16)> {
+        // start a new shelf if this one is out of room
+        if self.x + w > ATLAS_SIZE {
+            self.shelf_y += self.shelf_height;
+            self.x = 0;
+            self.shelf_height = 0;
+        }
+        if self.shelf_y + h > ATLAS_SIZE {
+            return None;
+        }
+        let result = (self.x, self.shelf_y);
+        self.x += w;
+        self.shelf_height = self.shelf_height.max(h);
+        Some(result)
+    }
+}
+
+/// Packs small images, such as avatars, into shared textures. A new page is
+/// started whenever the last one fills up.
+pub struct AvatarAtlas<'gfx> {
+    c2d: &'gfx Citro2d,
+    /// Only the last page has room for more images.
+    pages: Vec<AtlasPage<'gfx>>,
+    /// Images already in the atlas, and their pixels, by a hash of their
+    /// size and pixels. Many accounts share the same default avatar, so this
+    /// saves space. The pixels are kept so that images whose hashes collide
+    /// aren't mistaken for each other.
+    added: HashMap<u64, (Vec<u32>, Image<'gfx>)>,
+}
+
+impl<'gfx> AvatarAtlas<'gfx> {
+    pub fn new(c2d: &'gfx Citro2d) -> Result<Self, C2dMemError> {
+        Ok(Self {
+            c2d,
+            pages: vec![AtlasPage::new(c2d)?],
+            added: HashMap::new(),
+        })
+    }
+
+    /// Copy an image into the atlas. Space is never reclaimed, so the atlas
+    /// is best suited to images that are loaded once and kept around.
+    pub fn add_avatar(
+        &mut self,
+        pixels: &[u32],
+        w: u16,
+        h: u16,
+    ) -> Result<Image<'gfx>, AtlasFullError> {
+        if pixels.len() != usize::from(w) * usize::from(h) || w > ATLAS_SIZE || h > ATLAS_SIZE {
+            return Err(AtlasFullError);
+        }
+        let mut hasher = DefaultHasher::new();
+        (w, h, pixels).hash(&mut hasher);
+        let hash = hasher.finish();
+        if let Some((added_pixels, image)) = self.added.get(&hash) {
+            if image.size() == (w, h) && added_pixels[..] == *pixels {
+                return Ok(image.clone_ref());
+            }
+        }
+        let placed = self.pages.last_mut().and_then(|page| page.place(w, h));
+        let (x, y) = match placed {
+            Some(position) => position,
+            None => {
+                let mut page = AtlasPage::new(self.c2d).map_err(|_| AtlasFullError)?;
+                // a new page always has room, as the size was checked above
+                let position = page.place(w, h).ok_or(AtlasFullError)?;
+                self.pages.push(page);
+                position
+            }
+        };
+        // the page that was placed on is now the last one
+        let texture = &self.pages.last().unwrap().texture;
+        // the region fits, as the page placed it
+        texture
+            .upload_region::<RGBA8>(x, y, w, h, pixels)
+            .map_err(|_| AtlasFullError)?;
+        let image = Image::atlas_region(texture.clone(), x, y, w, h);
+        let result = image.clone_ref();
+        // if the hash collided, the image added first keeps it
+        self.added
+            .entry(hash)
+            .or_insert_with(|| (pixels.to_vec(), image));
+        Ok(result)
+    }
+}
+
+/// Build a standalone RGBA8 image from a list of pixels.
+pub fn build_rgba8<'gfx>(
+    c2d: &'gfx Citro2d,
+    pixels: &[u32],
+    width: u16,
+    height: u16,
+) -> Result<Image<'gfx>, Box<dyn Error + Send + Sync>> {
+    Image::build::<RGBA8, _>(c2d, width, height, |tex| {
+        let mut pixels = pixels.iter();
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(&color) = pixels.next() {
+                    unsafe {
+                        tex.set_unchecked(x, y, color);
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Decode a JPEG straight to RGB pixels, skipping the format detection and
+/// RGBA conversion of the image crate. If a maximum scale is given, the JPEG
+/// is decoded at the smallest size that is still at least that large.
+/// Greyscale and CMYK JPEGs are refused, and left to the image crate.
+fn decode_jpeg_rgb(
+    buffer: &[u8],
+    max_scale: Option<u16>,
+    config: &Config,
+) -> Result<(Vec<u8>, u16, u16), Box<dyn Error + Send + Sync>> {
+    let mut decoder = jpeg_decoder::Decoder::new(buffer);
+    decoder.read_info()?;
+    let info = decoder.info().ok_or("JPEG has no header")?;
+    if info.pixel_format != jpeg_decoder::PixelFormat::RGB24 {
+        return Err("JPEG is not in RGB".into());
+    }
+    let max_dimension = config.max_image_dimension;
+    if u32::from(info.width) > max_dimension || u32::from(info.height) > max_dimension {
+        return Err("JPEG is too large".into());
+    }
+    let (width, height) = match max_scale {
+        Some(max_scale) => decoder.scale(max_scale, max_scale)?,
+        None => (info.width, info.height),
+    };
+    let pixels = decoder.decode()?;
+    Ok((pixels, width, height))
+}
+
+/// The size of an image scaled down to fit in a square, keeping its aspect
+/// ratio.
+fn fit_within(width: u32, height: u32, max: u32) -> (u32, u32) {
+    let ratio = f64::min(
+        f64::from(max) / f64::from(width),
+        f64::from(max) / f64::from(height),
+    );
+    let fit = |dim: u32| ((f64::from(dim) * ratio).round() as u32).max(1);
+    (fit(width), fit(height))
+}
+
+/// Make an image from RGB pixels, such as those of a JPEG. They have no
+/// alpha, so large images use the smaller RGB565 format.
+fn convert_rgb(
+    pool: &LogicImgPool,
+    pixels: Vec<u8>,
+    width: u16,
+    height: u16,
+    max_scale: Option<u16>,
+) -> Result<(u16, u16, OpaqueImg), Box<dyn Error + Send + Sync>> {
+    let mut img = image::RgbImage::from_raw(width.into(), height.into(), pixels)
+        .ok_or("wrong number of pixels for image size")?;
+    if let Some(max_scale) = max_scale {
+        let max_scale = u32::from(max_scale);
+        if img.width() > max_scale || img.height() > max_scale {
+            let (w, h) = fit_within(img.width(), img.height(), max_scale);
+            img = image::imageops::resize(&img, w, h, image::imageops::FilterType::Triangle);
+        }
+    }
+    let width = img.width() as u16;
+    let height = img.height() as u16;
+
+    if width <= ATLAS_MAX_IMAGE && height <= ATLAS_MAX_IMAGE {
+        let pixels = img
+            .pixels()
+            .map(|p| u32::from_be_bytes([p.0[0], p.0[1], p.0[2], 0xff]))
+            .collect();
+        return Ok((width, height, pool.alloc_atlas(pixels, width, height)));
+    }
+    let result = pool.alloc(width, height, move |c2d| {
+        Image::build::<RGB565, _>(c2d, width, height, |tex| {
+            let mut pixels = img.pixels();
+            for y in 0..height {
+                for x in 0..width {
+                    unsafe {
+                        let [r, g, b] = pixels.next().unwrap_unchecked().0;
+                        tex.set_unchecked(x, y, RGB565::pack(r, g, b));
+                    }
+                }
+            }
+        })
+    });
+    Ok((width, height, result))
+}
+
 fn convert_image(
     pool: &LogicImgPool,
     buffer: &[u8],
     max_scale: Option<u16>,
+    config: &Config,
 ) -> Result<(u16, u16, OpaqueImg), Box<dyn Error + Send + Sync>> {
+    // JPEGs are never transparent, so they don't need converting to RGBA.
+    // anything the fast path can't handle goes through the image crate
+    if buffer.starts_with(&JPEG_MAGIC) {
+        if let Ok((pixels, width, height)) = decode_jpeg_rgb(buffer, max_scale, config) {
+            return convert_rgb(pool, pixels, width, height, max_scale);
+        }
+    }
     let mut reader = image::io::Reader::new(Cursor::new(&buffer));
 
     let mut limits = image::io::Limits::default();
-    limits.max_image_width = Some(1024);
-    limits.max_image_height = Some(1024);
-    limits.max_alloc = Some(8 * 1024 * 1024);
+    limits.max_image_width = Some(config.max_image_dimension);
+    limits.max_image_height = Some(config.max_image_dimension);
+    limits.max_alloc = Some(config.max_image_alloc);
     reader.limits(limits);
     let mut img = reader.with_guessed_format()?.decode()?;
     // if custom scale requested, use that
@@ -32,11 +298,56 @@ fn convert_image(
             img = img.resize(max_scale, max_scale, image::imageops::FilterType::Triangle);
         }
     }
-    let img = img.to_rgba8();
-
     let width = img.width() as u16;
     let height = img.height() as u16;
-    let result = pool.alloc(move |c2d| {
+    // greyscale images can use smaller texture formats
+    match img.color() {
+        ColorType::L8 => {
+            let img = img.into_luma8();
+            let result = pool.alloc(width, height, move |c2d| {
+                Image::build::<Luminance8, _>(c2d, width, height, |tex| {
+                    let mut pixels = img.pixels();
+                    for y in 0..height {
+                        for x in 0..width {
+                            unsafe {
+                                let [luminance] = pixels.next().unwrap_unchecked().0;
+                                tex.set_unchecked(x, y, luminance);
+                            }
+                        }
+                    }
+                })
+            });
+            return Ok((width, height, result));
+        }
+
+        ColorType::La8 => {
+            let img = img.into_luma_alpha8();
+            let result = pool.alloc(width, height, move |c2d| {
+                Image::build::<LA8, _>(c2d, width, height, |tex| {
+                    let mut pixels = img.pixels();
+                    for y in 0..height {
+                        for x in 0..width {
+                            unsafe {
+                                let color = u16::from_be_bytes(pixels.next().unwrap_unchecked().0);
+                                tex.set_unchecked(x, y, color);
+                            }
+                        }
+                    }
+                })
+            });
+            return Ok((width, height, result));
+        }
+
+        _ => {}
+    }
+    let img = img.to_rgba8();
+
+    // small images share a texture to save memory
+    if width <= ATLAS_MAX_IMAGE && height <= ATLAS_MAX_IMAGE {
+        let pixels = img.pixels().map(|p| u32::from_be_bytes(p.0)).collect();
+        return Ok((width, height, pool.alloc_atlas(pixels, width, height)));
+    }
+    let result = pool.alloc(width, height, move |c2d| {
         // TODO don't use RGBA8 if not necessary -
         // use rgb565 if there's no alpha, for instance
         Image::build::<RGBA8, _>(c2d, width, height, |tex| {
@@ -55,6 +366,18 @@ fn convert_image(
     Ok((width, height, result))
 }
 
+fn is_too_large(e: &(dyn Error + Send + Sync + 'static)) -> bool {
+    match e.downcast_ref::<CurlError>() {
+        Some(e) => e.is_file_size_exceeded(),
+        None => false,
+    }
+}
+
+/// Make a grey image to stand in for an image that couldn't be loaded.
+fn placeholder(pool: &LogicImgPool) -> (u16, u16, OpaqueImg) {
+    (1, 1, pool.alloc_atlas(vec![0x808080ff], 1, 1))
+}
+
 pub struct WebImage {
     pub width: u16,
     pub height: u16,
@@ -85,7 +408,7 @@ impl Drop for CachedImage {
             // 2. the entry in WebImageCache.
             // so, we should tell the cache to remove the entry, as no one else
             // will be pointing to it after we're dropped.
-            self.cache.remove(&self.image.url);
+            self.cache.remove(&self.image);
         }
     }
 }
@@ -94,24 +417,86 @@ impl Drop for CachedImage {
 pub struct WebImageCache {
     /// Contains references to all web images in use. Wrapped to allow interior mutability.
     entries: Mutex<HashMap<String, Arc<WebImage>>>,
+    /// The limits on decoding images.
+    config: Mutex<Config>,
 }
 
 impl WebImageCache {
-    pub fn new() -> Self {
+    pub fn new(config: Config) -> Self {
         Self {
             entries: Mutex::new(HashMap::new()),
+            config: Mutex::new(config),
         }
     }
 
+    /// Change the limits on decoding images. Images already loaded are kept.
+    pub fn set_config(&self, config: Config) {
+        *self.config.lock().unwrap() = config;
+    }
+
     pub fn get(
         self: &Arc<Self>,
         retriever: &Retriever,
         pool: &LogicImgPool,
         images: &[(&str, Option<u16>)],
     ) -> Result<Vec<CachedImage>, Box<dyn Error + Send + Sync>> {
+        self.get_with_progress(retriever, pool, images, |_| {})
+    }
+
+    /// Get a single image.
+    pub fn get_single(
+        self: &Arc<Self>,
+        retriever: &Retriever,
+        pool: &LogicImgPool,
+        url: &str,
+        max_scale: Option<u16>,
+    ) -> Result<CachedImage, Box<dyn Error + Send + Sync>> {
+        Ok(self.get(retriever, pool, &[(url, max_scale)])?.remove(0))
+    }
+
+    /// Get a single image, using a placeholder if it couldn't be loaded.
+    pub fn get_or_placeholder(
+        self: &Arc<Self>,
+        retriever: &Retriever,
+        pool: &LogicImgPool,
+        url: &str,
+        max_scale: Option<u16>,
+    ) -> CachedImage {
+        match self.get_single(retriever, pool, url, max_scale) {
+            Ok(image) => image,
+            Err(_) => {
+                // the placeholder isn't stored in the cache, so that loading
+                // the image can be tried again later
+                let (width, height, image) = placeholder(pool);
+                CachedImage {
+                    image: Arc::new(WebImage {
+                        width,
+                        height,
+                        image: Mutex::new(image),
+                        url: url.into(),
+                    }),
+                    cache: self.clone(),
+                }
+            }
+        }
+    }
+
+    /// Like get, but calls the given function with the fraction of images
+    /// loaded so far as each one finishes downloading.
+    pub fn get_with_progress<F>(
+        self: &Arc<Self>,
+        retriever: &Retriever,
+        pool: &LogicImgPool,
+        images: &[(&str, Option<u16>)],
+        mut progress: F,
+    ) -> Result<Vec<CachedImage>, Box<dyn Error + Send + Sync>>
+    where
+        F: FnMut(f32),
+    {
         let mut requests = vec![];
         let mut request_info = vec![];
         let mut added_requests = HashSet::new();
+        let config = *self.config.lock().unwrap();
         let mut entries = self.entries.lock().unwrap();
         for (url, max_scale) in images {
             // ensure each entry exists
@@ -120,29 +505,61 @@ impl WebImageCache {
                 requests.push(Request {
                     method: Method::Get,
                     url: url_string.clone(),
+                    max_size: Some(MAX_DOWNLOAD_SIZE),
+                    // images may be on other servers, and never need a token
+                    auth: RequestAuth::None,
                 });
                 added_requests.insert(url_string);
                 request_info.push((url, max_scale));
             }
         }
-        let responses = retriever.request(requests);
-        for (url, max_scale) in request_info {
-            let response = responses.recv().unwrap()?;
-            // add image
-            let (width, height, image) = convert_image(pool, &response, *max_scale)?;
-            let image = Arc::new(WebImage {
-                width,
-                height,
-                image: Mutex::new(image),
-                url: String::from(*url),
-            });
-            // store in cache
-            entries.insert(String::from(*url), image);
+        // placeholders for images that couldn't be queued
+        let mut uncached = HashMap::new();
+        let total = request_info.len();
+        let mut done = 0;
+        // queue a few at a time, so that they fit in the queue
+        let mut requests = requests.into_iter().enumerate().collect::<Vec<_>>();
+        while !requests.is_empty() {
+            let rest = requests.split_off(requests.len().min(MAX_BATCH_SIZE));
+            let count = requests.len();
+            let responses = retriever.batch_request(std::mem::replace(&mut requests, rest));
+            for _ in 0..count {
+                // responses may not arrive in the order they were requested
+                let (id, response) = responses.recv().unwrap();
+                let (url, max_scale) = request_info[id];
+                // add image, or a placeholder if it was too big to download. if
+                // there wasn't room to queue it, the placeholder isn't stored in
+                // the cache, so that loading the image can be tried again later
+                let ((width, height, image), cache) = match response {
+                    // an image that can't be decoded within the limits shouldn't
+                    // stop the others from loading
+                    Ok(response) => match convert_image(pool, &response, *max_scale, &config) {
+                        Ok(image) => (image, true),
+                        Err(_) => (placeholder(pool), true),
+                    },
+                    Err(e) if is_too_large(e.as_ref()) => (placeholder(pool), true),
+                    Err(e) if e.is::<QueueFullError>() => (placeholder(pool), false),
+                    Err(e) => return Err(e),
+                };
+                let image = Arc::new(WebImage {
+                    width,
+                    height,
+                    image: Mutex::new(image),
+                    url: String::from(*url),
+                });
+                if cache {
+                    entries.insert(String::from(*url), image);
+                } else {
+                    uncached.insert(String::from(*url), image);
+                }
+                done += 1;
+                progress(done as f32 / total as f32);
+            }
         }
         // build result from reading cache
         let mut result = vec![];
         for (url, _) in images {
-            let image = entries.get(*url).unwrap();
+            let image = entries.get(*url).or_else(|| uncached.get(*url)).unwrap();
             // create cached image struct from this
             result.push(CachedImage {
                 image: image.clone(),
@@ -152,8 +569,34 @@ impl WebImageCache {
         Ok(result)
     }
 
-    fn remove(&self, url: &str) {
+    fn remove(&self, image: &Arc<WebImage>) {
+        let mut entries = self.entries.lock().unwrap();
+        // the entry may have been replaced since remove_pattern evicted it
+        if let Some(entry) = entries.get(&image.url) {
+            if Arc::ptr_eq(entry, image) {
+                entries.remove(&image.url);
+            }
+        }
+    }
+
+    /// Evict every image whose URL starts with the given prefix, so that they
+    /// are downloaded again next time. Images already in use stay loaded.
+    pub fn remove_pattern(&self, prefix: &str) {
         let mut entries = self.entries.lock().unwrap();
-        entries.remove(url);
+        entries.retain(|url, _| !url.starts_with(prefix));
+    }
+
+    /// The image at the given URL if it is already loaded, without
+    /// downloading it or keeping it loaded.
+    pub fn peek(&self, url: &str) -> Option<WeakImg> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(url)
+            .map(|image| image.image.lock().unwrap().downgrade())
+    }
+
+    /// The number of images in the cache.
+    pub fn entry_count(&self) -> usize {
+        self.entries.lock().unwrap().len()
     }
 }
@@ -0,0 +1,24 @@
+use ctru::{prelude::KeyPad, services::Hid};
+
+/// True if the touch screen is being touched.
+pub fn is_touching(hid: &Hid) -> bool {
+    hid.keys_held().contains(KeyPad::KEY_TOUCH)
+}
+
+/// True if the touch screen is touched now but wasn't last frame.
+pub fn touch_just_started(prev: Option<(u16, u16)>, curr: Option<(u16, u16)>) -> bool {
+    prev.is_none() && curr.is_some()
+}
+
+/// True if the touch screen was touched last frame but isn't now.
+pub fn touch_just_ended(prev: Option<(u16, u16)>, curr: Option<(u16, u16)>) -> bool {
+    prev.is_some() && curr.is_none()
+}
+
+/// How far a touch moved from one position to another.
+pub fn touch_delta(prev: (u16, u16), curr: (u16, u16)) -> (i32, i32) {
+    (
+        i32::from(curr.0) - i32::from(prev.0),
+        i32::from(curr.1) - i32::from(prev.1),
+    )
+}
@@ -1,7 +1,9 @@
-use ctru::applets::swkbd::{Button, Features, Filters, Swkbd};
+use ctru::applets::swkbd::{Button, Features, Filters, Kind, Swkbd};
 
 use std::{error::Error, fmt::Display};
 
+use super::KeyboardKind;
+
 #[derive(Clone, Copy, Debug)]
 pub struct KeyboardError(Option<ctru::applets::swkbd::Error>);
 
@@ -31,12 +33,24 @@ impl Display for KeyboardError {
 
 impl Error for KeyboardError {}
 
-pub fn get_input(hint: &str, restrict: bool, blank_allowed: bool) -> Result<String, KeyboardError> {
+impl KeyboardError {
+    /// True if the user closed the keyboard with the cancel button, rather
+    /// than something going wrong.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+pub fn get_input(
+    hint: &str,
+    kind: KeyboardKind,
+    blank_allowed: bool,
+) -> Result<String, KeyboardError> {
     let mut kbd = Swkbd::init(
-        if restrict {
-            ctru::applets::swkbd::Kind::Qwerty
-        } else {
-            ctru::applets::swkbd::Kind::Normal
+        match kind {
+            KeyboardKind::Normal => Kind::Normal,
+            KeyboardKind::Qwerty => Kind::Qwerty,
+            KeyboardKind::Numpad => Kind::Numpad,
         },
         1,
     );
@@ -44,7 +58,7 @@ pub fn get_input(hint: &str, restrict: bool, blank_allowed: bool) -> Result<Stri
     kbd.configure_button(Button::Left, "Cancel", false);
     kbd.configure_button(Button::Right, "OK", false);
     let mut features = Features::ALLOW_HOME | Features::ALLOW_RESET | Features::ALLOW_POWER;
-    if !restrict {
+    if kind == KeyboardKind::Normal {
         features |= Features::MULTILINE;
     }
     kbd.set_features(features);
@@ -1,63 +1,179 @@
 pub mod citro2d;
 mod image;
+mod input;
 mod kbd;
 pub mod screen;
+mod scroll;
 mod text;
 
 use std::{
     cell::RefCell,
     collections::HashMap,
     error::Error,
-    sync::{Arc, Mutex},
+    io::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::Sender,
+        Arc, Mutex, Weak,
+    },
 };
 
 pub use kbd::KeyboardError;
 
 use bit_set::BitSet;
-use ctru::services::{Apt, Hid};
+use ctru::{
+    prelude::KeyPad,
+    services::{Apt, Hid},
+};
+
+use crate::{
+    config::{Theme, ThemeColors, DEFAULT_FONT_SCALE},
+    html::HtmlSpan,
+};
 
 use self::{
-    citro2d::{color32, Citro2d, Image, RenderTarget, Scene2d},
+    citro2d::{Citro2d, Image, NineSlice, RenderTarget, Scene2d},
+    image::{build_rgba8, AvatarAtlas},
+    input::{is_touching, touch_delta, touch_just_ended, touch_just_started},
     text::{TextLines, TextRenderer},
 };
 
 pub use self::image::{CachedImage, WebImage, WebImageCache};
 
+/// The number of characters that fit on a line of the bottom screen.
+const CONSOLE_WIDTH: usize = 40;
+
+/// The furthest a touch can move, in pixels either way, and still be a tap.
+const TAP_DISTANCE: i32 = 4;
+
+/// How long a toast stays on the screen, in frames.
+const TOAST_FRAMES: u64 = 120;
+
+/// The width and height of the panel image.
+const PANEL_SIZE: u16 = 16;
+
+/// How far the corners of the panel image reach in from its edges.
+const PANEL_BORDER: u16 = 6;
+
+/// The radius of the panel's rounded corners, and the width of its outline.
+const PANEL_RADIUS: f32 = 5.0;
+const PANEL_OUTLINE: f32 = 2.0;
+
+/// Build the image drawn by Ui::draw_panel: a rounded box filled with the
+/// selection color and outlined with the accent color. It is split into its
+/// nine pieces here, rather than every time it is drawn.
+fn build_panel<'gfx>(
+    c2d: &'gfx Citro2d,
+    colors: &ThemeColors,
+) -> Result<NineSlice<'gfx>, Box<dyn Error + Send + Sync>> {
+    let size = f32::from(PANEL_SIZE);
+    let pixels = (0..PANEL_SIZE)
+        .flat_map(|y| (0..PANEL_SIZE).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            // measure from the center of the nearest corner's curve
+            let x = f32::from(x) + 0.5;
+            let y = f32::from(y) + 0.5;
+            let dx = x - x.clamp(PANEL_RADIUS, size - PANEL_RADIUS);
+            let dy = y - y.clamp(PANEL_RADIUS, size - PANEL_RADIUS);
+            let distance = dx.hypot(dy);
+            let color = if distance > PANEL_RADIUS {
+                0
+            } else if distance > PANEL_RADIUS - PANEL_OUTLINE {
+                colors.accent
+            } else {
+                colors.selection
+            };
+            // textures store the bytes the other way around from color32
+            color.swap_bytes()
+        })
+        .collect::<Vec<_>>();
+    let image = build_rgba8(c2d, &pixels, PANEL_SIZE, PANEL_SIZE)?;
+    Ok(NineSlice::new(&image, PANEL_BORDER))
+}
+
 pub struct Ui<'gfx, 'screen> {
     apt: Apt,
     hid: Hid,
 
     c2d: &'gfx Citro2d,
+    sender: UiMsgSender,
     receiver: UiMsgReceiver,
 
     target: RenderTarget<'gfx, 'screen>,
 
     pool: HashMap<usize, Image<'gfx>>,
+    atlas: AvatarAtlas<'gfx>,
     screen: Box<dyn Screen>,
+    /// The ID the current screen was pushed with, see UiMsg::PushTaggedScreen.
+    screen_id: Option<ScreenId>,
+    /// Screens hidden by PushScreen, most recent last, with their IDs.
+    stack: Vec<(Box<dyn Screen>, Option<ScreenId>)>,
+    /// Progress of the current loading operation, see UiMsg::Progress.
+    progress: f32,
+    /// Told when the user presses START+A to write a toot, see UiMsg::SetQuickToot.
+    quick_toot: Option<Sender<()>>,
+    /// The colors to draw screens with.
+    theme: ThemeColors,
+    /// Drawn by draw_panel, rebuilt when the theme changes.
+    panel: NineSlice<'gfx>,
+    /// The scale that text asked for at DEFAULT_FONT_SCALE is drawn at.
+    font_scale: f32,
+    /// The number of new notifications, see UiMsg::SetNotificationBadge.
+    notification_badge: usize,
+    /// A message to show, and the frame to stop showing it on.
+    toast: Option<(TextLines, u64)>,
+    /// Where the touch screen was touched last frame, if it was.
+    prev_touch: Option<(u16, u16)>,
+    /// Where the current touch started, if the screen is being touched.
+    touch_start: Option<(u16, u16)>,
 
     text_renderer: RefCell<TextRenderer<'gfx>>,
 }
 
 impl<'gfx: 'screen, 'screen> Ui<'gfx, 'screen> {
-    pub fn new(c2d: &'gfx Citro2d, receiver: UiMsgReceiver) -> Result<Self, Box<dyn Error>> {
+    pub fn new(
+        c2d: &'gfx Citro2d,
+        sender: UiMsgSender,
+        receiver: UiMsgReceiver,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
         let apt = Apt::init()?;
         let hid = Hid::init()?;
 
         let target = RenderTarget::new_2d(c2d, c2d.gfx().top_screen.borrow_mut())?;
 
         let pool = HashMap::new();
+        let atlas = AvatarAtlas::new(c2d)?;
         let screen = Box::new(EmptyScreen);
 
-        let text_renderer = RefCell::new(TextRenderer::new(c2d)?);
+        let mut text_renderer = TextRenderer::new(c2d)?;
+        // the extra font is optional, so ignore errors
+        _ = text_renderer.add_font_file(c2d, text::EXTRA_FONT_PATH);
+        let text_renderer = RefCell::new(text_renderer);
+
+        let theme = ThemeColors::from_theme(Theme::default());
+        let panel = build_panel(c2d, &theme)?;
 
         Ok(Self {
             apt,
             hid,
             c2d,
+            sender,
             receiver,
             target,
             pool,
+            atlas,
             screen,
+            screen_id: None,
+            stack: vec![],
+            progress: 1.0,
+            quick_toot: None,
+            theme,
+            panel,
+            font_scale: DEFAULT_FONT_SCALE,
+            notification_badge: 0,
+            toast: None,
+            prev_touch: None,
+            touch_start: None,
             text_renderer,
         })
     }
@@ -76,24 +192,86 @@ impl<'gfx: 'screen, 'screen> Ui<'gfx, 'screen> {
                     }
                 }
 
+                UiMsg::LoadAtlasImage {
+                    id,
+                    pixels,
+                    width,
+                    height,
+                } => {
+                    // fall back to a separate texture if the atlas is full
+                    let img = match self.atlas.add_avatar(&pixels, width, height) {
+                        Ok(img) => Ok(img),
+                        Err(_) => build_rgba8(self.c2d, &pixels, width, height),
+                    };
+                    if let Ok(img) = img {
+                        self.pool.insert(id, img);
+                    }
+                }
+
                 UiMsg::UnloadImage(id) => {
                     self.pool.remove(&id);
                 }
 
                 UiMsg::SetScreen(screen) => {
                     self.screen = screen;
+                    self.screen_id = None;
+                    self.stack.clear();
+                    self.show_title();
+                }
+
+                UiMsg::PushScreen(screen) => self.push_screen(screen, None),
+
+                UiMsg::PushTaggedScreen(id, screen) => self.push_screen(screen, Some(id)),
+
+                UiMsg::PopScreen => self.close_screen(None),
+
+                UiMsg::CloseScreen(id) => self.close_screen(Some(id)),
+
+                UiMsg::Progress(progress) => {
+                    self.progress = progress;
+                }
+
+                UiMsg::SetQuickToot(quick_toot) => {
+                    if self.quick_toot.is_none() {
+                        // let the user know about the shortcut, in the bottom right
+                        print!("\x1b[30;26H+Toot (START+A)");
+                        _ = std::io::stdout().flush();
+                    }
+                    self.quick_toot = Some(quick_toot);
+                }
+
+                UiMsg::SetTheme(theme) => {
+                    self.theme = ThemeColors::from_theme(theme);
+                    // keep the old panel if there's no memory for a new one
+                    if let Ok(panel) = build_panel(self.c2d, &self.theme) {
+                        self.panel = panel;
+                    }
+                }
+
+                UiMsg::SetFontScale(font_scale) => {
+                    self.font_scale = font_scale;
+                }
+
+                UiMsg::SetNotificationBadge(count) => {
+                    self.notification_badge = count;
+                }
+
+                UiMsg::Toast(text) => {
+                    let scale = self.text_scale(0.5);
+                    let mut renderer = self.text_renderer.borrow_mut();
+                    let lines = TextLines::new(&text, &mut renderer, 352.0, scale);
+                    self.toast = Some((lines, self.c2d.frame_count() + TOAST_FRAMES));
                 }
 
                 UiMsg::Flush => break,
 
                 UiMsg::Keyboard {
                     hint,
-                    restrict,
+                    kind,
                     blank_allowed,
                     tx,
                 } => {
-                    tx.send(kbd::get_input(hint, restrict, blank_allowed))
-                        .unwrap();
+                    tx.send(kbd::get_input(hint, kind, blank_allowed)).unwrap();
                 }
 
                 UiMsg::WordWrap {
@@ -102,29 +280,175 @@ impl<'gfx: 'screen, 'screen> Ui<'gfx, 'screen> {
                     scale,
                     tx,
                 } => {
+                    let scale = self.text_scale(scale);
                     let mut renderer = self.text_renderer.borrow_mut();
                     let lines = TextLines::new(&text, &mut renderer, width, scale);
                     tx.send(lines).unwrap();
                 }
 
+                UiMsg::WordWrapHtml {
+                    spans,
+                    width,
+                    scale,
+                    tx,
+                } => {
+                    let scale = self.text_scale(scale);
+                    let mut renderer = self.text_renderer.borrow_mut();
+                    let lines = TextLines::from_html_spans(&spans, &mut renderer, width, scale);
+                    tx.send(lines).unwrap();
+                }
+
+                UiMsg::WordWrapBatch { items, tx } => {
+                    let mut renderer = self.text_renderer.borrow_mut();
+                    let lines = items
+                        .iter()
+                        .map(|(text, width, scale)| {
+                            let scale = self.text_scale(*scale);
+                            TextLines::new(text, &mut renderer, *width, scale)
+                        })
+                        .collect();
+                    tx.send(lines).unwrap();
+                }
+
+                UiMsg::WordWrapHtmlBatch { items, tx } => {
+                    let mut renderer = self.text_renderer.borrow_mut();
+                    let lines = items
+                        .iter()
+                        .map(|(spans, width, scale)| {
+                            let scale = self.text_scale(*scale);
+                            TextLines::from_html_spans(spans, &mut renderer, *width, scale)
+                        })
+                        .collect();
+                    tx.send(lines).unwrap();
+                }
+
                 UiMsg::Quit => return false,
             }
         }
         // update the screen
         self.hid.scan_input();
-        self.screen.update(&self.hid);
-        // render the screen
-        let frame = self.c2d.begin_frame();
-        self.target.scene_2d(&frame, |ctx| {
-            self.screen.draw(&self, &self.target, ctx);
-        });
-        drop(frame);
+        let input = HidEvents::read(&self.hid, self.prev_touch, self.touch_start);
+        self.prev_touch = input.current_touch();
+        self.touch_start = self.prev_touch.and(input.touch_start);
+        // the quick toot shortcut works on every screen, so check it first
+        let quick_toot =
+            input.held.contains(KeyPad::KEY_START) && input.pressed.contains(KeyPad::KEY_A);
+        match &self.quick_toot {
+            Some(tx) if quick_toot => {
+                // ignore send errors, the logic thread may have already stopped
+                _ = tx.send(());
+            }
+            _ => self.screen.update(&input),
+        }
+        // render the screen, unless the GPU isn't ready, in which case it is
+        // skipped and tried again next frame
+        if let Ok(frame) = self.c2d.begin_frame() {
+            self.target.scene_2d(&frame, |ctx| {
+                self.screen.draw(&self, &self.target, ctx);
+                self.draw_toast(ctx);
+                self.draw_progress(ctx);
+                self.draw_notification_badge(ctx);
+            });
+            drop(frame);
+        }
         // wait for vblank
         self.c2d.gfx().wait_for_vblank();
         // continue running
         true
     }
 
+    fn push_screen(&mut self, screen: Box<dyn Screen>, id: Option<ScreenId>) {
+        let previous = std::mem::replace(&mut self.screen, screen);
+        let previous_id = std::mem::replace(&mut self.screen_id, id);
+        self.stack.push((previous, previous_id));
+        self.show_title();
+    }
+
+    /// Close the topmost screen pushed with the given ID, or without one if
+    /// None. The screen set with SetScreen is never closed.
+    fn close_screen(&mut self, id: Option<ScreenId>) {
+        if self.screen_id == id {
+            if let Some((screen, screen_id)) = self.stack.pop() {
+                self.screen = screen;
+                self.screen_id = screen_id;
+                self.screen.on_resume(&self.sender);
+                self.show_title();
+            }
+        } else {
+            let index = self.stack.iter().rposition(|(_, other)| *other == id);
+            // the bottom of the stack is the screen set with SetScreen
+            if let Some(index) = index.filter(|&index| index > 0) {
+                self.stack.remove(index);
+            }
+        }
+    }
+
+    /// Write the current screen's title centered on the first line of the
+    /// bottom screen, replacing the last one.
+    fn show_title(&self) {
+        let title = self.screen.title();
+        let column = CONSOLE_WIDTH.saturating_sub(title.chars().count()) / 2 + 1;
+        print!("\x1b[1;1H\x1b[2K\x1b[1;{}H{}", column, title);
+        _ = std::io::stdout().flush();
+    }
+
+    fn draw_progress(&self, ctx: &Scene2d) {
+        let color = self.theme.accent;
+        if self.progress <= 0.0 {
+            // unknown progress, so show a segment sliding across the screen
+            let x = (self.c2d.frame_count() % 125) as f32 * 4.0 - 100.0;
+            ctx.rect_solid(x, 0.0, 100.0, 4.0, color);
+        } else if self.progress < 1.0 {
+            let background = self.theme.selection;
+            self.draw_progress_bar(ctx, 0.0, 0.0, 400.0, 4.0, self.progress, color, background);
+        }
+    }
+
+    /// Draw the current toast at the bottom of the screen, if there is one.
+    fn draw_toast(&self, ctx: &Scene2d) {
+        if let Some((toast, until)) = &self.toast {
+            if self.c2d.frame_count() < *until {
+                let y = 220.0 - toast.height();
+                self.draw_panel(ctx, 16.0, y - 4.0, 368.0, toast.height() + 8.0);
+                self.draw_lines(ctx, 24.0, y, self.theme.foreground, toast);
+            }
+        }
+    }
+
+    /// Draw the number of new notifications in the top right corner.
+    fn draw_notification_badge(&self, ctx: &Scene2d) {
+        if self.notification_badge == 0 {
+            return;
+        }
+        let label = self.notification_badge.to_string();
+        let width = self.text_width(&label, 0.4) + 8.0;
+        let x = 396.0 - width;
+        ctx.rect_solid(x, 6.0, width, 14.0, self.theme.accent);
+        self.draw_text(ctx, x + 4.0, 7.0, 0.4, self.theme.foreground, &label);
+    }
+
+    /// Draw a bar filled from the left by the given fraction, which is
+    /// clamped between 0 and 1.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_progress_bar(
+        &self,
+        ctx: &Scene2d,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        progress: f32,
+        fg: u32,
+        bg: u32,
+    ) {
+        ctx.rect_solid(x, y, width, height, bg);
+        ctx.rect_solid(x, y, width * progress.clamp(0.0, 1.0), height, fg);
+    }
+
+    pub fn theme(&self) -> &ThemeColors {
+        &self.theme
+    }
+
     pub fn draw_opaque_img(
         &self,
         img: &OpaqueImg,
@@ -139,19 +463,133 @@ impl<'gfx: 'screen, 'screen> Ui<'gfx, 'screen> {
         }
     }
 
+    /// Draw an image if it is still loaded, or nothing if it has been freed.
+    pub fn draw_weak_img(
+        &self,
+        img: &WeakImg,
+        ctx: &Scene2d,
+        x: f32,
+        y: f32,
+        scale_x: f32,
+        scale_y: f32,
+    ) {
+        if !img.is_alive() {
+            return;
+        }
+        if let Some(img) = self.pool.get(&img.id) {
+            img.draw(ctx, x, y, scale_x, scale_y);
+        }
+    }
+
+    /// Draw an image scaled to fit within the given size, keeping its aspect ratio.
+    pub fn draw_image_fit(
+        &self,
+        img: &OpaqueImg,
+        ctx: &Scene2d,
+        x: f32,
+        y: f32,
+        max_w: f32,
+        max_h: f32,
+    ) {
+        let (scale_x, scale_y) = img.scale_to(max_w, max_h);
+        let scale = scale_x.min(scale_y);
+        self.draw_opaque_img(img, ctx, x, y, scale, scale);
+    }
+
+    /// Draw an image scaled to cover the given area, keeping its aspect ratio.
+    /// The image is centered on the area, and any overflow is not clipped.
+    pub fn draw_image_fill(&self, img: &OpaqueImg, ctx: &Scene2d, x: f32, y: f32, w: f32, h: f32) {
+        let (scale_x, scale_y) = img.scale_to(w, h);
+        let scale = scale_x.max(scale_y);
+        let x = x + (w - f32::from(img.width) * scale) / 2.0;
+        let y = y + (h - f32::from(img.height) * scale) / 2.0;
+        self.draw_opaque_img(img, ctx, x, y, scale, scale);
+    }
+
+    /// Cover the given area with copies of an image, at its original size.
+    pub fn draw_image_tiled(&self, img: &OpaqueImg, ctx: &Scene2d, x: f32, y: f32, w: f32, h: f32) {
+        if let Some(img) = self.pool.get(&img.id) {
+            ctx.image_tiled(img, x, y, w, h);
+        }
+    }
+
+    /// Draw a bordered box in the theme's colors, which can be any size.
+    pub fn draw_panel(&self, ctx: &Scene2d, x: f32, y: f32, w: f32, h: f32) {
+        ctx.draw_nine_slice(&self.panel, x, y, w, h);
+    }
+
+    /// Draw a single line of text without wrapping, for text that changes often.
+    pub fn draw_text(&self, ctx: &Scene2d, x: f32, y: f32, scale: f32, color: u32, text: &str) {
+        let scale = self.text_scale(scale);
+        let mut renderer = self.text_renderer.borrow_mut();
+        renderer.print(ctx, text, x, y, scale, color);
+    }
+
+    /// The width of a single line of text.
+    pub fn text_width(&self, text: &str, scale: f32) -> f32 {
+        let scale = self.text_scale(scale);
+        let mut renderer = self.text_renderer.borrow_mut();
+        renderer.text_width(text, scale)
+    }
+
+    /// The scale to draw text at, adjusted for the chosen font scale.
+    fn text_scale(&self, scale: f32) -> f32 {
+        scale * self.font_scale / DEFAULT_FONT_SCALE
+    }
+
     pub fn draw_lines(&self, ctx: &Scene2d, x: f32, y: f32, color: u32, lines: &TextLines) {
         let mut renderer = self.text_renderer.borrow_mut();
-        lines.render(&mut renderer, ctx, x, y, color);
+        lines.render(
+            &mut renderer,
+            ctx,
+            x,
+            y,
+            &self.theme,
+            color,
+            None,
+            |_, _, _, _| {},
+        );
+    }
+
+    /// Draw lines of text, with custom emojis looked up by shortcode. Lines
+    /// outside of the viewport, if given as (top, bottom), are skipped.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_lines_with_emojis(
+        &self,
+        ctx: &Scene2d,
+        x: f32,
+        y: f32,
+        color: u32,
+        lines: &TextLines,
+        emojis: &HashMap<String, CachedImage>,
+        viewport: Option<(f32, f32)>,
+    ) {
+        let mut renderer = self.text_renderer.borrow_mut();
+        lines.render(
+            &mut renderer,
+            ctx,
+            x,
+            y,
+            &self.theme,
+            color,
+            viewport,
+            |shortcode, x, y, size| {
+                if let Some(emoji) = emojis.get(shortcode) {
+                    let img = emoji.image().image.lock().unwrap();
+                    self.draw_image_fit(&img, ctx, x, y, size, size);
+                }
+            },
+        );
     }
 }
 
 pub trait ImageLoader:
-    Send + for<'gfx> FnOnce(&'gfx Citro2d) -> Result<Image<'gfx>, Box<dyn Error>>
+    Send + for<'gfx> FnOnce(&'gfx Citro2d) -> Result<Image<'gfx>, Box<dyn Error + Send + Sync>>
 {
 }
 
 impl<T> ImageLoader for T where
-    T: Send + for<'gfx> FnOnce(&'gfx Citro2d) -> Result<Image<'gfx>, Box<dyn Error>>
+    T: Send + for<'gfx> FnOnce(&'gfx Citro2d) -> Result<Image<'gfx>, Box<dyn Error + Send + Sync>>
 {
 }
 
@@ -159,16 +597,52 @@ impl<T> ImageLoader for T where
 pub enum UiMsg {
     /// Load an image with the given ID by running the given function.
     LoadImage(usize, Box<dyn ImageLoader>),
+    /// Load an image with the given ID into the shared image atlas.
+    LoadAtlasImage {
+        id: usize,
+        pixels: Vec<u32>,
+        width: u16,
+        height: u16,
+    },
     /// Unload the image with the given ID.
     UnloadImage(usize),
-    /// Switch to a new screen.
+    /// Switch to a new screen, discarding any screens kept by PushScreen.
     SetScreen(Box<dyn Screen>),
+    /// Switch to a new screen, keeping the current one to return to later.
+    PushScreen(Box<dyn Screen>),
+    /// Return to the screen that was current before the last PushScreen.
+    /// Screens pushed with PushTaggedScreen are skipped over.
+    PopScreen,
+    /// Like PushScreen, but the screen is only closed by CloseScreen with the
+    /// same ID. This lets a thread other than the logic thread show a screen
+    /// without the two closing each other's screens.
+    PushTaggedScreen(ScreenId, Box<dyn Screen>),
+    /// Close the screen pushed with PushTaggedScreen with the given ID, even
+    /// if other screens have been pushed on top of it.
+    CloseScreen(ScreenId),
+    /// Show a loading bar at the top of the screen. 0.0 means the progress is
+    /// unknown, and 1.0 or above hides the bar.
+    Progress(f32),
+    /// Send to the given Sender when the user asks to write a toot from any screen.
+    SetQuickToot(Sender<()>),
+    /// Change the colors that screens are drawn with.
+    SetTheme(Theme),
+    /// Change the scale of normal text. Only text wrapped after this is sent
+    /// is affected, apart from text drawn with Ui::draw_text.
+    SetFontScale(f32),
+    /// Show the number of new notifications in the corner of the screen, or
+    /// hide it if zero. The count is the total since the app started, as
+    /// there is nowhere to read notifications and mark them seen.
+    SetNotificationBadge(usize),
+    /// Briefly show a message at the bottom of the screen, over whichever
+    /// screen is current.
+    Toast(String),
     /// Stop processing messages for this frame, in order to show the current screen.
     Flush,
     /// Open the keyboard and wait for a response.
     Keyboard {
         hint: &'static str,
-        restrict: bool,
+        kind: KeyboardKind,
         blank_allowed: bool,
         tx: std::sync::mpsc::Sender<Result<String, KeyboardError>>,
     },
@@ -179,24 +653,65 @@ pub enum UiMsg {
         scale: f32,
         tx: std::sync::mpsc::Sender<TextLines>,
     },
+    /// Wrap lines of styled text.
+    WordWrapHtml {
+        spans: Vec<HtmlSpan>,
+        width: f32,
+        scale: f32,
+        tx: std::sync::mpsc::Sender<TextLines>,
+    },
+    /// Wrap several texts, each with its own width and scale, and send back
+    /// the results in the same order.
+    WordWrapBatch {
+        items: Vec<(String, f32, f32)>,
+        tx: std::sync::mpsc::Sender<Vec<TextLines>>,
+    },
+    /// Like WordWrapBatch, but for styled text.
+    WordWrapHtmlBatch {
+        items: Vec<(Vec<HtmlSpan>, f32, f32)>,
+        tx: std::sync::mpsc::Sender<Vec<TextLines>>,
+    },
     /// Quit the application.
     Quit,
 }
 
+/// The layout of the software keyboard.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardKind {
+    /// The full keyboard, allowing multiple lines.
+    Normal,
+    /// A single line of letters, numbers and symbols.
+    Qwerty,
+    /// Numbers only.
+    Numpad,
+}
+
+/// Identifies a screen pushed with UiMsg::PushTaggedScreen.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ScreenId(u64);
+
+impl ScreenId {
+    /// Make an ID that no other screen has.
+    pub fn unique() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
 pub type UiMsgSender = std::sync::mpsc::Sender<UiMsg>;
 pub type UiMsgReceiver = std::sync::mpsc::Receiver<UiMsg>;
 
 pub fn get_input(
     sender: &UiMsgSender,
     hint: &'static str,
-    restrict: bool,
+    kind: KeyboardKind,
     blank_allowed: bool,
 ) -> Result<String, KeyboardError> {
     let (tx, rx) = std::sync::mpsc::channel();
     sender
         .send(UiMsg::Keyboard {
             hint,
-            restrict,
+            kind,
             blank_allowed,
             tx,
         })
@@ -204,67 +719,309 @@ pub fn get_input(
     rx.recv().unwrap()
 }
 
+/// Ask for a number, such as a code, on the number pad.
+pub fn get_number_input(sender: &UiMsgSender, hint: &'static str) -> Result<String, KeyboardError> {
+    get_input(sender, hint, KeyboardKind::Numpad, false)
+}
+
+pub fn word_wrap(sender: &UiMsgSender, text: String, width: f32, scale: f32) -> TextLines {
+    let (tx, rx) = std::sync::mpsc::channel();
+    sender
+        .send(UiMsg::WordWrap {
+            text,
+            width,
+            scale,
+            tx,
+        })
+        .unwrap();
+    rx.recv().unwrap()
+}
+
+pub fn word_wrap_html(
+    sender: &UiMsgSender,
+    spans: Vec<HtmlSpan>,
+    width: f32,
+    scale: f32,
+) -> TextLines {
+    let (tx, rx) = std::sync::mpsc::channel();
+    sender
+        .send(UiMsg::WordWrapHtml {
+            spans,
+            width,
+            scale,
+            tx,
+        })
+        .unwrap();
+    rx.recv().unwrap()
+}
+
+/// Wrap several texts, given as (text, width, scale), while only waiting on
+/// the UI thread once.
+pub fn word_wrap_batch(sender: &UiMsgSender, items: Vec<(String, f32, f32)>) -> Vec<TextLines> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    sender.send(UiMsg::WordWrapBatch { items, tx }).unwrap();
+    rx.recv().unwrap()
+}
+
+/// Like word_wrap_batch, but for styled text.
+pub fn word_wrap_html_batch(
+    sender: &UiMsgSender,
+    items: Vec<(Vec<HtmlSpan>, f32, f32)>,
+) -> Vec<TextLines> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    sender.send(UiMsg::WordWrapHtmlBatch { items, tx }).unwrap();
+    rx.recv().unwrap()
+}
+
+/// The image IDs handed out by a LogicImgPool.
+#[derive(Default)]
+struct UsedIds {
+    ids: BitSet,
+    /// The serial number of the image using each ID. IDs are reused, but
+    /// serial numbers are not, so a WeakImg can tell if its image is gone.
+    serials: HashMap<usize, u64>,
+    next_serial: u64,
+}
+
+impl UsedIds {
+    /// True if the given ID still belongs to the image with the given serial.
+    fn is_current(&self, id: usize, serial: u64) -> bool {
+        self.serials.get(&id) == Some(&serial)
+    }
+}
+
 /// Allocates images on the logic thread.
+///
+/// The pool never touches GPU memory itself. It only hands out IDs and sends
+/// messages to the UI thread, which owns the images, so it is safe to use
+/// from any thread.
 #[derive(Clone)]
 pub struct LogicImgPool {
     sender: UiMsgSender,
-    used_ids: Arc<Mutex<BitSet>>,
+    used_ids: Arc<Mutex<UsedIds>>,
 }
 
 impl LogicImgPool {
     pub fn new(sender: UiMsgSender) -> Self {
         Self {
             sender,
-            used_ids: Arc::new(Mutex::new(BitSet::new())),
+            used_ids: Arc::new(Mutex::new(UsedIds::default())),
         }
     }
 
-    pub fn alloc_box(&self, f: Box<dyn ImageLoader>) -> OpaqueImg {
+    /// The number of images handed out that haven't been dropped yet.
+    pub fn live_image_count(&self) -> usize {
+        self.used_ids.lock().unwrap().ids.len()
+    }
+
+    fn alloc_msg<F>(&self, width: u16, height: u16, f: F) -> OpaqueImg
+    where
+        F: FnOnce(usize) -> UiMsg,
+    {
         let mut used_ids = self.used_ids.lock().unwrap();
         let mut id = 0;
         for i in 0.. {
-            if !used_ids.contains(i) {
-                used_ids.insert(i);
+            if !used_ids.ids.contains(i) {
+                used_ids.ids.insert(i);
                 id = i;
                 break;
             }
         }
-        self.sender.send(UiMsg::LoadImage(id, f)).unwrap();
+        let serial = used_ids.next_serial;
+        used_ids.next_serial += 1;
+        used_ids.serials.insert(id, serial);
+        // the lock is still held, so this load is queued after the unload of
+        // any image that previously had this ID
+        self.sender.send(f(id)).unwrap();
         OpaqueImg {
             id,
+            serial,
+            width,
+            height,
             pool: self.clone(),
         }
     }
 
-    pub fn alloc<F>(&self, f: F) -> OpaqueImg
+    /// Allocate an image of the given size, built by the given function.
+    pub fn alloc_box(&self, width: u16, height: u16, f: Box<dyn ImageLoader>) -> OpaqueImg {
+        self.alloc_msg(width, height, |id| UiMsg::LoadImage(id, f))
+    }
+
+    pub fn alloc<F>(&self, width: u16, height: u16, f: F) -> OpaqueImg
     where
         F: ImageLoader + 'static,
     {
-        self.alloc_box(Box::new(f))
+        self.alloc_box(width, height, Box::new(f))
+    }
+
+    /// Allocate an RGBA8 image in the shared image atlas.
+    pub fn alloc_atlas(&self, pixels: Vec<u32>, width: u16, height: u16) -> OpaqueImg {
+        self.alloc_msg(width, height, |id| UiMsg::LoadAtlasImage {
+            id,
+            pixels,
+            width,
+            height,
+        })
     }
 
     fn dealloc(&self, id: usize) {
-        self.used_ids.lock().unwrap().remove(id);
+        let mut used_ids = self.used_ids.lock().unwrap();
+        // the unload must be queued before the ID can be reused, or the UI
+        // thread could unload the image that was loaded in its place
         // ignore send errors here, it means that the ui deallocated before us
         _ = self.sender.send(UiMsg::UnloadImage(id));
+        used_ids.ids.remove(id);
+        used_ids.serials.remove(&id);
     }
 }
 
+// images are allocated and freed from the logic thread, and kept by screens
+// that are sent to the UI thread. The pool is also shared between the
+// threads that load images.
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    const fn assert_sync<T: Sync>() {}
+    assert_send::<LogicImgPool>();
+    assert_sync::<LogicImgPool>();
+    assert_send::<OpaqueImg>();
+    assert_send::<WeakImg>();
+};
+
 /// Image object that can be shared between threads.
+///
+/// This is only an ID of an image owned by the UI thread, so it can be sent
+/// anywhere. Dropping it asks the UI thread to free the image.
 pub struct OpaqueImg {
     id: usize,
+    /// Tells this image apart from others that have used the same ID.
+    serial: u64,
+    width: u16,
+    height: u16,
     pool: LogicImgPool,
 }
 
+impl OpaqueImg {
+    /// The scale factors needed to stretch this image to the given size.
+    fn scale_to(&self, w: f32, h: f32) -> (f32, f32) {
+        (w / f32::from(self.width), h / f32::from(self.height))
+    }
+
+    /// Make a reference to this image that doesn't keep it loaded.
+    pub fn downgrade(&self) -> WeakImg {
+        WeakImg {
+            id: self.id,
+            serial: self.serial,
+            width: self.width,
+            height: self.height,
+            used_ids: Arc::downgrade(&self.pool.used_ids),
+        }
+    }
+}
+
+/// A reference to an image that may have been freed.
+///
+/// Unlike OpaqueImg, this doesn't keep the image loaded, so it can be used to
+/// show an image only if something else is already keeping it around.
+#[derive(Clone)]
+pub struct WeakImg {
+    id: usize,
+    serial: u64,
+    width: u16,
+    height: u16,
+    used_ids: Weak<Mutex<UsedIds>>,
+}
+
+impl WeakImg {
+    /// The scale factors needed to stretch this image to the given size.
+    fn scale_to(&self, w: f32, h: f32) -> (f32, f32) {
+        (w / f32::from(self.width), h / f32::from(self.height))
+    }
+
+    /// True if the image has not been freed yet.
+    pub fn is_alive(&self) -> bool {
+        match self.used_ids.upgrade() {
+            Some(used_ids) => used_ids.lock().unwrap().is_current(self.id, self.serial),
+            None => false,
+        }
+    }
+}
+
 impl Drop for OpaqueImg {
     fn drop(&mut self) {
         self.pool.dealloc(self.id);
     }
 }
 
+/// The buttons and touch screen as read at the start of a frame. Menus
+/// should act on pressed buttons, so that holding a button doesn't repeat
+/// the action every frame, and only things like scrolling on held ones.
+pub struct HidEvents {
+    /// Buttons pressed since the last frame.
+    pub pressed: KeyPad,
+    /// Buttons that are down, including ones that were just pressed.
+    pub held: KeyPad,
+    /// Buttons let go of since the last frame.
+    pub released: KeyPad,
+    /// Where the touch screen is touched, if KEY_TOUCH is held.
+    pub touch: (u16, u16),
+    pub circle_pad: (i16, i16),
+    /// Where the touch screen was touched last frame, if it was.
+    pub prev_touch: Option<(u16, u16)>,
+    /// Where the current touch started, or the one that just ended.
+    pub touch_start: Option<(u16, u16)>,
+}
+
+impl HidEvents {
+    fn read(hid: &Hid, prev_touch: Option<(u16, u16)>, touch_start: Option<(u16, u16)>) -> Self {
+        let touch = hid.touch_position();
+        let curr_touch = is_touching(hid).then_some(touch);
+        let touch_start = if touch_just_started(prev_touch, curr_touch) {
+            curr_touch
+        } else {
+            touch_start
+        };
+        Self {
+            pressed: hid.keys_down(),
+            held: hid.keys_held(),
+            released: hid.keys_up(),
+            touch,
+            circle_pad: hid.circlepad_position(),
+            prev_touch,
+            touch_start,
+        }
+    }
+
+    /// Where the touch screen is touched, if it is.
+    pub fn current_touch(&self) -> Option<(u16, u16)> {
+        self.held.contains(KeyPad::KEY_TOUCH).then_some(self.touch)
+    }
+
+    /// Where the user tapped, if they just lifted their finger without
+    /// dragging it. This is where the touch ended, as it is no longer read.
+    pub fn tap(&self) -> Option<(u16, u16)> {
+        if !touch_just_ended(self.prev_touch, self.current_touch()) {
+            return None;
+        }
+        let end = self.prev_touch?;
+        let (dx, dy) = touch_delta(self.touch_start?, end);
+        (dx.abs() <= TAP_DISTANCE && dy.abs() <= TAP_DISTANCE).then_some(end)
+    }
+}
+
 pub trait Screen: Send + Sync {
-    fn update(&mut self, hid: &Hid) {
-        _ = hid;
+    fn update(&mut self, input: &HidEvents) {
+        _ = input;
+    }
+
+    /// The name shown at the top of the bottom screen while this screen is
+    /// current.
+    fn title(&self) -> &str {
+        ""
+    }
+
+    /// Called when this screen becomes current again after a PopScreen.
+    fn on_resume(&mut self, tx: &UiMsgSender) {
+        _ = tx;
     }
 
     fn draw<'gfx: 'screen, 'screen>(
@@ -280,10 +1037,58 @@ pub struct EmptyScreen;
 impl Screen for EmptyScreen {
     fn draw<'gfx: 'screen, 'screen>(
         &self,
-        _ui: &Ui<'gfx, 'screen>,
+        ui: &Ui<'gfx, 'screen>,
         target: &RenderTarget<'gfx, 'screen>,
-        _ctx: &Scene2d,
+        ctx: &Scene2d,
     ) {
-        target.clear(color32(0, 0, 0, 255));
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use super::LogicImgPool;
+
+    /// Images allocated and freed from several threads at once must never
+    /// share an ID while they are both alive.
+    #[test]
+    fn ids_not_reused_while_allocated() {
+        // keep the receiver, so that the pool's messages can be sent
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let pool = LogicImgPool::new(tx);
+        let live = Arc::new(Mutex::new(HashSet::new()));
+        let threads = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                let live = live.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        let images = (0..4)
+                            .map(|_| pool.alloc_atlas(vec![0; 64], 8, 8))
+                            .collect::<Vec<_>>();
+                        for image in &images {
+                            assert!(live.lock().unwrap().insert(image.id), "ID reused");
+                        }
+                        // forget the IDs before freeing them, as they can be
+                        // handed out again as soon as they are freed
+                        for image in images {
+                            live.lock().unwrap().remove(&image.id);
+                            drop(image);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(pool.live_image_count(), 0);
     }
 }
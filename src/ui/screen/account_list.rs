@@ -0,0 +1,126 @@
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex,
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::{
+    types::Account,
+    ui::{
+        citro2d::{RenderTarget, Scene2d},
+        scroll::ScrollState,
+        text::TextLines,
+        word_wrap, HidEvents, Screen, Ui, UiMsgSender, WeakImg, WebImageCache,
+    },
+};
+
+/// Requests sent from the account list to the logic thread.
+pub enum AccountListEvent {
+    /// The user wants to leave the screen.
+    Back,
+}
+
+/// The width and height that avatars are shown at.
+const AVATAR_SIZE: f32 = 32.0;
+
+struct AccountEntry {
+    label: TextLines,
+    /// The avatar, if it was already loaded for another screen. Avatars
+    /// aren't downloaded just for the list, as it may be long.
+    avatar: Option<WeakImg>,
+}
+
+impl AccountEntry {
+    fn height(&self) -> f32 {
+        self.label.height().max(AVATAR_SIZE)
+    }
+}
+
+/// Shows a list of accounts, such as those that favourited a status.
+pub struct AccountListScreen {
+    title: String,
+    accounts: Vec<AccountEntry>,
+    scroll: ScrollState,
+    hint: TextLines,
+    events: Mutex<Sender<AccountListEvent>>,
+}
+
+impl AccountListScreen {
+    pub fn new(
+        tx: &UiMsgSender,
+        cache: &WebImageCache,
+        title: String,
+        accounts: Vec<Account>,
+    ) -> (Self, Receiver<AccountListEvent>) {
+        let accounts = accounts
+            .into_iter()
+            .map(|account| {
+                let label = format!("{}\n@{}\n", account.display_name, account.acct);
+                AccountEntry {
+                    label: word_wrap(tx, label, 320.0, 0.5),
+                    avatar: cache.peek(&account.avatar_static),
+                }
+            })
+            .collect();
+        let hint = word_wrap(tx, "B: back".into(), 360.0, 0.5);
+        let (events, rx) = std::sync::mpsc::channel();
+        (
+            Self {
+                title,
+                accounts,
+                scroll: ScrollState::default(),
+                hint,
+                events: Mutex::new(events),
+            },
+            rx,
+        )
+    }
+
+    fn send(&self, event: AccountListEvent) {
+        // ignore send errors, the logic thread may have already stopped
+        _ = self.events.lock().unwrap().send(event);
+    }
+}
+
+impl Screen for AccountListScreen {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let mut scroll = 20.0 - self.scroll.offset();
+
+        for account in &self.accounts {
+            if let Some(avatar) = &account.avatar {
+                let (scale_x, scale_y) = avatar.scale_to(AVATAR_SIZE, AVATAR_SIZE);
+                ui.draw_weak_img(avatar, ctx, 20.0, scroll, scale_x, scale_y);
+            }
+            let x = 28.0 + AVATAR_SIZE;
+            ui.draw_lines(ctx, x, scroll, colors.foreground, &account.label);
+            scroll += account.height();
+        }
+
+        // keep the controls visible over the list
+        ctx.rect_solid(0.0, 208.0, 400.0, 32.0, colors.background_bottom);
+        ui.draw_lines(ctx, 20.0, 216.0, colors.foreground, &self.hint);
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        if input.pressed.contains(KeyPad::KEY_B) {
+            self.send(AccountListEvent::Back);
+        }
+
+        self.scroll.update_dpad(input);
+        self.scroll.update_analog(input);
+        self.scroll.update_touch(input);
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex,
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::ui::{
+    citro2d::{RenderTarget, Scene2d},
+    text::TextLines,
+    word_wrap, HidEvents, Screen, Ui, UiMsgSender,
+};
+
+/// Requests sent from the action menu to the logic thread.
+pub enum ActionMenuEvent {
+    /// The user chose the action at the given index.
+    Choose(usize),
+    /// The user wants to leave without doing anything.
+    Back,
+}
+
+/// Lists what can be done to a status, and lets the user pick one.
+pub struct ActionMenuScreen {
+    actions: Vec<TextLines>,
+    selected: usize,
+    hint: TextLines,
+    /// Dropped after sending, so we only send once.
+    events: Mutex<Option<Sender<ActionMenuEvent>>>,
+}
+
+impl ActionMenuScreen {
+    pub fn new(tx: UiMsgSender, names: Vec<String>) -> (Self, Receiver<ActionMenuEvent>) {
+        let actions = names
+            .into_iter()
+            .map(|name| word_wrap(&tx, name, 352.0, 0.5))
+            .collect();
+        let hint = word_wrap(&tx, "A: choose, B: back".into(), 360.0, 0.5);
+        let (events, rx) = std::sync::mpsc::channel();
+        (
+            Self {
+                actions,
+                selected: 0,
+                hint,
+                events: Mutex::new(Some(events)),
+            },
+            rx,
+        )
+    }
+
+    fn send(&self, event: ActionMenuEvent) {
+        if let Some(events) = self.events.lock().unwrap().take() {
+            // ignore send errors, the logic thread may have already stopped
+            _ = events.send(event);
+        }
+    }
+}
+
+impl Screen for ActionMenuScreen {
+    fn title(&self) -> &str {
+        "Actions"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let mut y = 20.0;
+        for (i, action) in self.actions.iter().enumerate() {
+            if i == self.selected {
+                ctx.rect_solid(16.0, y, 368.0, action.height(), colors.selection);
+            }
+            ui.draw_lines(ctx, 24.0, y, colors.foreground, action);
+            y += action.height();
+        }
+
+        ui.draw_lines(
+            ctx,
+            20.0,
+            220.0 - self.hint.height(),
+            colors.muted,
+            &self.hint,
+        );
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            self.send(ActionMenuEvent::Back);
+        } else if buttons.contains(KeyPad::KEY_DUP) {
+            self.selected = self.selected.saturating_sub(1);
+        } else if buttons.contains(KeyPad::KEY_DDOWN) {
+            if self.selected + 1 < self.actions.len() {
+                self.selected += 1;
+            }
+        } else if buttons.contains(KeyPad::KEY_A) && !self.actions.is_empty() {
+            self.send(ActionMenuEvent::Choose(self.selected));
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex,
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::ui::{
+    citro2d::{RenderTarget, Scene2d},
+    text::TextLines,
+    word_wrap, HidEvents, Screen, Ui, UiMsgSender,
+};
+
+/// Requests sent from the composer to the logic thread.
+pub enum ComposerEvent {
+    /// The user wants to leave without posting.
+    Back,
+    /// The user wants to change the text of the toot.
+    Edit,
+    /// The user wants to post the toot.
+    Post {
+        status: String,
+        reply_to: Option<String>,
+    },
+}
+
+/// Sent from the logic thread to the composer when the text changes.
+pub struct ComposerUpdate {
+    status: String,
+    lines: TextLines,
+}
+
+impl ComposerUpdate {
+    pub fn new(tx: &UiMsgSender, status: String) -> Self {
+        let lines = word_wrap(tx, status.clone(), 352.0, 0.5);
+        Self { status, lines }
+    }
+}
+
+/// Lets the user write a toot.
+pub struct ComposerScreen {
+    /// The ID of the status being replied to, if any.
+    reply_to: Option<String>,
+    title: TextLines,
+    status: ComposerUpdate,
+    hint: TextLines,
+    events: Mutex<Sender<ComposerEvent>>,
+    updates: Mutex<Receiver<ComposerUpdate>>,
+}
+
+impl ComposerScreen {
+    pub fn new(
+        tx: UiMsgSender,
+        reply_to: Option<String>,
+    ) -> (Self, Receiver<ComposerEvent>, Sender<ComposerUpdate>) {
+        let title = match reply_to {
+            Some(_) => "Reply",
+            None => "New toot",
+        };
+        let title = word_wrap(&tx, title.into(), 360.0, 0.5);
+        let status = ComposerUpdate::new(&tx, String::new());
+        let hint = word_wrap(&tx, "A: edit, START: post, B: cancel".into(), 360.0, 0.5);
+        let (events, events_rx) = std::sync::mpsc::channel();
+        let (updates_tx, updates) = std::sync::mpsc::channel();
+        (
+            Self {
+                reply_to,
+                title,
+                status,
+                hint,
+                events: Mutex::new(events),
+                updates: Mutex::new(updates),
+            },
+            events_rx,
+            updates_tx,
+        )
+    }
+
+    fn send(&self, event: ComposerEvent) {
+        // ignore send errors, the logic thread may have already stopped
+        _ = self.events.lock().unwrap().send(event);
+    }
+}
+
+impl Screen for ComposerScreen {
+    fn title(&self) -> &str {
+        "New post"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        ui.draw_lines(ctx, 20.0, 20.0, colors.foreground, &self.title);
+        let y = 28.0 + self.title.height();
+        let height = self.status.lines.height().max(16.0);
+        ui.draw_panel(ctx, 16.0, y - 4.0, 368.0, height + 8.0);
+        ui.draw_lines(ctx, 24.0, y, colors.foreground, &self.status.lines);
+
+        ui.draw_lines(
+            ctx,
+            20.0,
+            220.0 - self.hint.height(),
+            colors.muted,
+            &self.hint,
+        );
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        // apply any changes made with the keyboard
+        if let Some(update) = self.updates.lock().unwrap().try_iter().last() {
+            self.status = update;
+        }
+
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            self.send(ComposerEvent::Back);
+        } else if buttons.contains(KeyPad::KEY_START) {
+            // don't post an empty toot
+            if !self.status.status.trim().is_empty() {
+                self.send(ComposerEvent::Post {
+                    status: self.status.status.clone(),
+                    reply_to: self.reply_to.clone(),
+                });
+            }
+        } else if buttons.contains(KeyPad::KEY_A) {
+            self.send(ComposerEvent::Edit);
+        }
+    }
+}
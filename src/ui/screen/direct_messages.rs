@@ -0,0 +1,266 @@
+use std::{
+    error::Error,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Mutex,
+    },
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::{
+    html::{self, HtmlSpan},
+    net::Client,
+    types::{Status, Visibility},
+    ui::{
+        citro2d::{RenderTarget, Scene2d},
+        text::TextLines,
+        word_wrap, word_wrap_html, HidEvents, Screen, Ui, UiMsgSender,
+    },
+};
+
+/// Requests sent from the direct message screens to the logic thread.
+pub enum DirectMessageEvent {
+    /// The user wants to leave the screen.
+    Back,
+    /// The user wants to see the thread containing the given status.
+    OpenThread(String),
+    /// The user wants to reply to the given status.
+    Reply { id: String, acct: String },
+}
+
+struct DirectMessage {
+    id: String,
+    acct: String,
+    content: TextLines,
+    /// The app the message was posted with, if shown.
+    application: Option<TextLines>,
+}
+
+impl DirectMessage {
+    fn height(&self) -> f32 {
+        let application = match &self.application {
+            Some(application) => application.height(),
+            None => 0.0,
+        };
+        self.content.height() + application
+    }
+}
+
+/// A list of direct messages, one of which is selected.
+struct DirectMessageList {
+    messages: Vec<DirectMessage>,
+    selected: usize,
+    scroll: f32,
+    events: Mutex<Sender<DirectMessageEvent>>,
+}
+
+impl DirectMessageList {
+    /// If show_application is true, the selected message is labelled with the
+    /// app it was posted with.
+    fn new(
+        statuses: Vec<Status>,
+        selected: usize,
+        show_application: bool,
+        tx: &UiMsgSender,
+    ) -> Result<(Self, Receiver<DirectMessageEvent>), Box<dyn Error + Send + Sync>> {
+        let messages = statuses
+            .into_iter()
+            .enumerate()
+            .map(
+                |(i, status)| -> Result<DirectMessage, Box<dyn Error + Send + Sync>> {
+                    // show everyone involved, since there may be more than one recipient
+                    let recipients = status
+                        .mentions
+                        .iter()
+                        .map(|mention| format!("@{}", mention.acct))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let mut spans = vec![HtmlSpan::plain(format!(
+                        "from {} to {}\n",
+                        status.account.display_name, recipients
+                    ))];
+                    spans.extend(html::parse(&status.content)?);
+                    spans.push(HtmlSpan::plain("\n"));
+                    let content = word_wrap_html(tx, spans, 360.0, 0.5);
+                    let application = status
+                        .application
+                        .filter(|_| show_application && i == selected)
+                        .map(|application| {
+                            let label = match application.website {
+                                Some(website) => {
+                                    format!("via {} ({})\n", application.name, website)
+                                }
+                                None => format!("via {}\n", application.name),
+                            };
+                            word_wrap(tx, label, 360.0, 0.4)
+                        });
+                    Ok(DirectMessage {
+                        id: status.id,
+                        acct: status.account.acct,
+                        content,
+                        application,
+                    })
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        let (events, rx) = std::sync::mpsc::channel();
+        let mut result = Self {
+            messages,
+            selected,
+            scroll: 0.0,
+            events: Mutex::new(events),
+        };
+        result.scroll_to_selected();
+        Ok((result, rx))
+    }
+
+    fn send(&self, event: DirectMessageEvent) {
+        // ignore send errors, the logic thread may have already stopped
+        _ = self.events.lock().unwrap().send(event);
+    }
+
+    fn scroll_to_selected(&mut self) {
+        let top = self.messages[..self.selected]
+            .iter()
+            .map(|message| message.height())
+            .sum::<f32>();
+        let bottom = match self.messages.get(self.selected) {
+            Some(message) => top + message.height(),
+            None => top,
+        };
+        if top < self.scroll {
+            self.scroll = top;
+        } else if bottom > self.scroll + 200.0 {
+            self.scroll = bottom - 200.0;
+        }
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let mut scroll = 20.0 - self.scroll;
+
+        for (i, message) in self.messages.iter().enumerate() {
+            if i == self.selected {
+                ctx.rect_solid(16.0, scroll, 368.0, message.height(), colors.selection);
+            }
+            ui.draw_lines(ctx, 20.0, scroll, colors.foreground, &message.content);
+            if let Some(application) = &message.application {
+                let y = scroll + message.content.height();
+                ui.draw_lines(ctx, 20.0, y, colors.muted, application);
+            }
+            scroll += message.height();
+        }
+    }
+
+    fn update(&mut self, input: &HidEvents, can_open_thread: bool) {
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            self.send(DirectMessageEvent::Back);
+        } else if buttons.contains(KeyPad::KEY_DUP) {
+            self.selected = self.selected.saturating_sub(1);
+            self.scroll_to_selected();
+        } else if buttons.contains(KeyPad::KEY_DDOWN) {
+            if self.selected + 1 < self.messages.len() {
+                self.selected += 1;
+                self.scroll_to_selected();
+            }
+        } else if let Some(message) = self.messages.get(self.selected) {
+            if buttons.contains(KeyPad::KEY_A) && can_open_thread {
+                self.send(DirectMessageEvent::OpenThread(message.id.clone()));
+            } else if buttons.contains(KeyPad::KEY_X) {
+                self.send(DirectMessageEvent::Reply {
+                    id: message.id.clone(),
+                    acct: message.acct.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Shows the direct messages the user has sent and received.
+pub struct DirectMessagesScreen(DirectMessageList);
+
+impl DirectMessagesScreen {
+    pub fn new(
+        client: &Client,
+        tx: UiMsgSender,
+    ) -> Result<(Self, Receiver<DirectMessageEvent>), Box<dyn Error + Send + Sync>> {
+        let statuses = client
+            .get_direct_timeline(None, None)?
+            .into_iter()
+            .filter(|status| matches!(status.visibility, Visibility::Direct))
+            .collect();
+        let (list, rx) = DirectMessageList::new(statuses, 0, false, &tx)?;
+        Ok((Self(list), rx))
+    }
+}
+
+impl Screen for DirectMessagesScreen {
+    fn title(&self) -> &str {
+        "Direct messages"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        self.0.draw(ui, target, ctx);
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        self.0.update(input, true);
+    }
+}
+
+/// Shows the conversation that a direct message is part of.
+pub struct DirectMessageScreen(DirectMessageList);
+
+impl DirectMessageScreen {
+    pub fn new(
+        client: &Client,
+        tx: UiMsgSender,
+        id: &str,
+    ) -> Result<(Self, Receiver<DirectMessageEvent>), Box<dyn Error + Send + Sync>> {
+        let status = client.get_status(id)?;
+        let context = client.get_status_context(id)?;
+        // select the status that was opened
+        let selected = context.ancestors.len();
+        let statuses = context
+            .ancestors
+            .into_iter()
+            .chain([status].into_iter())
+            .chain(context.descendants.into_iter())
+            .collect();
+        let (list, rx) = DirectMessageList::new(statuses, selected, true, &tx)?;
+        Ok((Self(list), rx))
+    }
+}
+
+impl Screen for DirectMessageScreen {
+    fn title(&self) -> &str {
+        "Conversation"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        self.0.draw(ui, target, ctx);
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        self.0.update(input, false);
+    }
+}
@@ -6,32 +6,50 @@ use std::sync::{
 use ctru::prelude::KeyPad;
 
 use crate::ui::{
-    citro2d::{color32, RenderTarget, Scene2d},
+    citro2d::{RenderTarget, Scene2d},
     text::TextLines,
-    Screen, Ui, UiMsg, UiMsgSender,
+    word_wrap, HidEvents, Screen, Ui, UiMsgSender,
 };
 
+/// An action to run on the logic thread before retrying.
+pub type RetryAction = Box<dyn FnOnce() + Send>;
+
+/// Requests sent from the error screen to the logic thread.
+pub enum ErrorEvent {
+    /// The user wants to quit.
+    Quit,
+    /// The user wants to try again.
+    Retry(RetryAction),
+}
+
 pub struct ErrorScreen {
     message: TextLines,
-    on_close: Mutex<Sender<()>>,
+    /// Labels for the available actions.
+    buttons: Vec<TextLines>,
+    retry: Mutex<Option<RetryAction>>,
+    events: Mutex<Sender<ErrorEvent>>,
 }
 
 impl ErrorScreen {
-    pub fn new(message: String, tx: UiMsgSender) -> (Self, Receiver<()>) {
-        let (lines_tx, lines_rx) = std::sync::mpsc::channel();
-        tx.send(UiMsg::WordWrap {
-            text: message,
-            width: 360.0,
-            scale: 0.5,
-            tx: lines_tx,
-        })
-        .unwrap();
-        let message = lines_rx.recv().unwrap();
-        let (on_close, rx) = std::sync::mpsc::channel();
+    pub fn new(
+        message: String,
+        tx: UiMsgSender,
+        retry: Option<RetryAction>,
+    ) -> (Self, Receiver<ErrorEvent>) {
+        let message = word_wrap(&tx, message, 360.0, 0.5);
+        let mut buttons = vec![];
+        // only offer to retry if there's a way to do so
+        if retry.is_some() {
+            buttons.push(word_wrap(&tx, "RETRY (A)".into(), 360.0, 0.5));
+        }
+        buttons.push(word_wrap(&tx, "QUIT (START)".into(), 360.0, 0.5));
+        let (events, rx) = std::sync::mpsc::channel();
         (
             Self {
                 message,
-                on_close: Mutex::new(on_close),
+                buttons,
+                retry: Mutex::new(retry),
+                events: Mutex::new(events),
             },
             rx,
         )
@@ -39,11 +57,25 @@ impl ErrorScreen {
 }
 
 impl Screen for ErrorScreen {
-    fn update(&mut self, hid: &ctru::services::Hid) {
-        // tell logic thread to close the screen when start is pressed
-        if hid.keys_down().contains(KeyPad::KEY_START) {
-            self.on_close.lock().unwrap().send(()).unwrap();
-        }
+    fn title(&self) -> &str {
+        "Error"
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        let buttons = input.pressed;
+        // tell logic thread what to do when a button is pressed
+        let event = if buttons.contains(KeyPad::KEY_START) {
+            ErrorEvent::Quit
+        } else if buttons.contains(KeyPad::KEY_A) {
+            match self.retry.lock().unwrap().take() {
+                Some(action) => ErrorEvent::Retry(action),
+                None => return,
+            }
+        } else {
+            return;
+        };
+        // ignore send errors, the logic thread may have already stopped
+        _ = self.events.lock().unwrap().send(event);
     }
 
     fn draw<'gfx: 'screen, 'screen>(
@@ -52,7 +84,17 @@ impl Screen for ErrorScreen {
         target: &RenderTarget<'gfx, 'screen>,
         ctx: &Scene2d,
     ) {
-        target.clear(color32(0, 0, 0, 255));
-        ui.draw_lines(ctx, 20.0, 20.0, color32(255, 85, 85, 255), &self.message);
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+        ui.draw_lines(ctx, 20.0, 20.0, colors.error, &self.message);
+
+        // lay out the buttons along the bottom of the screen
+        let mut x = 20.0;
+        for button in &self.buttons {
+            let height = button.height();
+            ctx.rect_solid(x, 212.0 - height, 100.0, height + 8.0, colors.selection);
+            ui.draw_lines(ctx, x + 4.0, 216.0 - height, colors.foreground, button);
+            x += 108.0;
+        }
     }
 }
@@ -0,0 +1,178 @@
+use std::{
+    error::Error,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Mutex,
+    },
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::{
+    html::{self, HtmlSpan},
+    types::Status,
+    ui::{
+        citro2d::{RenderTarget, Scene2d},
+        scroll::ScrollState,
+        text::TextLines,
+        word_wrap, word_wrap_html, HidEvents, Screen, Ui, UiMsgSender,
+    },
+};
+
+/// Requests sent from the hashtag timeline to the logic thread.
+pub enum HashtagTimelineEvent {
+    /// The user wants to leave the screen.
+    Back,
+    /// The user wants more statuses, older than the one with the given ID.
+    LoadMore(String),
+}
+
+/// Wrap a status to be shown in the list, along with who posted it.
+fn wrap_status(
+    tx: &UiMsgSender,
+    status: Status,
+) -> Result<TextLines, Box<dyn Error + Send + Sync>> {
+    let mut spans = vec![HtmlSpan::plain(format!(
+        "from {}\n",
+        status.account.display_name
+    ))];
+    spans.extend(html::parse(&status.content)?);
+    spans.push(HtmlSpan::plain("\n"));
+    Ok(word_wrap_html(tx, spans, 360.0, 0.5))
+}
+
+/// More statuses with the hashtag, sent from the logic thread to be added to
+/// the end.
+pub struct HashtagTimelineUpdate {
+    statuses: Vec<TextLines>,
+    /// The ID of the oldest status, if there were any.
+    last_id: Option<String>,
+}
+
+impl HashtagTimelineUpdate {
+    pub fn new(
+        tx: &UiMsgSender,
+        statuses: Vec<Status>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let last_id = statuses.last().map(|status| status.id.clone());
+        let statuses = statuses
+            .into_iter()
+            .map(|status| wrap_status(tx, status))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { statuses, last_id })
+    }
+
+    /// An update with no statuses, that lets the user ask for the ones older
+    /// than the given ID again, for when loading them failed.
+    pub fn retry(max_id: String) -> Self {
+        Self {
+            statuses: vec![],
+            last_id: Some(max_id),
+        }
+    }
+}
+
+/// Shows the latest statuses with a hashtag.
+pub struct HashtagTimelineScreen {
+    /// The hashtag, with the leading #.
+    title: String,
+    statuses: Vec<TextLines>,
+    /// The ID of the oldest status shown, to load older ones from.
+    last_id: Option<String>,
+    scroll: ScrollState,
+    hint: TextLines,
+    /// True while waiting for more statuses, so only one request is made.
+    loading: bool,
+    events: Mutex<Sender<HashtagTimelineEvent>>,
+    updates: Mutex<Receiver<HashtagTimelineUpdate>>,
+}
+
+impl HashtagTimelineScreen {
+    pub fn new(
+        tx: &UiMsgSender,
+        hashtag: &str,
+        first: HashtagTimelineUpdate,
+    ) -> (
+        Self,
+        Receiver<HashtagTimelineEvent>,
+        Sender<HashtagTimelineUpdate>,
+    ) {
+        let hint = word_wrap(tx, "A: load more, B: back".into(), 360.0, 0.5);
+        let (events, events_rx) = std::sync::mpsc::channel();
+        let (updates_tx, updates) = std::sync::mpsc::channel();
+        (
+            Self {
+                title: format!("#{}", hashtag),
+                statuses: first.statuses,
+                last_id: first.last_id,
+                scroll: ScrollState::default(),
+                hint,
+                loading: false,
+                events: Mutex::new(events),
+                updates: Mutex::new(updates),
+            },
+            events_rx,
+            updates_tx,
+        )
+    }
+
+    fn send(&self, event: HashtagTimelineEvent) {
+        // ignore send errors, the logic thread may have already stopped
+        _ = self.events.lock().unwrap().send(event);
+    }
+}
+
+impl Screen for HashtagTimelineScreen {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let mut scroll = 20.0 - self.scroll.offset();
+
+        for status in &self.statuses {
+            ui.draw_lines(ctx, 20.0, scroll, colors.foreground, status);
+            scroll += status.height();
+        }
+
+        // keep the controls visible over the list
+        ctx.rect_solid(0.0, 208.0, 400.0, 32.0, colors.background_bottom);
+        // there is nothing more to load once the oldest status has been seen
+        let hint_color = if self.loading || self.last_id.is_none() {
+            colors.muted
+        } else {
+            colors.foreground
+        };
+        ui.draw_lines(ctx, 20.0, 216.0, hint_color, &self.hint);
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        for update in self.updates.lock().unwrap().try_iter() {
+            self.statuses.extend(update.statuses);
+            self.last_id = update.last_id;
+            self.loading = false;
+        }
+
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            self.send(HashtagTimelineEvent::Back);
+        } else if buttons.contains(KeyPad::KEY_A) && !self.loading {
+            if let Some(last_id) = &self.last_id {
+                self.loading = true;
+                self.send(HashtagTimelineEvent::LoadMore(last_id.clone()));
+            }
+        }
+
+        self.scroll.update_dpad(input);
+        self.scroll.update_analog(input);
+        self.scroll.update_touch(input);
+    }
+}
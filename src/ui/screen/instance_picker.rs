@@ -0,0 +1,164 @@
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex,
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::{
+    types::InstanceDirectoryEntry,
+    ui::{
+        citro2d::{RenderTarget, Scene2d},
+        text::TextLines,
+        word_wrap, HidEvents, Screen, Ui, UiMsgSender,
+    },
+};
+
+/// Requests sent from the instance picker to the logic thread.
+pub enum InstancePickerEvent {
+    /// The user picked the instance with the given domain.
+    Select(String),
+    /// The user wants to type in an instance that isn't listed.
+    Custom,
+}
+
+struct InstanceEntry {
+    name: String,
+    content: TextLines,
+}
+
+/// Lets the user choose an instance to log in to from a list of popular ones.
+pub struct InstancePickerScreen {
+    instances: Vec<InstanceEntry>,
+    selected: usize,
+    scroll: f32,
+    hint: TextLines,
+    /// Dropped after sending, so we only send once.
+    instance_picker_done: Mutex<Option<Sender<InstancePickerEvent>>>,
+}
+
+impl InstancePickerScreen {
+    pub fn new(
+        instances: Vec<InstanceDirectoryEntry>,
+        tx: UiMsgSender,
+    ) -> (Self, Receiver<InstancePickerEvent>) {
+        let instances = instances
+            .into_iter()
+            .map(|instance| {
+                let users = match instance.active_users {
+                    Some(users) => format!("{} active users", users),
+                    None => "Unknown active users".into(),
+                };
+                let description = instance
+                    .info
+                    .and_then(|info| info.short_description)
+                    .unwrap_or_default();
+                let content = word_wrap(
+                    &tx,
+                    format!("{}\n{}\n{}\n", instance.name, users, description.trim()),
+                    352.0,
+                    0.5,
+                );
+                InstanceEntry {
+                    name: instance.name,
+                    content,
+                }
+            })
+            .collect();
+        let hint = word_wrap(
+            &tx,
+            "A: choose instance, B: type another instance".into(),
+            360.0,
+            0.5,
+        );
+        let (instance_picker_done, rx) = std::sync::mpsc::channel();
+        (
+            Self {
+                instances,
+                selected: 0,
+                scroll: 0.0,
+                hint,
+                instance_picker_done: Mutex::new(Some(instance_picker_done)),
+            },
+            rx,
+        )
+    }
+
+    fn send(&self, event: InstancePickerEvent) {
+        if let Some(instance_picker_done) = self.instance_picker_done.lock().unwrap().take() {
+            // ignore send errors, the logic thread may have already stopped
+            _ = instance_picker_done.send(event);
+        }
+    }
+
+    fn scroll_to_selected(&mut self) {
+        let top = self.instances[..self.selected]
+            .iter()
+            .map(|instance| instance.content.height())
+            .sum::<f32>();
+        let bottom = match self.instances.get(self.selected) {
+            Some(instance) => top + instance.content.height(),
+            None => top,
+        };
+        if top < self.scroll {
+            self.scroll = top;
+        } else if bottom > self.scroll + 180.0 {
+            self.scroll = bottom - 180.0;
+        }
+    }
+}
+
+impl Screen for InstancePickerScreen {
+    fn title(&self) -> &str {
+        "Choose an instance"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let mut scroll = 20.0 - self.scroll;
+
+        for (i, instance) in self.instances.iter().enumerate() {
+            if i == self.selected {
+                ctx.rect_solid(
+                    16.0,
+                    scroll,
+                    368.0,
+                    instance.content.height(),
+                    colors.selection,
+                );
+            }
+            ui.draw_lines(ctx, 24.0, scroll, colors.foreground, &instance.content);
+            scroll += instance.content.height();
+        }
+
+        // keep the controls visible over the list
+        ctx.rect_solid(0.0, 208.0, 400.0, 32.0, colors.background_bottom);
+        ui.draw_lines(ctx, 20.0, 216.0, colors.foreground, &self.hint);
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            self.send(InstancePickerEvent::Custom);
+        } else if buttons.contains(KeyPad::KEY_DUP) {
+            self.selected = self.selected.saturating_sub(1);
+            self.scroll_to_selected();
+        } else if buttons.contains(KeyPad::KEY_DDOWN) {
+            if self.selected + 1 < self.instances.len() {
+                self.selected += 1;
+                self.scroll_to_selected();
+            }
+        } else if buttons.contains(KeyPad::KEY_A) {
+            if let Some(instance) = self.instances.get(self.selected) {
+                self.send(InstancePickerEvent::Select(instance.name.clone()));
+            }
+        }
+    }
+}
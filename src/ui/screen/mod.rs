@@ -1,7 +1,29 @@
+mod account_list;
+mod action_menu;
+mod composer;
+mod direct_messages;
 mod error;
+mod hashtag_timeline;
+mod instance_picker;
+mod profile_edit;
 mod qr;
+mod scheduled;
+mod settings;
 mod timeline;
+mod trending_statuses;
 
-pub use error::ErrorScreen;
+pub use account_list::{AccountListEvent, AccountListScreen};
+pub use action_menu::{ActionMenuEvent, ActionMenuScreen};
+pub use composer::{ComposerEvent, ComposerScreen, ComposerUpdate};
+pub use direct_messages::{DirectMessageEvent, DirectMessageScreen, DirectMessagesScreen};
+pub use error::{ErrorEvent, ErrorScreen};
+pub use hashtag_timeline::{HashtagTimelineEvent, HashtagTimelineScreen, HashtagTimelineUpdate};
+pub use instance_picker::{InstancePickerEvent, InstancePickerScreen};
+pub use profile_edit::{ProfileEditEvent, ProfileEditScreen, ProfileField, ProfileFieldUpdate};
 pub use qr::QrScreen;
-pub use timeline::TimelineScreen;
+pub use scheduled::{ScheduledStatusesEvent, ScheduledStatusesScreen, ScheduledStatusesUpdate};
+pub use settings::{SettingsEvent, SettingsScreen};
+pub use timeline::{TimelineEvent, TimelineScreen, TimelineUpdate};
+pub use trending_statuses::{
+    TrendingStatusesEvent, TrendingStatusesScreen, TrendingStatusesUpdate,
+};
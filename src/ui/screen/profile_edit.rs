@@ -0,0 +1,239 @@
+use std::{
+    error::Error,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Mutex,
+    },
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::{
+    html,
+    types::Account,
+    ui::{
+        citro2d::{color32, RenderTarget, Scene2d},
+        text::TextLines,
+        word_wrap, HidEvents, Screen, Ui, UiMsgSender,
+    },
+};
+
+/// A field of the profile that can be edited.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProfileField {
+    DisplayName,
+    Note,
+}
+
+/// Requests sent from the profile editor to the logic thread.
+pub enum ProfileEditEvent {
+    /// The user wants to leave without saving.
+    Back,
+    /// The user wants to change the given field.
+    Edit(ProfileField),
+    /// The user wants to save their changes.
+    Save { display_name: String, note: String },
+}
+
+/// Sent from the logic thread to the profile editor when a field changes.
+pub struct ProfileFieldUpdate {
+    field: ProfileField,
+    value: String,
+    lines: TextLines,
+}
+
+impl ProfileFieldUpdate {
+    pub fn new(tx: &UiMsgSender, field: ProfileField, value: String) -> Self {
+        let label = match field {
+            ProfileField::DisplayName => "Display name",
+            ProfileField::Note => "Bio",
+        };
+        let lines = word_wrap(tx, format!("{}\n{}\n", label, value), 352.0, 0.5);
+        Self {
+            field,
+            value,
+            lines,
+        }
+    }
+}
+
+/// The indent of profile metadata, leaving room for the verified badge.
+const METADATA_INDENT: f32 = 10.0;
+
+/// A name and value shown on the profile, which can't be edited here.
+struct ProfileMetadata {
+    content: TextLines,
+    /// When the link in the value was verified, if it was.
+    verified: Option<TextLines>,
+}
+
+impl ProfileMetadata {
+    fn height(&self) -> f32 {
+        let verified = match &self.verified {
+            Some(verified) => verified.height(),
+            None => 0.0,
+        };
+        self.content.height() + verified
+    }
+}
+
+/// Draw a small green box with a tick in it.
+fn draw_verified_badge(ctx: &Scene2d, x: f32, y: f32, color: u32) {
+    let white = color32(255, 255, 255, 255);
+    ctx.rect_solid(x, y, 5.0, 5.0, color);
+    ctx.rect_solid(x + 1.0, y + 2.0, 1.0, 1.0, white);
+    ctx.rect_solid(x + 2.0, y + 3.0, 1.0, 1.0, white);
+    ctx.rect_solid(x + 3.0, y + 1.0, 1.0, 1.0, white);
+}
+
+/// Lets the user change their display name and bio.
+pub struct ProfileEditScreen {
+    display_name: ProfileFieldUpdate,
+    note: ProfileFieldUpdate,
+    metadata: Vec<ProfileMetadata>,
+    selected: ProfileField,
+    hint: TextLines,
+    events: Mutex<Sender<ProfileEditEvent>>,
+    updates: Mutex<Receiver<ProfileFieldUpdate>>,
+}
+
+impl ProfileEditScreen {
+    pub fn new(
+        account: &Account,
+        tx: UiMsgSender,
+    ) -> Result<
+        (Self, Receiver<ProfileEditEvent>, Sender<ProfileFieldUpdate>),
+        Box<dyn Error + Send + Sync>,
+    > {
+        // prefer the plain text source of the bio, if we were given it
+        let note = match &account.source {
+            Some(source) => source.note.clone(),
+            None => html::to_plain_text(&account.note)?.trim_end().into(),
+        };
+        let display_name =
+            ProfileFieldUpdate::new(&tx, ProfileField::DisplayName, account.display_name.clone());
+        let note = ProfileFieldUpdate::new(&tx, ProfileField::Note, note);
+        let metadata = account
+            .fields
+            .iter()
+            .map(
+                |field| -> Result<ProfileMetadata, Box<dyn Error + Send + Sync>> {
+                    let value = html::to_plain_text(&field.value)?;
+                    let content = word_wrap(
+                        &tx,
+                        format!("{}: {}\n", field.name, value.trim()),
+                        352.0 - METADATA_INDENT,
+                        0.5,
+                    );
+                    let verified = field.is_verified().then(|| {
+                        let date = field
+                            .verified_at
+                            .map(|at| at.format("%Y-%m-%d").to_string())
+                            .unwrap_or_default();
+                        let label = format!("Verified {}\n", date);
+                        word_wrap(&tx, label, 352.0 - METADATA_INDENT, 0.4)
+                    });
+                    Ok(ProfileMetadata { content, verified })
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        let hint = word_wrap(&tx, "A: edit, START: save, B: cancel".into(), 360.0, 0.5);
+        let (events, events_rx) = std::sync::mpsc::channel();
+        let (updates_tx, updates) = std::sync::mpsc::channel();
+        Ok((
+            Self {
+                display_name,
+                note,
+                metadata,
+                selected: ProfileField::DisplayName,
+                hint,
+                events: Mutex::new(events),
+                updates: Mutex::new(updates),
+            },
+            events_rx,
+            updates_tx,
+        ))
+    }
+
+    fn send(&self, event: ProfileEditEvent) {
+        // ignore send errors, the logic thread may have already stopped
+        _ = self.events.lock().unwrap().send(event);
+    }
+
+    fn field(&mut self, field: ProfileField) -> &mut ProfileFieldUpdate {
+        match field {
+            ProfileField::DisplayName => &mut self.display_name,
+            ProfileField::Note => &mut self.note,
+        }
+    }
+}
+
+impl Screen for ProfileEditScreen {
+    fn title(&self) -> &str {
+        "Edit profile"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let mut y = 20.0;
+        for field in [&self.display_name, &self.note] {
+            if field.field == self.selected {
+                ctx.rect_solid(16.0, y, 368.0, field.lines.height(), colors.selection);
+            }
+            ui.draw_lines(ctx, 24.0, y, colors.foreground, &field.lines);
+            y += field.lines.height() + 8.0;
+        }
+
+        for metadata in &self.metadata {
+            if metadata.verified.is_some() {
+                draw_verified_badge(ctx, 24.0, y + 4.0, colors.success);
+            }
+            let x = 24.0 + METADATA_INDENT;
+            ui.draw_lines(ctx, x, y, colors.foreground, &metadata.content);
+            if let Some(verified) = &metadata.verified {
+                let verified_y = y + metadata.content.height();
+                ui.draw_lines(ctx, x, verified_y, colors.muted, verified);
+            }
+            y += metadata.height();
+        }
+
+        ui.draw_lines(
+            ctx,
+            20.0,
+            220.0 - self.hint.height(),
+            colors.muted,
+            &self.hint,
+        );
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        // apply any changes made with the keyboard
+        let updates = self.updates.lock().unwrap().try_iter().collect::<Vec<_>>();
+        for update in updates {
+            *self.field(update.field) = update;
+        }
+
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            self.send(ProfileEditEvent::Back);
+        } else if buttons.contains(KeyPad::KEY_START) {
+            self.send(ProfileEditEvent::Save {
+                display_name: self.display_name.value.clone(),
+                note: self.note.value.clone(),
+            });
+        } else if buttons.contains(KeyPad::KEY_A) {
+            self.send(ProfileEditEvent::Edit(self.selected));
+        } else if buttons.contains(KeyPad::KEY_DUP) {
+            self.selected = ProfileField::DisplayName;
+        } else if buttons.contains(KeyPad::KEY_DDOWN) {
+            self.selected = ProfileField::Note;
+        }
+    }
+}
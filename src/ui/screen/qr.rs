@@ -1,16 +1,27 @@
-use std::{error::Error, sync::Mutex};
+use std::{
+    error::Error,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Mutex,
+    },
+};
 
 use bit_set::BitSet;
+use ctru::prelude::KeyPad;
 use qrcode::{
     render::{Canvas, Pixel},
     QrCode,
 };
 
 use crate::ui::{
-    citro2d::{color32, Image, Luminance4, RenderTarget, Scene2d},
-    LogicImgPool, OpaqueImg, Screen, Ui,
+    citro2d::{Image, Luminance4, RenderTarget, Scene2d, WrapParam, LA8},
+    text::TextLines,
+    word_wrap, HidEvents, LogicImgPool, OpaqueImg, Screen, Ui, UiMsgSender,
 };
 
+/// The most characters of the URL to show below the code.
+const URL_LABEL_LEN: usize = 30;
+
 #[derive(Clone, Copy)]
 struct MyPixel(());
 
@@ -57,19 +68,37 @@ impl Canvas for MyCanvas {
     }
 }
 
+/// The size of one tile of the background pattern, which has a dot in its
+/// top left corner.
+const DOT_SPACING: u16 = 8;
+
+/// Shows a URL as a QR code, until the user continues or the timeout expires.
 pub struct QrScreen {
     qr_code: Mutex<OpaqueImg>,
+    dots: Mutex<OpaqueImg>,
     width: u16,
     height: u16,
+    instructions: TextLines,
+    url_label: TextLines,
+    /// Frames left until continuing automatically, if there is a timeout.
+    timeout_frames: Option<u32>,
+    /// Told when it's time to move on. Dropped after sending, so we only send once.
+    on_continue: Mutex<Option<Sender<()>>>,
 }
 
 impl QrScreen {
-    pub fn new(data: &[u8], pool: LogicImgPool) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let qr = QrCode::new(data)?;
+    pub fn new(
+        url: &str,
+        instructions: &str,
+        pool: LogicImgPool,
+        tx: UiMsgSender,
+        timeout_frames: Option<u32>,
+    ) -> Result<(Self, Receiver<()>), Box<dyn Error + Send + Sync>> {
+        let qr = QrCode::new(url.as_bytes())?;
         let image = qr.render::<MyPixel>().build();
         let width = image.width as u16;
         let height = image.height as u16;
-        let qr_code = pool.alloc(move |c2d| {
+        let qr_code = pool.alloc(width, height, move |c2d| {
             Image::build::<Luminance4, _>(c2d, width, height, |texture| {
                 // no filtering, so the qr code is crisp
                 texture.set_filter(false);
@@ -89,15 +118,69 @@ impl QrScreen {
                 }
             })
         });
-        Ok(Self {
-            qr_code: Mutex::new(qr_code),
-            width,
-            height,
-        })
+        let dots = pool.alloc(DOT_SPACING, DOT_SPACING, |c2d| {
+            Image::build::<LA8, _>(c2d, DOT_SPACING, DOT_SPACING, |texture| {
+                texture.set_wrap(WrapParam::Repeat, WrapParam::Repeat);
+                for y in 0..DOT_SPACING {
+                    for x in 0..DOT_SPACING {
+                        // a faint grey, so it shows up on light and dark themes
+                        let pixel = if x == 0 && y == 0 { 0x8030 } else { 0 };
+                        // SAFETY: for loops keep us in range
+                        unsafe {
+                            texture.set_unchecked(x, y, pixel);
+                        }
+                    }
+                }
+            })
+        });
+        let instructions = word_wrap(&tx, instructions.into(), 360.0, 0.5);
+        let url_label = match url.char_indices().nth(URL_LABEL_LEN) {
+            Some((end, _)) => format!("{}\u{2026}", &url[..end]),
+            None => url.into(),
+        };
+        let url_label = word_wrap(&tx, url_label, 360.0, 0.5);
+        let (on_continue, rx) = std::sync::mpsc::channel();
+        Ok((
+            Self {
+                qr_code: Mutex::new(qr_code),
+                dots: Mutex::new(dots),
+                width,
+                height,
+                instructions,
+                url_label,
+                timeout_frames,
+                on_continue: Mutex::new(Some(on_continue)),
+            },
+            rx,
+        ))
+    }
+
+    fn next(&self) {
+        if let Some(on_continue) = self.on_continue.lock().unwrap().take() {
+            // ignore send errors, the logic thread may have already stopped
+            _ = on_continue.send(());
+        }
     }
 }
 
 impl Screen for QrScreen {
+    fn title(&self) -> &str {
+        "QR code"
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        if input.pressed.contains(KeyPad::KEY_A) {
+            self.next();
+        }
+        if let Some(frames) = &mut self.timeout_frames {
+            if *frames == 0 {
+                self.next();
+            } else {
+                *frames -= 1;
+            }
+        }
+    }
+
     fn draw<'gfx: 'screen, 'screen>(
         &self,
         ui: &Ui<'gfx, 'screen>,
@@ -106,8 +189,31 @@ impl Screen for QrScreen {
     ) {
         let x = 200.0 - f32::from(self.width);
         let y = 120.0 - f32::from(self.height);
-        target.clear(color32(0, 0, 0, 255));
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+        let dots = self.dots.lock().unwrap();
+        ui.draw_image_tiled(&dots, ctx, 0.0, 0.0, 400.0, 240.0);
         let qr_code = self.qr_code.lock().unwrap();
         ui.draw_opaque_img(&qr_code, ctx, x, y, 2.0, 2.0);
+
+        ui.draw_lines(ctx, 20.0, 8.0, colors.foreground, &self.instructions);
+        ui.draw_lines(
+            ctx,
+            20.0,
+            124.0 + f32::from(self.height),
+            colors.foreground,
+            &self.url_label,
+        );
+        if let Some(frames) = self.timeout_frames {
+            let seconds = (frames + 59) / 60;
+            ui.draw_text(
+                ctx,
+                20.0,
+                216.0,
+                0.5,
+                colors.muted,
+                &format!("Auto-continue in {} seconds", seconds),
+            );
+        }
     }
 }
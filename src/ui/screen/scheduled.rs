@@ -0,0 +1,179 @@
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex,
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::{
+    types::ScheduledStatus,
+    ui::{
+        citro2d::{RenderTarget, Scene2d},
+        text::TextLines,
+        word_wrap, HidEvents, Screen, Ui, UiMsgSender,
+    },
+};
+
+/// Requests sent from the scheduled posts screen to the logic thread.
+pub enum ScheduledStatusesEvent {
+    /// The user wants to leave the screen.
+    Back,
+    /// The user wants to schedule another post.
+    Schedule,
+    /// The user wants to cancel the scheduled post with the given ID.
+    Cancel(String),
+}
+
+struct ScheduledEntry {
+    id: String,
+    content: TextLines,
+}
+
+/// The scheduled posts as the server has them, sent from the logic thread
+/// after a post is scheduled or cancelled.
+pub struct ScheduledStatusesUpdate(Vec<ScheduledEntry>);
+
+impl ScheduledStatusesUpdate {
+    pub fn new(tx: &UiMsgSender, statuses: Vec<ScheduledStatus>) -> Self {
+        let entries = statuses
+            .into_iter()
+            .map(|status| ScheduledEntry {
+                content: word_wrap(
+                    tx,
+                    format!(
+                        "at {}\n{}\n",
+                        status.scheduled_at.format("%Y-%m-%d %H:%M"),
+                        status.params.text,
+                    ),
+                    360.0,
+                    0.5,
+                ),
+                id: status.id,
+            })
+            .collect();
+        Self(entries)
+    }
+}
+
+/// Lists the statuses that are scheduled to be posted, one of which is
+/// selected.
+pub struct ScheduledStatusesScreen {
+    entries: Vec<ScheduledEntry>,
+    selected: usize,
+    scroll: f32,
+    hint: TextLines,
+    events: Mutex<Sender<ScheduledStatusesEvent>>,
+    updates: Mutex<Receiver<ScheduledStatusesUpdate>>,
+}
+
+impl ScheduledStatusesScreen {
+    pub fn new(
+        tx: &UiMsgSender,
+        first: ScheduledStatusesUpdate,
+    ) -> (
+        Self,
+        Receiver<ScheduledStatusesEvent>,
+        Sender<ScheduledStatusesUpdate>,
+    ) {
+        let hint = word_wrap(
+            tx,
+            "A: schedule, X: cancel post, B: back".into(),
+            360.0,
+            0.5,
+        );
+        let (events, events_rx) = std::sync::mpsc::channel();
+        let (updates_tx, updates) = std::sync::mpsc::channel();
+        (
+            Self {
+                entries: first.0,
+                selected: 0,
+                scroll: 0.0,
+                hint,
+                events: Mutex::new(events),
+                updates: Mutex::new(updates),
+            },
+            events_rx,
+            updates_tx,
+        )
+    }
+
+    fn send(&self, event: ScheduledStatusesEvent) {
+        // ignore send errors, the logic thread may have already stopped
+        _ = self.events.lock().unwrap().send(event);
+    }
+
+    fn scroll_to_selected(&mut self) {
+        let top = self.entries[..self.selected]
+            .iter()
+            .map(|entry| entry.content.height())
+            .sum::<f32>();
+        let bottom = match self.entries.get(self.selected) {
+            Some(entry) => top + entry.content.height(),
+            None => top,
+        };
+        // the list ends above the hint
+        if top < self.scroll {
+            self.scroll = top;
+        } else if bottom > self.scroll + 188.0 {
+            self.scroll = bottom - 188.0;
+        }
+    }
+}
+
+impl Screen for ScheduledStatusesScreen {
+    fn title(&self) -> &str {
+        "Scheduled posts"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let mut scroll = 20.0 - self.scroll;
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let height = entry.content.height();
+            if i == self.selected {
+                ctx.rect_solid(16.0, scroll, 368.0, height, colors.selection);
+            }
+            ui.draw_lines(ctx, 20.0, scroll, colors.foreground, &entry.content);
+            scroll += height;
+        }
+
+        // keep the controls visible over the list
+        ctx.rect_solid(0.0, 208.0, 400.0, 32.0, colors.background_bottom);
+        ui.draw_lines(ctx, 20.0, 216.0, colors.foreground, &self.hint);
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        if let Some(update) = self.updates.lock().unwrap().try_iter().last() {
+            self.entries = update.0;
+            self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+            self.scroll_to_selected();
+        }
+
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            self.send(ScheduledStatusesEvent::Back);
+        } else if buttons.contains(KeyPad::KEY_A) {
+            self.send(ScheduledStatusesEvent::Schedule);
+        } else if buttons.contains(KeyPad::KEY_DUP) {
+            self.selected = self.selected.saturating_sub(1);
+            self.scroll_to_selected();
+        } else if buttons.contains(KeyPad::KEY_DDOWN) {
+            if self.selected + 1 < self.entries.len() {
+                self.selected += 1;
+                self.scroll_to_selected();
+            }
+        } else if buttons.contains(KeyPad::KEY_X) {
+            if let Some(entry) = self.entries.get(self.selected) {
+                self.send(ScheduledStatusesEvent::Cancel(entry.id.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Arc, Mutex,
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::{
+    config::{
+        Config, Theme, DEFAULT_CIRCLE_PAD_DEADZONE, DEFAULT_FONT_SCALE, DEFAULT_SOC_BUFFER_SIZE,
+        DEFAULT_TIMEZONE_OFFSET,
+    },
+    net::retriever::RetrieverStats,
+    ui::{
+        citro2d::{texture_bytes, texture_count, RenderTarget, Scene2d},
+        scroll::ScrollState,
+        text::TextLines,
+        word_wrap, HidEvents, Screen, Ui, UiMsg, UiMsgSender,
+    },
+    util::tz::format_offset,
+};
+
+/// Requests sent from the settings screen to the logic thread.
+pub enum SettingsEvent {
+    /// The user wants to leave, having chosen the given settings.
+    Back(Config),
+}
+
+/// The largest circle pad deadzone that can be chosen.
+const MAX_CIRCLE_PAD_DEADZONE: i16 = 100;
+
+/// How much the circle pad deadzone changes with each press.
+const CIRCLE_PAD_DEADZONE_STEP: i16 = 5;
+
+/// The smallest and largest font scales that can be chosen.
+const MIN_FONT_SCALE: f32 = 0.3;
+const MAX_FONT_SCALE: f32 = 1.0;
+
+/// How much the font scale changes with each press.
+const FONT_SCALE_STEP: f32 = 0.1;
+
+/// The furthest the time zone can be from UTC, in minutes either way.
+const MAX_TIMEZONE_OFFSET: i32 = 12 * 60;
+
+/// How much the time zone changes with each press, in minutes.
+const TIMEZONE_OFFSET_STEP: i32 = 30;
+
+/// The gap between the tops of the rows, at the default font scale.
+const ROW_HEIGHT: f32 = 24.0;
+
+/// The number of rows of information shown below the settings.
+const INFO_ROWS: usize = 5;
+
+/// The settings that can be changed, in the order they are listed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Setting {
+    Theme,
+    CirclePadDeadzone,
+    FontScale,
+    TimezoneOffset,
+    MaxImageDimension,
+    MaxImageAlloc,
+    /// The socket service's buffer. Larger buffers use more memory, but are
+    /// more stable with large responses.
+    SocBufferSize,
+}
+
+impl Setting {
+    const ALL: [Setting; 7] = [
+        Setting::Theme,
+        Setting::CirclePadDeadzone,
+        Setting::FontScale,
+        Setting::TimezoneOffset,
+        Setting::MaxImageDimension,
+        Setting::MaxImageAlloc,
+        Setting::SocBufferSize,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&s| s == self).unwrap()
+    }
+}
+
+/// Lets the user change how the app looks.
+pub struct SettingsScreen {
+    selected: Setting,
+    /// The settings as chosen so far.
+    config: Config,
+    /// The number of images cached when the screen was opened.
+    cached_images: usize,
+    /// The number of images allocated when the screen was opened.
+    live_images: usize,
+    /// How the network has been doing, which keeps counting while the screen
+    /// is open.
+    network: Arc<RetrieverStats>,
+    hint: TextLines,
+    /// Larger text can make the rows taller than the screen, so they scroll.
+    scroll: ScrollState,
+    tx: Mutex<UiMsgSender>,
+    events: Mutex<Sender<SettingsEvent>>,
+}
+
+impl SettingsScreen {
+    pub fn new(
+        tx: UiMsgSender,
+        config: Config,
+        cached_images: usize,
+        live_images: usize,
+        network: Arc<RetrieverStats>,
+    ) -> (Self, Receiver<SettingsEvent>) {
+        let hint = word_wrap(
+            &tx,
+            "Up/Down: choose setting, Left/Right: change it, B: back".into(),
+            360.0,
+            0.5,
+        );
+        let (events, rx) = std::sync::mpsc::channel();
+        (
+            Self {
+                selected: Setting::Theme,
+                config,
+                cached_images,
+                live_images,
+                network,
+                hint,
+                scroll: ScrollState::default(),
+                tx: Mutex::new(tx),
+                events: Mutex::new(events),
+            },
+            rx,
+        )
+    }
+
+    fn set_theme(&mut self, theme: Theme) {
+        self.config.theme = theme;
+        // show the new theme straight away, so the user can see what it looks like
+        // ignore send errors, the ui may have already closed
+        _ = self.tx.lock().unwrap().send(UiMsg::SetTheme(theme));
+    }
+
+    fn set_font_scale(&mut self, font_scale: f32) {
+        // round to the nearest step, so that repeated steps don't drift
+        let font_scale = (font_scale / FONT_SCALE_STEP).round() * FONT_SCALE_STEP;
+        self.config.font_scale = font_scale.clamp(MIN_FONT_SCALE, MAX_FONT_SCALE);
+        // preview the new size straight away, like the theme
+        // ignore send errors, the ui may have already closed
+        let font_scale = UiMsg::SetFontScale(self.config.font_scale);
+        _ = self.tx.lock().unwrap().send(font_scale);
+    }
+
+    /// The gap between the tops of the rows at the chosen font scale.
+    fn row_height(&self) -> f32 {
+        ROW_HEIGHT * self.config.font_scale / DEFAULT_FONT_SCALE
+    }
+
+    /// How much of the list fits on the screen above the hint.
+    fn visible_height(&self) -> f32 {
+        196.0 - self.hint.height()
+    }
+
+    /// Keep the scroll position within the list, and the selected row fully
+    /// on the screen if it should be.
+    fn clamp_scroll(&mut self, show_selected: bool) {
+        let row_height = self.row_height();
+        let visible = self.visible_height();
+        let rows = Setting::ALL.len() + INFO_ROWS;
+        let max = (rows as f32 * row_height - visible).max(0.0);
+        let mut offset = self.scroll.offset().min(max);
+        if show_selected {
+            let top = self.selected.index() as f32 * row_height;
+            offset = offset.max(top + row_height - visible).min(top);
+        }
+        self.scroll.set_offset(offset);
+    }
+
+    /// Change the selected setting to its previous or next value.
+    fn change(&mut self, forward: bool) {
+        match self.selected {
+            Setting::Theme if forward => self.set_theme(self.config.theme.next()),
+            Setting::Theme => self.set_theme(self.config.theme.previous()),
+            Setting::CirclePadDeadzone => {
+                let step = if forward {
+                    CIRCLE_PAD_DEADZONE_STEP
+                } else {
+                    -CIRCLE_PAD_DEADZONE_STEP
+                };
+                self.config.circle_pad_deadzone =
+                    (self.config.circle_pad_deadzone + step).clamp(0, MAX_CIRCLE_PAD_DEADZONE);
+            }
+            Setting::FontScale if forward => {
+                self.set_font_scale(self.config.font_scale + FONT_SCALE_STEP)
+            }
+            Setting::FontScale => self.set_font_scale(self.config.font_scale - FONT_SCALE_STEP),
+            Setting::TimezoneOffset => {
+                let step = if forward {
+                    TIMEZONE_OFFSET_STEP
+                } else {
+                    -TIMEZONE_OFFSET_STEP
+                };
+                self.config.timezone_offset = (self.config.timezone_offset + step)
+                    .clamp(-MAX_TIMEZONE_OFFSET, MAX_TIMEZONE_OFFSET);
+            }
+            // the image limits double or halve with each press
+            Setting::MaxImageDimension => {
+                let dimension = if forward {
+                    self.config.max_image_dimension * 2
+                } else {
+                    self.config.max_image_dimension / 2
+                };
+                self.config.max_image_dimension = dimension.clamp(
+                    Config::MIN_IMAGE_DIMENSION,
+                    self.config.image_dimension_limit(),
+                );
+            }
+            Setting::MaxImageAlloc => {
+                let alloc = if forward {
+                    self.config.max_image_alloc * 2
+                } else {
+                    self.config.max_image_alloc / 2
+                };
+                self.config.max_image_alloc =
+                    alloc.clamp(Config::MIN_IMAGE_ALLOC, self.config.image_alloc_limit());
+            }
+            Setting::SocBufferSize => {
+                let size = if forward {
+                    self.config.soc_buffer_size * 2
+                } else {
+                    self.config.soc_buffer_size / 2
+                };
+                self.config.soc_buffer_size =
+                    size.clamp(Config::MIN_SOC_BUFFER_SIZE, Config::MAX_SOC_BUFFER_SIZE);
+            }
+        }
+    }
+}
+
+impl Screen for SettingsScreen {
+    fn title(&self) -> &str {
+        "Settings"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let row_height = self.row_height();
+        let top = 20.0 - self.scroll.offset();
+        let selected_y = top + self.selected.index() as f32 * row_height;
+        ctx.rect_solid(16.0, selected_y, 368.0, row_height - 4.0, colors.selection);
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 2.0,
+            0.5,
+            colors.foreground,
+            &format!("Theme: < {} >", self.config.theme.name()),
+        );
+        let deadzone = if self.config.circle_pad_deadzone == DEFAULT_CIRCLE_PAD_DEADZONE {
+            format!("{} (default)", self.config.circle_pad_deadzone)
+        } else {
+            self.config.circle_pad_deadzone.to_string()
+        };
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 2.0 + row_height,
+            0.5,
+            colors.foreground,
+            &format!("Circle pad deadzone: < {} >", deadzone),
+        );
+        let font_scale = if self.config.font_scale == DEFAULT_FONT_SCALE {
+            format!("{:.1} (default)", self.config.font_scale)
+        } else {
+            format!("{:.1}", self.config.font_scale)
+        };
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 2.0 + row_height * 2.0,
+            0.5,
+            colors.foreground,
+            &format!("Text size: < {} >", font_scale),
+        );
+        let timezone = if self.config.timezone_offset == DEFAULT_TIMEZONE_OFFSET {
+            format!("{} (default)", format_offset(self.config.timezone_offset))
+        } else {
+            format_offset(self.config.timezone_offset)
+        };
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 2.0 + row_height * 3.0,
+            0.5,
+            colors.foreground,
+            &format!("Time zone: < {} >", timezone),
+        );
+        let defaults = Config::new(self.config.new_3ds);
+        let dimension = if self.config.max_image_dimension == defaults.max_image_dimension {
+            format!("{} px (default)", self.config.max_image_dimension)
+        } else {
+            format!("{} px", self.config.max_image_dimension)
+        };
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 2.0 + row_height * 4.0,
+            0.5,
+            colors.foreground,
+            &format!("Max image size: < {} >", dimension),
+        );
+        let alloc_mb = self.config.max_image_alloc / (1024 * 1024);
+        let alloc = if self.config.max_image_alloc == defaults.max_image_alloc {
+            format!("{} MB (default)", alloc_mb)
+        } else {
+            format!("{} MB", alloc_mb)
+        };
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 2.0 + row_height * 5.0,
+            0.5,
+            colors.foreground,
+            &format!("Image memory: < {} >", alloc),
+        );
+        let soc_kb = self.config.soc_buffer_size / 1024;
+        let soc = if self.config.soc_buffer_size == DEFAULT_SOC_BUFFER_SIZE {
+            format!("{} KB (default)", soc_kb)
+        } else {
+            format!("{} KB", soc_kb)
+        };
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 2.0 + row_height * 6.0,
+            0.5,
+            colors.foreground,
+            &format!("Network buffer: < {} >", soc),
+        );
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 4.0 + row_height * 7.0,
+            0.5,
+            colors.muted,
+            "Bigger uses more RAM, but is more stable (after restart)",
+        );
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 4.0 + row_height * 8.0,
+            0.5,
+            colors.muted,
+            &format!("Cached images: {}", self.cached_images),
+        );
+        // atlas images share textures and fonts have textures of their own,
+        // so these aren't expected to match
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 4.0 + row_height * 9.0,
+            0.5,
+            colors.muted,
+            &format!(
+                "Live images: {} / GPU textures: {}",
+                self.live_images,
+                texture_count()
+            ),
+        );
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 4.0 + row_height * 10.0,
+            0.5,
+            colors.muted,
+            &format!("GPU texture memory: {} KB", texture_bytes() / 1024),
+        );
+        ui.draw_text(
+            ctx,
+            24.0,
+            top + 4.0 + row_height * 11.0,
+            0.5,
+            colors.muted,
+            &self.network.to_string(),
+        );
+
+        // keep the hint visible over the list
+        let hint_y = 220.0 - self.hint.height();
+        let bar_y = hint_y - 4.0;
+        ctx.rect_solid(0.0, bar_y, 400.0, 240.0 - bar_y, colors.background_bottom);
+        ui.draw_lines(ctx, 20.0, hint_y, colors.muted, &self.hint);
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            // ignore send errors, the logic thread may have already stopped
+            _ = self
+                .events
+                .lock()
+                .unwrap()
+                .send(SettingsEvent::Back(self.config));
+        } else if buttons.contains(KeyPad::KEY_DUP) {
+            let index = self.selected.index().saturating_sub(1);
+            self.selected = Setting::ALL[index];
+            self.clamp_scroll(true);
+        } else if buttons.contains(KeyPad::KEY_DDOWN) {
+            let index = (self.selected.index() + 1).min(Setting::ALL.len() - 1);
+            self.selected = Setting::ALL[index];
+            self.clamp_scroll(true);
+        } else if buttons.intersects(KeyPad::KEY_DLEFT | KeyPad::KEY_L) {
+            self.change(false);
+            // the rows change height with the text size
+            self.clamp_scroll(true);
+        } else if buttons.intersects(KeyPad::KEY_DRIGHT | KeyPad::KEY_R) {
+            self.change(true);
+            self.clamp_scroll(true);
+        }
+
+        // the D-pad chooses settings, so only the circle pad and touch screen
+        // scroll freely, such as to see the information below the settings
+        self.scroll.update_analog(input);
+        self.scroll.update_touch(input);
+        self.clamp_scroll(false);
+    }
+}
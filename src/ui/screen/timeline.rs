@@ -1,145 +1,877 @@
-use std::{error::Error, sync::Arc};
-
-use ctru::{prelude::KeyPad, services::Hid};
-use quick_xml::events::Event;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc, Mutex,
+    },
+};
 
 use crate::{
+    config::DEFAULT_TIMEZONE_OFFSET,
+    html::{self, HtmlSpan, SpanKind},
     net::Client,
+    types::{CustomEmoji, StatusTag},
     ui::{
-        citro2d::{color32, RenderTarget, Scene2d},
+        citro2d::{frame_count, RenderTarget, Scene2d},
+        scroll::ScrollState,
         text::TextLines,
-        CachedImage, LogicImgPool, Screen, Ui, UiMsg, UiMsgSender, WebImageCache,
+        word_wrap_batch, word_wrap_html, word_wrap_html_batch, CachedImage, HidEvents,
+        LogicImgPool, Screen, Ui, UiMsg, UiMsgSender, WebImageCache,
     },
+    util::{time::readable_relative_time, tz::utc_offset},
 };
+use chrono::{DateTime, FixedOffset, Utc};
+use ctru::prelude::KeyPad;
+use serde::{Deserialize, Serialize};
+
+/// The width and height that avatars are shown at.
+const AVATAR_SIZE: u16 = 32;
+
+/// How often to update how long ago statuses were posted, in frames.
+const TIMESTAMP_REFRESH_FRAMES: u64 = 1800;
+
+/// Requests sent from the timeline to the logic thread.
+pub enum TimelineEvent {
+    /// The user dismissed the announcement with the given ID.
+    DismissAnnouncement(String),
+    /// The user wants to see their direct messages.
+    OpenDirectMessages,
+    /// The user wants to edit their profile.
+    EditProfile,
+    /// The user wants to change their settings.
+    OpenSettings,
+    /// The user wants to see what is popular.
+    OpenTrending,
+    /// The user wants to see the statuses they have scheduled.
+    OpenScheduled,
+    /// The user tapped a mention or hashtag with the given URL.
+    OpenLink(String),
+    /// The user wants to see what they can do to the status with the given ID.
+    OpenActions(String),
+    /// The user wants to see the statuses with the given hashtag.
+    OpenHashtag(String),
+    /// The user added or removed their reaction to an announcement.
+    ToggleReaction {
+        announcement_id: String,
+        name: String,
+        /// True to add the reaction, false to remove it.
+        add: bool,
+    },
+}
+
+/// Sent from the logic thread to the timeline when settings change.
+pub enum TimelineUpdate {
+    CirclePadDeadzone(i16),
+    /// The user's time zone, in minutes east of UTC.
+    TimezoneOffset(i32),
+    /// The server didn't accept a ToggleReaction, so change the reaction back.
+    UndoReaction {
+        announcement_id: String,
+        name: String,
+    },
+}
+
+/// Where the wrapped text of the last timeline is saved between launches.
+static WRAPPED_CACHE_PATH: &str = "/toot-3d-wrapped.json";
+
+/// The height of the row of reactions below an announcement.
+const REACTION_HEIGHT: f32 = 20.0;
+
+/// The height of the row of hashtags below a status.
+const TAG_HEIGHT: f32 = 18.0;
+
+/// How wide the row of hashtags below a status can be, leaving room to show
+/// that there are more.
+const TAG_ROW_WIDTH: f32 = 340.0;
+
+struct ReactionBox {
+    /// The Unicode emoji reacted with.
+    name: String,
+    count: u64,
+    /// True if the logged-in account has reacted with this emoji.
+    me: bool,
+}
+
+impl ReactionBox {
+    /// Add or remove the logged-in account's reaction.
+    fn toggle(&mut self) {
+        self.me = !self.me;
+        if self.me {
+            self.count += 1;
+        } else {
+            self.count = self.count.saturating_sub(1);
+        }
+    }
+}
+
+struct AnnouncementBanner {
+    id: String,
+    content: TextLines,
+    reactions: Vec<ReactionBox>,
+}
 
 struct TimelineStatus {
+    id: String,
+    /// Who boosted the status, if it is a boost.
+    boosted_by: Option<TextLines>,
     avatar: CachedImage,
     content: TextLines,
+    /// True if the status was posted by the logged-in account.
+    own: bool,
+    /// True if the logged-in account has bookmarked the status.
+    bookmarked: bool,
+    created_at: DateTime<Utc>,
+    /// How long ago the status was posted, as of the last refresh.
+    time: String,
+    /// The hashtags used in the status, shown below its content.
+    tags: Vec<StatusTag>,
 }
 
-pub struct TimelineScreen {
-    statuses: Vec<TimelineStatus>,
-    scroll: f32,
+/// Which part of the selected status the A button acts on.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StatusFocus {
+    /// The status itself, to see what can be done to it.
+    FocusContent,
+    /// The hashtag at the given index, to see other statuses with it.
+    FocusTags(usize),
 }
 
-// will need to move this somewhere else later
-fn parse_html(html: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
-    let mut reader = quick_xml::reader::Reader::from_str(html);
-    reader.check_end_names(false);
-    let mut result = String::new();
+/// The wrapped content of a status, saved for the next launch.
+#[derive(Deserialize, Serialize)]
+struct WrappedContent {
+    /// When the status was last edited, as the saved text is out of date if
+    /// it has been edited since.
+    edited_at: Option<DateTime<Utc>>,
+    content: TextLines,
+}
 
-    loop {
-        match reader.read_event()? {
-            Event::Eof => break,
+/// The wrapped content of the statuses from the last timeline, so that
+/// statuses seen before don't need to be wrapped again.
+#[derive(Deserialize, Serialize)]
+struct WrappedCache {
+    /// The font scale the text was wrapped for.
+    font_scale: f32,
+    /// The content of each status, by the ID it has on the timeline.
+    statuses: HashMap<String, WrappedContent>,
+}
 
-            Event::Start(e) => match e.name().as_ref() {
-                _ => {}
-            },
+impl WrappedCache {
+    fn new(font_scale: f32) -> Self {
+        Self {
+            font_scale,
+            statuses: HashMap::new(),
+        }
+    }
 
-            Event::End(e) => match e.name().as_ref() {
-                b"p" | b"br" => result.push('\n'),
-                _ => {}
-            },
+    /// Load the saved cache. If there isn't one, or it was wrapped for
+    /// another font scale, an empty one is returned.
+    fn load(font_scale: f32) -> Self {
+        let saved = File::open(WRAPPED_CACHE_PATH)
+            .ok()
+            .and_then(|file| serde_json::from_reader::<_, Self>(file).ok());
+        match saved {
+            Some(cache) if cache.font_scale == font_scale => cache,
+            _ => Self::new(font_scale),
+        }
+    }
 
-            Event::Text(e) => {
-                result.push_str(&e.unescape()?);
-            }
+    fn save(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let file = File::create(WRAPPED_CACHE_PATH)?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
 
-            _ => {}
-        }
+    /// Take the content of a status out of the cache, if it is up to date.
+    fn take(&mut self, id: &str, edited_at: Option<DateTime<Utc>>) -> Option<TextLines> {
+        let wrapped = self.statuses.remove(id)?;
+        (wrapped.edited_at == edited_at).then_some(wrapped.content)
+    }
+
+    fn insert(&mut self, id: String, edited_at: Option<DateTime<Utc>>, content: TextLines) {
+        self.statuses
+            .insert(id, WrappedContent { edited_at, content });
+    }
+}
+
+/// A status that has been read, but whose text hasn't been wrapped yet.
+struct PendingStatus {
+    id: String,
+    /// The text of the boosted-by line, if the status is a boost.
+    boosted_by: Option<String>,
+    avatar: CachedImage,
+    spans: Vec<HtmlSpan>,
+    own: bool,
+    bookmarked: bool,
+    created_at: DateTime<Utc>,
+    edited_at: Option<DateTime<Utc>>,
+    tags: Vec<StatusTag>,
+}
+
+/// Fills the loading bar when dropped, so that it goes away however loading
+/// the timeline ends.
+struct FinishProgress<'a>(&'a UiMsgSender);
+
+impl Drop for FinishProgress<'_> {
+    fn drop(&mut self) {
+        // ignore send errors, the progress bar is only cosmetic
+        _ = self.0.send(UiMsg::Progress(1.0));
     }
+}
 
-    Ok(result)
+/// Draw a small ribbon with a notch at the bottom.
+fn draw_bookmark(ctx: &Scene2d, x: f32, y: f32, color: u32) {
+    ctx.rect_solid(x, y, 8.0, 9.0, color);
+    ctx.rect_solid(x, y + 9.0, 3.0, 3.0, color);
+    ctx.rect_solid(x + 5.0, y + 9.0, 3.0, 3.0, color);
+}
+
+impl TimelineStatus {
+    /// The height of the status, not including the separator below it.
+    fn height(&self) -> f32 {
+        let tags = if self.tags.is_empty() {
+            0.0
+        } else {
+            TAG_HEIGHT
+        };
+        self.content_offset() + self.content.height() + tags
+    }
+
+    /// How far below the top of the status its content starts.
+    fn content_offset(&self) -> f32 {
+        let boosted_by = self.boosted_by.as_ref().map_or(0.0, TextLines::height);
+        boosted_by + f32::from(AVATAR_SIZE)
+    }
+
+    /// The gap left between statuses.
+    fn separator_height() -> f32 {
+        4.0
+    }
+}
+
+impl AnnouncementBanner {
+    /// The height of the banner, not including the gap below it.
+    fn height(&self) -> f32 {
+        let reactions = if self.reactions.is_empty() {
+            0.0
+        } else {
+            REACTION_HEIGHT
+        };
+        self.content.height() + reactions + 8.0
+    }
+}
+
+pub struct TimelineScreen {
+    announcements: Vec<AnnouncementBanner>,
+    statuses: Vec<TimelineStatus>,
+    scroll: ScrollState,
+    /// The status to highlight, if any.
+    focused_index: Option<usize>,
+    /// The part of the highlighted status that is chosen.
+    focused_element: StatusFocus,
+    /// The selected reaction of the topmost announcement.
+    selected_reaction: usize,
+    /// Images of the custom emojis used, by shortcode.
+    emojis: HashMap<String, CachedImage>,
+    /// The time zone to show dates in.
+    timezone: FixedOffset,
+    events: Mutex<Sender<TimelineEvent>>,
+    updates: Mutex<Receiver<TimelineUpdate>>,
+}
+
+fn draw_border(ctx: &Scene2d, x: f32, y: f32, w: f32, h: f32, color: u32) {
+    ctx.rect_solid(x, y, w, 2.0, color);
+    ctx.rect_solid(x, y + h - 2.0, w, 2.0, color);
+    ctx.rect_solid(x, y + 2.0, 2.0, h - 4.0, color);
+    ctx.rect_solid(x + w - 2.0, y + 2.0, 2.0, h - 4.0, color);
 }
 
 impl TimelineScreen {
+    /// Load the home timeline. The font scale is the one text is being drawn
+    /// at, for reusing text wrapped before.
     pub fn new(
         cache: &Arc<WebImageCache>,
         client: &Client,
         pool: &LogicImgPool,
         tx: UiMsgSender,
-    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        font_scale: f32,
+    ) -> Result<(Self, Receiver<TimelineEvent>, Sender<TimelineUpdate>), Box<dyn Error + Send + Sync>>
+    {
+        Self::new_focused(cache, client, pool, tx, font_scale, None)
+    }
+
+    /// Load the home timeline, with the status with the given ID highlighted
+    /// and scrolled to the middle of the screen, if it is still there.
+    pub fn new_focused(
+        cache: &Arc<WebImageCache>,
+        client: &Client,
+        pool: &LogicImgPool,
+        tx: UiMsgSender,
+        font_scale: f32,
+        focused_id: Option<&str>,
+    ) -> Result<(Self, Receiver<TimelineEvent>, Sender<TimelineUpdate>), Box<dyn Error + Send + Sync>>
+    {
+        // ignore send errors here and below, the progress bar is only cosmetic
+        _ = tx.send(UiMsg::Progress(0.0));
+        let _finish_progress = FinishProgress(&tx);
+        // emojis are only decoration, so go without them if they can't be loaded
+        let instance_emojis = client.get_custom_emojis().unwrap_or_default();
+        // image URLs of the emojis that are used, by shortcode
+        let mut emoji_urls = HashMap::new();
+        let mut add_emoji_urls = |spans: &[HtmlSpan], status_emojis: &[CustomEmoji]| {
+            for span in spans {
+                if let SpanKind::Emoji(shortcode) = &span.kind {
+                    let emoji = status_emojis
+                        .iter()
+                        .find(|emoji| emoji.shortcode == *shortcode)
+                        .or_else(|| instance_emojis.get(shortcode));
+                    if let Some(emoji) = emoji {
+                        emoji_urls.insert(shortcode.clone(), emoji.static_url.clone());
+                    }
+                }
+            }
+        };
+        // only show announcements that the user hasn't seen yet
+        let announcements = client
+            .get_announcements()?
+            .into_iter()
+            .filter(|announcement| !announcement.read)
+            .map(
+                |announcement| -> Result<AnnouncementBanner, Box<dyn Error + Send + Sync>> {
+                    let mut spans = vec![HtmlSpan::plain("Announcement\n")];
+                    spans.extend(html::parse_with_emojis(&announcement.text, |shortcode| {
+                        instance_emojis.contains_key(shortcode)
+                    })?);
+                    add_emoji_urls(&spans, &[]);
+                    let content = word_wrap_html(&tx, spans, 352.0, 0.5);
+                    // reacting with custom emojis isn't supported, so only
+                    // show the Unicode ones
+                    let reactions = announcement
+                        .reactions
+                        .into_iter()
+                        .filter(|reaction| reaction.url.is_none())
+                        .map(|reaction| ReactionBox {
+                            name: reaction.name,
+                            count: reaction.count,
+                            me: reaction.me,
+                        })
+                        .collect();
+                    Ok(AnnouncementBanner {
+                        id: announcement.id,
+                        content,
+                        reactions,
+                    })
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
         let statuses = client.get_home_timeline()?;
         // get list of avatars
-        let avatars = cache.get(
+        let avatars = cache.get_with_progress(
             client.retriever(),
             pool,
             &statuses
                 .iter()
-                .map(|status| (status.account.avatar_static.as_str(), Some(32)))
+                .map(|status| {
+                    let avatar = &status.effective_status().account.avatar_static;
+                    (avatar.as_str(), Some(AVATAR_SIZE))
+                })
                 .collect::<Vec<_>>()[..],
+            |progress| {
+                _ = tx.send(UiMsg::Progress(progress));
+            },
         )?;
-        let statuses = statuses
+        let own_id = client.logged_in_account().map(|account| account.id.clone());
+        let home_instance = client.logged_in_instance();
+        let mut pending = statuses
             .into_iter()
             .zip(avatars)
             .map(
-                |(status, avatar)| -> Result<TimelineStatus, Box<dyn Error + Send + Sync>> {
-                    let (lines_tx, lines_rx) = std::sync::mpsc::channel();
-                    tx.send(UiMsg::WordWrap {
-                        text: format!(
-                            "from {}\n{}\n",
+                |(status, avatar)| -> Result<PendingStatus, Box<dyn Error + Send + Sync>> {
+                    let boosted_by = status.is_reblog().then(|| {
+                        format!(
+                            "Boosted by {} {}\n",
                             status.account.display_name,
-                            parse_html(&status.content)?
-                        ),
-                        width: 360.0,
-                        scale: 0.5,
-                        tx: lines_tx,
+                            status.account.short_acct(home_instance)
+                        )
+                    });
+                    let id = status.id.clone();
+                    // show the original status for boosts
+                    let status = status.effective_status();
+                    let mut spans = vec![HtmlSpan::plain(format!(
+                        "from {} {}\n",
+                        status.account.display_name,
+                        status.account.short_acct(home_instance)
+                    ))];
+                    spans.extend(html::parse_with_emojis(&status.content, |shortcode| {
+                        status
+                            .emojis
+                            .iter()
+                            .any(|emoji| emoji.shortcode == shortcode)
+                            || instance_emojis.contains_key(shortcode)
+                    })?);
+                    spans.push(HtmlSpan::plain("\n"));
+                    add_emoji_urls(&spans, &status.emojis);
+                    Ok(PendingStatus {
+                        own: own_id.as_ref() == Some(&status.account.id),
+                        bookmarked: status.bookmarked || client.is_bookmarked(&status.id),
+                        created_at: status.created_at,
+                        edited_at: status.edited_at,
+                        tags: status.tags.clone(),
+                        id,
+                        boosted_by,
+                        avatar,
+                        spans,
                     })
-                    .unwrap();
-                    let content = lines_rx.recv().unwrap();
-                    Ok(TimelineStatus { avatar, content })
                 },
             )
             .collect::<Result<Vec<_>, _>>()?;
-        Ok(Self {
+        // wrap all of the text at once, rather than waiting on the UI thread
+        // for every status
+        let boosted_by = pending
+            .iter()
+            .filter_map(|status| status.boosted_by.clone())
+            .map(|text| (text, 360.0, 0.5))
+            .collect();
+        let mut boosted_by = word_wrap_batch(&tx, boosted_by).into_iter();
+        // statuses seen last time don't need wrapping again
+        let mut wrapped = WrappedCache::load(font_scale);
+        let cached = pending
+            .iter()
+            .map(|status| wrapped.take(&status.id, status.edited_at))
+            .collect::<Vec<_>>();
+        let contents = pending
+            .iter_mut()
+            .zip(&cached)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(status, _)| (std::mem::take(&mut status.spans), 360.0, 0.5))
+            .collect();
+        let mut contents = word_wrap_html_batch(&tx, contents).into_iter();
+        // the statuses that weren't cached were wrapped in order
+        let contents = cached
+            .into_iter()
+            .map(|cached| cached.or_else(|| contents.next()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or("some statuses could not be wrapped")?;
+        // only keep this timeline's statuses, so the cache doesn't keep growing
+        let mut wrapped = WrappedCache::new(font_scale);
+        for (status, content) in pending.iter().zip(&contents) {
+            wrapped.insert(status.id.clone(), status.edited_at, content.clone());
+        }
+        // the cache only saves time, so the timeline works without it
+        _ = wrapped.save();
+        // the logic thread sends the chosen time zone when the settings change
+        let timezone = utc_offset(DEFAULT_TIMEZONE_OFFSET);
+        let statuses = pending
+            .into_iter()
+            .zip(contents)
+            .map(|(status, content)| TimelineStatus {
+                time: readable_relative_time(&status.created_at, timezone),
+                created_at: status.created_at,
+                own: status.own,
+                bookmarked: status.bookmarked,
+                id: status.id,
+                // the boosted statuses were wrapped in order
+                boosted_by: status.boosted_by.and_then(|_| boosted_by.next()),
+                avatar: status.avatar,
+                content,
+                tags: status.tags,
+            })
+            .collect();
+        let emojis = emoji_urls
+            .into_iter()
+            .map(|(shortcode, url)| {
+                let image = cache.get_or_placeholder(client.retriever(), pool, &url, Some(32));
+                (shortcode, image)
+            })
+            .collect();
+        let (events, rx) = std::sync::mpsc::channel();
+        let (updates_tx, updates) = std::sync::mpsc::channel();
+        let mut result = Self {
+            announcements,
             statuses,
-            scroll: 0.0,
-        })
+            scroll: ScrollState::default(),
+            focused_index: None,
+            focused_element: StatusFocus::FocusContent,
+            selected_reaction: 0,
+            emojis,
+            timezone,
+            events: Mutex::new(events),
+            updates: Mutex::new(updates),
+        };
+        if let Some(focused_id) = focused_id {
+            result.focused_index = result
+                .statuses
+                .iter()
+                .position(|status| status.id == focused_id);
+        }
+        if let Some(index) = result.focused_index {
+            // center the focused status on the screen
+            let middle = result.status_top(index) + result.statuses[index].height() / 2.0;
+            result.scroll.set_offset(middle + 20.0 - 120.0);
+        }
+        Ok((result, rx, updates_tx))
+    }
+
+    /// The position of the top of a status, relative to the top of the timeline.
+    fn status_top(&self, index: usize) -> f32 {
+        let announcements = self
+            .announcements
+            .iter()
+            .map(|announcement| announcement.height() + 4.0)
+            .sum::<f32>();
+        let statuses = self.statuses[..index]
+            .iter()
+            .map(|status| status.height() + TimelineStatus::separator_height())
+            .sum::<f32>();
+        announcements + statuses
+    }
+
+    /// Work out again how long ago each status was posted.
+    fn refresh_timestamps(&mut self) {
+        for status in &mut self.statuses {
+            status.time = readable_relative_time(&status.created_at, self.timezone);
+        }
+    }
+
+    /// Add or remove the selected reaction of the topmost announcement.
+    fn toggle_reaction(&mut self) {
+        let announcement = match self.announcements.first_mut() {
+            Some(announcement) => announcement,
+            None => return,
+        };
+        let reaction = match announcement.reactions.get_mut(self.selected_reaction) {
+            Some(reaction) => reaction,
+            None => return,
+        };
+        // show the change straight away, rather than waiting for the server
+        reaction.toggle();
+        // ignore send errors, the logic thread may have already stopped
+        _ = self
+            .events
+            .lock()
+            .unwrap()
+            .send(TimelineEvent::ToggleReaction {
+                announcement_id: announcement.id.clone(),
+                name: reaction.name.clone(),
+                add: reaction.me,
+            });
+    }
+
+    fn select_status(&mut self, index: usize) {
+        self.focused_index = Some(index);
+        self.focused_element = StatusFocus::FocusContent;
+    }
+
+    /// Move between the selected status and its hashtags.
+    fn move_focus(&mut self, forward: bool) {
+        let tag_count = match self.focused_index.and_then(|i| self.statuses.get(i)) {
+            Some(status) => status.tags.len(),
+            None => return,
+        };
+        self.focused_element = match self.focused_element {
+            StatusFocus::FocusContent if forward && tag_count > 0 => StatusFocus::FocusTags(0),
+            StatusFocus::FocusTags(i) if forward && i + 1 < tag_count => {
+                StatusFocus::FocusTags(i + 1)
+            }
+            StatusFocus::FocusTags(0) if !forward => StatusFocus::FocusContent,
+            StatusFocus::FocusTags(i) if !forward => StatusFocus::FocusTags(i - 1),
+            focus => focus,
+        };
+    }
+
+    /// Follow the mention or hashtag on the line the user tapped, if any,
+    /// or otherwise select the status that was tapped. The touch screen is
+    /// treated as lining up with the top screen, so only the height of the
+    /// tap matters. Drags scroll instead, so they don't count.
+    fn update_touch(&mut self, input: &HidEvents) {
+        let touch_y = match input.tap() {
+            Some((_, touch_y)) => f32::from(touch_y),
+            None => return,
+        };
+        for (i, status) in self.statuses.iter().enumerate() {
+            let top = 20.0 - self.scroll.offset() + self.status_top(i);
+            if touch_y < top || touch_y >= top + status.height() {
+                continue;
+            }
+            let ranges = status.content.line_y_ranges(top + status.content_offset());
+            let line = ranges
+                .iter()
+                .position(|&(top, bottom, _)| touch_y >= top && touch_y < bottom);
+            match line.and_then(|line| status.content.line_target(line)) {
+                Some(SpanKind::Mention(url) | SpanKind::Hashtag(url)) => {
+                    // ignore send errors, the logic thread may have already stopped
+                    _ = self
+                        .events
+                        .lock()
+                        .unwrap()
+                        .send(TimelineEvent::OpenLink(url.clone()));
+                }
+                _ => self.select_status(i),
+            }
+            return;
+        }
     }
 }
 
 impl Screen for TimelineScreen {
+    fn title(&self) -> &str {
+        "Home"
+    }
+
     fn draw<'gfx: 'screen, 'screen>(
         &self,
         ui: &Ui<'gfx, 'screen>,
         target: &RenderTarget<'gfx, 'screen>,
         ctx: &Scene2d,
     ) {
-        target.clear(color32(0, 0, 0, 255));
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
 
-        let mut scroll = 20.0 - self.scroll;
+        let mut scroll = 20.0 - self.scroll.offset();
 
-        for status in &self.statuses {
+        let mut first = true;
+        for announcement in &self.announcements {
+            ui.draw_panel(ctx, 16.0, scroll - 4.0, 368.0, announcement.height());
+            ui.draw_lines_with_emojis(
+                ctx,
+                24.0,
+                scroll,
+                colors.foreground,
+                &announcement.content,
+                &self.emojis,
+                Some((0.0, 240.0)),
+            );
+            let mut x = 24.0;
+            let y = scroll + announcement.content.height() + 2.0;
+            for (i, reaction) in announcement.reactions.iter().enumerate() {
+                let color = if reaction.me {
+                    colors.accent
+                } else {
+                    colors.foreground
+                };
+                let label = format!("{} {}", reaction.name, reaction.count);
+                let width = ui.text_width(&label, 0.5) + 8.0;
+                // only the topmost announcement's reactions can be chosen
+                if first && i == self.selected_reaction {
+                    ctx.rect_solid(x, y, width, 16.0, colors.selection);
+                }
+                draw_border(ctx, x, y - 2.0, width, 20.0, colors.muted);
+                ui.draw_text(ctx, x + 4.0, y, 0.5, color, &label);
+                x += width + 4.0;
+            }
+            first = false;
+            scroll += announcement.height() + 4.0;
+        }
+
+        for (i, status) in self.statuses.iter().enumerate() {
+            if Some(i) == self.focused_index {
+                ctx.rect_solid(16.0, scroll, 368.0, status.height(), colors.selection);
+            }
+            if let Some(boosted_by) = &status.boosted_by {
+                ui.draw_lines(ctx, 20.0, scroll, colors.muted, boosted_by);
+                scroll += boosted_by.height();
+            }
             let img = status.avatar.image().image.lock().unwrap();
-            ui.draw_opaque_img(
-                &img,
+            let avatar_size = f32::from(AVATAR_SIZE);
+            // servers crop avatars to squares, so covering the square just
+            // avoids thin gaps when decoding rounds one side down
+            ui.draw_image_fill(&img, ctx, 20.0, scroll, avatar_size, avatar_size);
+            if status.own {
+                ui.draw_text(
+                    ctx,
+                    28.0 + avatar_size,
+                    scroll + 8.0,
+                    0.5,
+                    colors.success,
+                    "Your post",
+                );
+            }
+            if status.bookmarked {
+                draw_bookmark(ctx, 372.0, scroll + 4.0, colors.accent);
+            }
+            scroll += avatar_size;
+            let time_width = ui.text_width(&status.time, 0.5);
+            ui.draw_text(
                 ctx,
-                20.0,
+                380.0 - time_width,
                 scroll,
-                32.0 / f32::from(status.avatar.image().width),
-                32.0 / f32::from(status.avatar.image().height),
+                0.5,
+                colors.muted,
+                &status.time,
             );
-            scroll += 32.0;
-            ui.draw_lines(
+            ui.draw_lines_with_emojis(
                 ctx,
                 20.0,
                 scroll,
-                color32(255, 255, 255, 255),
+                colors.foreground,
                 &status.content,
+                &self.emojis,
+                Some((0.0, 240.0)),
             );
             scroll += status.content.height();
+            if !status.tags.is_empty() {
+                let labels = status.tags.iter().map(|tag| format!("#{}", tag.name));
+                let labels = labels.collect::<Vec<_>>();
+                let widths = labels.iter().map(|label| ui.text_width(label, 0.4) + 8.0);
+                let widths = widths.collect::<Vec<_>>();
+                let focused_tag = match self.focused_element {
+                    StatusFocus::FocusTags(j) if Some(i) == self.focused_index => Some(j),
+                    _ => None,
+                };
+                // only so many tags fit on the row, so skip enough of the
+                // first ones that the focused tag is shown
+                let mut start = 0;
+                if let Some(j) = focused_tag {
+                    let row_width = |start: usize| widths[start..=j].iter().sum::<f32>();
+                    while start < j && row_width(start) + 4.0 * (j - start) as f32 > TAG_ROW_WIDTH {
+                        start += 1;
+                    }
+                }
+                let mut x = 20.0;
+                for j in start..labels.len() {
+                    let width = widths[j];
+                    if x + width > 20.0 + TAG_ROW_WIDTH {
+                        // let the user know there are more to move to
+                        ui.draw_text(ctx, x, scroll + 3.0, 0.4, colors.tag, "...");
+                        break;
+                    }
+                    let color = if focused_tag == Some(j) {
+                        ctx.rect_solid(x, scroll + 1.0, width, 16.0, colors.tag);
+                        colors.background
+                    } else {
+                        draw_border(ctx, x, scroll + 1.0, width, 16.0, colors.tag);
+                        colors.tag
+                    };
+                    ui.draw_text(ctx, x + 4.0, scroll + 3.0, 0.4, color, &labels[j]);
+                    x += width + 4.0;
+                }
+                scroll += TAG_HEIGHT;
+            }
+            scroll += TimelineStatus::separator_height();
         }
     }
 
-    fn update(&mut self, hid: &Hid) {
-        let buttons = hid.keys_held();
-        if buttons.contains(KeyPad::KEY_DUP) {
-            self.scroll -= 4.0;
-            if self.scroll < 0.0 {
-                self.scroll = 0.0;
+    fn update(&mut self, input: &HidEvents) {
+        // dismiss the topmost announcement, or else act on the selected status
+        if input.pressed.contains(KeyPad::KEY_A) {
+            if !self.announcements.is_empty() {
+                let announcement = self.announcements.remove(0);
+                self.selected_reaction = 0;
+                // ignore send errors, the logic thread may have already stopped
+                _ = self
+                    .events
+                    .lock()
+                    .unwrap()
+                    .send(TimelineEvent::DismissAnnouncement(announcement.id));
+            } else if let Some(status) = self.focused_index.and_then(|i| self.statuses.get(i)) {
+                let event = match self.focused_element {
+                    StatusFocus::FocusContent => TimelineEvent::OpenActions(status.id.clone()),
+                    StatusFocus::FocusTags(i) => {
+                        TimelineEvent::OpenHashtag(status.tags[i].name.clone())
+                    }
+                };
+                // ignore send errors, the logic thread may have already stopped
+                _ = self.events.lock().unwrap().send(event);
+            }
+        }
+
+        if input.pressed.contains(KeyPad::KEY_SELECT) {
+            _ = self
+                .events
+                .lock()
+                .unwrap()
+                .send(TimelineEvent::OpenDirectMessages);
+        }
+
+        if input.pressed.contains(KeyPad::KEY_Y) {
+            _ = self.events.lock().unwrap().send(TimelineEvent::EditProfile);
+        }
+
+        if input.pressed.contains(KeyPad::KEY_X) {
+            _ = self
+                .events
+                .lock()
+                .unwrap()
+                .send(TimelineEvent::OpenSettings);
+        }
+
+        if input.pressed.contains(KeyPad::KEY_R) {
+            _ = self
+                .events
+                .lock()
+                .unwrap()
+                .send(TimelineEvent::OpenTrending);
+        }
+
+        if input.pressed.contains(KeyPad::KEY_L) {
+            _ = self
+                .events
+                .lock()
+                .unwrap()
+                .send(TimelineEvent::OpenScheduled);
+        }
+
+        // choose and toggle reactions on the topmost announcement
+        if let Some(announcement) = self.announcements.first() {
+            let reaction_count = announcement.reactions.len();
+            let buttons = input.pressed;
+            if buttons.contains(KeyPad::KEY_DLEFT) {
+                self.selected_reaction = self.selected_reaction.saturating_sub(1);
+            } else if buttons.contains(KeyPad::KEY_DRIGHT) {
+                if self.selected_reaction + 1 < reaction_count {
+                    self.selected_reaction += 1;
+                }
+            } else if buttons.contains(KeyPad::KEY_B) {
+                self.toggle_reaction();
             }
-        } else if buttons.contains(KeyPad::KEY_DDOWN) {
-            self.scroll += 4.0;
+        } else if input.pressed.contains(KeyPad::KEY_DLEFT) {
+            self.move_focus(false);
+        } else if input.pressed.contains(KeyPad::KEY_DRIGHT) {
+            self.move_focus(true);
         }
+
+        self.update_touch(input);
+
+        for update in self.updates.lock().unwrap().try_iter() {
+            match update {
+                TimelineUpdate::CirclePadDeadzone(deadzone) => {
+                    self.scroll.circle_pad_deadzone = deadzone;
+                }
+                TimelineUpdate::TimezoneOffset(minutes) => {
+                    self.timezone = utc_offset(minutes);
+                    self.refresh_timestamps();
+                }
+                TimelineUpdate::UndoReaction {
+                    announcement_id,
+                    name,
+                } => {
+                    let reaction = self
+                        .announcements
+                        .iter_mut()
+                        .filter(|announcement| announcement.id == announcement_id)
+                        .flat_map(|announcement| &mut announcement.reactions)
+                        .find(|reaction| reaction.name == name);
+                    // the announcement may have been dismissed since
+                    if let Some(reaction) = reaction {
+                        reaction.toggle();
+                    }
+                }
+            }
+        }
+
+        self.scroll.update_dpad(input);
+        self.scroll.update_analog(input);
+        self.scroll.update_touch(input);
+
+        // keep the timestamps current, without formatting them every frame
+        if frame_count() % TIMESTAMP_REFRESH_FRAMES == 0 {
+            self.refresh_timestamps();
+        }
+    }
+
+    fn on_resume(&mut self, _tx: &UiMsgSender) {
+        // the timestamps may not have been refreshed while another screen was open
+        self.refresh_timestamps();
     }
 }
@@ -0,0 +1,160 @@
+use std::{
+    error::Error,
+    str::FromStr,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Mutex,
+    },
+};
+
+use ctru::prelude::KeyPad;
+
+use crate::{
+    html::{self, HtmlSpan},
+    types::Status,
+    ui::{
+        citro2d::{RenderTarget, Scene2d},
+        scroll::ScrollState,
+        text::TextLines,
+        word_wrap, word_wrap_html, HidEvents, Screen, Ui, UiMsgSender,
+    },
+};
+
+/// Requests sent from the trending statuses screen to the logic thread.
+pub enum TrendingStatusesEvent {
+    /// The user wants to leave the screen.
+    Back,
+    /// The user wants more statuses, skipping the given number already shown.
+    LoadMore(usize),
+}
+
+/// Wrap a status to be shown in the list, along with who posted it and where.
+fn wrap_status(
+    tx: &UiMsgSender,
+    status: Status,
+) -> Result<TextLines, Box<dyn Error + Send + Sync>> {
+    // trending statuses come from all over, so say where each is from
+    let domain = url::Url::from_str(&status.uri)
+        .ok()
+        .and_then(|uri| uri.domain().map(String::from))
+        .unwrap_or_default();
+    let mut spans = vec![HtmlSpan::plain(format!(
+        "from {} on {}\n",
+        status.account.display_name, domain
+    ))];
+    spans.extend(html::parse(&status.content)?);
+    spans.push(HtmlSpan::plain("\n"));
+    Ok(word_wrap_html(tx, spans, 360.0, 0.5))
+}
+
+/// More trending statuses, sent from the logic thread to be added to the end.
+/// The default has no statuses, for when loading more failed.
+#[derive(Default)]
+pub struct TrendingStatusesUpdate(Vec<TextLines>);
+
+impl TrendingStatusesUpdate {
+    pub fn new(
+        tx: &UiMsgSender,
+        statuses: Vec<Status>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let statuses = statuses
+            .into_iter()
+            .map(|status| wrap_status(tx, status))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self(statuses))
+    }
+}
+
+/// Shows the statuses that are popular across the instance right now.
+pub struct TrendingStatusesScreen {
+    statuses: Vec<TextLines>,
+    scroll: ScrollState,
+    hint: TextLines,
+    /// True while waiting for more statuses, so only one request is made.
+    loading: bool,
+    events: Mutex<Sender<TrendingStatusesEvent>>,
+    updates: Mutex<Receiver<TrendingStatusesUpdate>>,
+}
+
+impl TrendingStatusesScreen {
+    pub fn new(
+        tx: &UiMsgSender,
+        first: TrendingStatusesUpdate,
+    ) -> (
+        Self,
+        Receiver<TrendingStatusesEvent>,
+        Sender<TrendingStatusesUpdate>,
+    ) {
+        let hint = word_wrap(tx, "A: load more, B: back".into(), 360.0, 0.5);
+        let (events, events_rx) = std::sync::mpsc::channel();
+        let (updates_tx, updates) = std::sync::mpsc::channel();
+        (
+            Self {
+                statuses: first.0,
+                scroll: ScrollState::default(),
+                hint,
+                loading: false,
+                events: Mutex::new(events),
+                updates: Mutex::new(updates),
+            },
+            events_rx,
+            updates_tx,
+        )
+    }
+
+    fn send(&self, event: TrendingStatusesEvent) {
+        // ignore send errors, the logic thread may have already stopped
+        _ = self.events.lock().unwrap().send(event);
+    }
+}
+
+impl Screen for TrendingStatusesScreen {
+    fn title(&self) -> &str {
+        "Trending"
+    }
+
+    fn draw<'gfx: 'screen, 'screen>(
+        &self,
+        ui: &Ui<'gfx, 'screen>,
+        target: &RenderTarget<'gfx, 'screen>,
+        ctx: &Scene2d,
+    ) {
+        let colors = ui.theme();
+        target.clear_gradient(ctx, colors.background, colors.background_bottom);
+
+        let mut scroll = 20.0 - self.scroll.offset();
+
+        for status in &self.statuses {
+            ui.draw_lines(ctx, 20.0, scroll, colors.foreground, status);
+            scroll += status.height();
+        }
+
+        // keep the controls visible over the list
+        ctx.rect_solid(0.0, 208.0, 400.0, 32.0, colors.background_bottom);
+        let hint_color = if self.loading {
+            colors.muted
+        } else {
+            colors.foreground
+        };
+        ui.draw_lines(ctx, 20.0, 216.0, hint_color, &self.hint);
+    }
+
+    fn update(&mut self, input: &HidEvents) {
+        for update in self.updates.lock().unwrap().try_iter() {
+            self.statuses.extend(update.0);
+            self.loading = false;
+        }
+
+        let buttons = input.pressed;
+        if buttons.contains(KeyPad::KEY_B) {
+            self.send(TrendingStatusesEvent::Back);
+        } else if buttons.contains(KeyPad::KEY_A) && !self.loading {
+            self.loading = true;
+            self.send(TrendingStatusesEvent::LoadMore(self.statuses.len()));
+        }
+
+        self.scroll.update_dpad(input);
+        self.scroll.update_analog(input);
+        self.scroll.update_touch(input);
+    }
+}
@@ -0,0 +1,70 @@
+use ctru::prelude::KeyPad;
+
+use crate::{
+    config::DEFAULT_CIRCLE_PAD_DEADZONE,
+    ui::{input::touch_delta, HidEvents},
+};
+
+/// How far the D-pad scrolls each frame.
+const DPAD_SPEED: f32 = 4.0;
+
+/// How far the circle pad scrolls each frame when pushed all the way.
+const CIRCLE_PAD_SPEED: f32 = 6.0;
+
+/// How far the circle pad reports it can be pushed.
+const CIRCLE_PAD_MAX: f32 = 156.0;
+
+/// The scroll position of a screen, moved with the D-pad or circle pad.
+pub struct ScrollState {
+    offset: f32,
+    /// How far the circle pad must be pushed before it scrolls.
+    pub circle_pad_deadzone: i16,
+}
+
+impl Default for ScrollState {
+    fn default() -> Self {
+        Self {
+            offset: 0.0,
+            circle_pad_deadzone: DEFAULT_CIRCLE_PAD_DEADZONE,
+        }
+    }
+}
+
+impl ScrollState {
+    pub fn offset(&self) -> f32 {
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: f32) {
+        self.offset = offset.max(0.0);
+    }
+
+    /// Scroll while up or down is held on the D-pad.
+    pub fn update_dpad(&mut self, input: &HidEvents) {
+        let buttons = input.held;
+        if buttons.contains(KeyPad::KEY_DUP) {
+            self.set_offset(self.offset - DPAD_SPEED);
+        } else if buttons.contains(KeyPad::KEY_DDOWN) {
+            self.set_offset(self.offset + DPAD_SPEED);
+        }
+    }
+
+    /// Scroll with the circle pad, faster the further it is pushed.
+    pub fn update_analog(&mut self, input: &HidEvents) {
+        let (_, y) = input.circle_pad;
+        if y.abs() > self.circle_pad_deadzone {
+            // pushing up moves the view up, like dragging a page on a tablet
+            let speed = -f32::from(y) / CIRCLE_PAD_MAX * CIRCLE_PAD_SPEED;
+            self.set_offset(self.offset + speed);
+        }
+    }
+
+    /// Scroll by dragging on the touch screen. Dragging up shows what is
+    /// further down, as if the page were being pushed along.
+    pub fn update_touch(&mut self, input: &HidEvents) {
+        if let (Some(prev), Some(curr)) = (input.prev_touch, input.current_touch()) {
+            let (_, dy) = touch_delta(prev, curr);
+            self.set_offset(self.offset - dy as f32);
+        }
+    }
+}
@@ -1,26 +1,132 @@
 use std::{error::Error, mem::MaybeUninit, num::NonZeroUsize, pin::Pin, rc::Rc};
 
 use lru::LruCache;
+use serde::{Deserialize, Serialize};
 use unicode_linebreak::{linebreaks, BreakOpportunity};
 
-use super::citro2d::{AnyTexture, Citro2d, Image, Scene2d, TexDim};
+use crate::{
+    config::ThemeColors,
+    html::{HtmlSpan, SpanKind, EMOJI_PLACEHOLDER},
+};
+
+use super::citro2d::{AnyTexture, C2dMemError, Citro2d, Image, Scene2d, TexDim};
+
+/// Path to an optional font on the SD card, for characters the system font lacks.
+pub static EXTRA_FONT_PATH: &str = "/toot-3d-font.bcfnt";
+
+/// How a run of text is drawn. The system font has no bold or italic
+/// variants, so they are simulated.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+enum TextStyle {
+    #[default]
+    Normal,
+    Bold,
+    Italic,
+    /// Drawn on a background, since there is no monospace font either.
+    Code,
+}
+
+/// How far italic text leans: tan(15 degrees).
+const ITALIC_SLANT: f32 = 0.268;
+
+/// A glyph split into its top and bottom halves, which are drawn apart to
+/// make it lean.
+struct SlantedGlyph<'gfx> {
+    top: Image<'gfx>,
+    bottom: Image<'gfx>,
+    /// The height of the top half, in pixels of the glyph's font.
+    top_height: f32,
+}
+
+impl<'gfx> SlantedGlyph<'gfx> {
+    fn new(image: &Image<'gfx>) -> Self {
+        let (width, height) = image.size();
+        let top_height = height / 2;
+        Self {
+            top: image.region(0, 0, width, top_height),
+            bottom: image.region(0, top_height, width, height - top_height),
+            top_height: f32::from(top_height),
+        }
+    }
+}
 
 struct Glyph<'gfx> {
-    /// The glyph image.
-    image: Image<'gfx>,
+    /// The glyph image, or None if no font has this glyph.
+    image: Option<Image<'gfx>>,
+    /// The glyph image for italic text. It is only split from the glyph
+    /// image once the glyph is drawn in italics.
+    slanted: Option<SlantedGlyph<'gfx>>,
     /// The width of the glyph.
     x_advance: f32,
+    /// The scale to draw the glyph at, relative to the system font.
+    scale: f32,
 }
 
-pub struct TextRenderer<'gfx> {
-    /// pre-calculated sheets
+struct Font<'gfx> {
+    /// The font data. Null refers to the system font.
+    font: *mut ctru_sys::CFNT_s,
+    /// The sheets of glyphs.
     sheets: Vec<Pin<Rc<AnyTexture<'gfx>>>>,
+    /// The height of a glyph.
+    height: u8,
+}
+
+impl<'gfx> Font<'gfx> {
+    fn new(c2d: &'gfx Citro2d, font: *mut ctru_sys::CFNT_s, info: &ctru_sys::TGLP_s) -> Self {
+        let mut sheets = vec![];
+        let sheet_size = info.sheetSize as usize;
+        for i in 0..info.nSheets {
+            let mut tex = unsafe {
+                let data = info.sheetData.add(usize::from(i) * sheet_size);
+                AnyTexture::raw(
+                    c2d,
+                    &mut *std::ptr::slice_from_raw_parts_mut(data, sheet_size),
+                    TexDim::assume_valid(info.sheetWidth),
+                    TexDim::assume_valid(info.sheetHeight),
+                    u32::from(info.sheetFmt),
+                )
+            };
+            tex.set_filter(true);
+            sheets.push(Rc::pin(tex));
+        }
+        Self {
+            font,
+            sheets,
+            height: info.cellHeight,
+        }
+    }
+
+    /// Find the glyph index for a character, if this font has it.
+    fn glyph_index(&self, c: char) -> Option<i32> {
+        unsafe {
+            let index = ctru_sys::fontGlyphIndexFromCodePoint(self.font, u32::from(c));
+            // the font gives us the replacement character if it doesn't have a glyph
+            let font = if self.font.is_null() {
+                get_shared_font().ok()?
+            } else {
+                &*self.font
+            };
+            if index == i32::from(font.finf.alterCharIndex) {
+                None
+            } else {
+                Some(index)
+            }
+        }
+    }
+}
+
+pub struct TextRenderer<'gfx> {
+    /// Fonts to search for glyphs, in order. The system font is last.
+    fonts: Vec<Font<'gfx>>,
     /// pre-calculated height
     height: u8,
     /// pre-calculated scale
     scale: f32,
     /// Cache of recently used glyphs.
     cache: LruCache<char, Glyph<'gfx>>,
+    /// The widths of the ASCII glyphs, so that measuring English text
+    /// doesn't need the cache.
+    ascii_advances: [f32; 128],
 }
 
 fn get_shared_font() -> ctru::Result<&'static ctru_sys::CFNT_s> {
@@ -38,96 +144,249 @@ fn get_shared_font() -> ctru::Result<&'static ctru_sys::CFNT_s> {
 }
 
 impl<'gfx> TextRenderer<'gfx> {
-    pub fn new(c2d: &'gfx Citro2d) -> Result<Self, Box<dyn Error>> {
+    pub fn new(c2d: &'gfx Citro2d) -> Result<Self, Box<dyn Error + Send + Sync>> {
         let font = get_shared_font()?;
 
         let info = unsafe { &*font.finf.tglp };
         let height = info.cellHeight;
         // based on c2d code
         let scale = 30.0 / f32::from(height);
-        let mut sheets = vec![];
-        let sheet_size = info.sheetSize as usize;
-        for i in 0..info.nSheets {
-            let mut tex = unsafe {
-                let data = info.sheetData.add(usize::from(i) * sheet_size);
-                AnyTexture::raw(
-                    c2d,
-                    &mut *std::ptr::slice_from_raw_parts_mut(data, sheet_size),
-                    TexDim::assume_valid(info.sheetWidth),
-                    TexDim::assume_valid(info.sheetHeight),
-                    u32::from(info.sheetFmt),
-                )
-            };
-            tex.set_filter(true);
-            sheets.push(Rc::pin(tex));
-        }
+        let fonts = vec![Font::new(c2d, std::ptr::null_mut(), info)];
         // 128 is more than enough for all of ASCII, so it's probably a good
         // cache size
         let cache = LruCache::new(NonZeroUsize::new(2).unwrap());
 
-        Ok(Self {
-            sheets,
+        let mut result = Self {
+            fonts,
             height,
             scale,
             cache,
+            ascii_advances: [0.0; 128],
+        };
+        result.ascii_advances = result.calc_ascii_advances();
+        Ok(result)
+    }
+
+    /// Load a BCFNT font to use before the system font.
+    pub fn add_font_file(
+        &mut self,
+        c2d: &'gfx Citro2d,
+        path: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let data = std::fs::read(path)?;
+        if !data.starts_with(b"CFNT") {
+            return Err("not a BCFNT font".into());
+        }
+        let font = unsafe {
+            // the GPU reads the sheets, so the font must be in linear memory.
+            // it is never freed, as the sheets are used for the rest of the
+            // program.
+            let ptr = ctru_sys::linearAlloc(data.len()) as *mut u8;
+            if ptr.is_null() {
+                return Err(Box::new(C2dMemError));
+            }
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+            let font = ptr as *mut ctru_sys::CFNT_s;
+            ctru_sys::fontFixPointers(font);
+            font
+        };
+        let info = unsafe { &*(*font).finf.tglp };
+        let index = self.fonts.len() - 1;
+        self.fonts.insert(index, Font::new(c2d, font, info));
+        // glyphs from the system font may now be wrong
+        self.cache.clear();
+        self.ascii_advances = self.calc_ascii_advances();
+        Ok(())
+    }
+
+    /// Find the font that has a glyph for a character, and its index there.
+    fn find_glyph(&self, c: char) -> Option<(&Font<'gfx>, i32)> {
+        self.fonts
+            .iter()
+            .find_map(|font| font.glyph_index(c).map(|index| (font, index)))
+    }
+
+    /// Work out the widths of the ASCII glyphs, like get_glyph does.
+    fn calc_ascii_advances(&self) -> [f32; 128] {
+        std::array::from_fn(|i| match self.find_glyph(char::from(i as u8)) {
+            Some((font, index)) => {
+                let pos = unsafe {
+                    let mut pos = MaybeUninit::uninit();
+                    ctru_sys::fontCalcGlyphPos(pos.as_mut_ptr(), font.font, index, 0, 1.0, 1.0);
+                    pos.assume_init()
+                };
+                pos.xAdvance * f32::from(self.height) / f32::from(font.height)
+            }
+            None => f32::from(self.height) / 2.0,
         })
     }
 
-    fn get_glyph(&mut self, c: char) -> &'_ Glyph<'gfx> {
+    /// The width of a glyph, without loading it for ASCII characters.
+    fn x_advance(&mut self, c: char) -> f32 {
+        match self.ascii_advances.get(c as usize) {
+            Some(&x_advance) => x_advance,
+            None => self.get_glyph(c).x_advance,
+        }
+    }
+
+    fn get_glyph(&mut self, c: char) -> &'_ mut Glyph<'gfx> {
         // check the cache first
-        if !self.cache.contains(&c) {
-            // not in the cache, put it in
-            let pos = unsafe {
-                let index =
-                    ctru_sys::fontGlyphIndexFromCodePoint(std::ptr::null_mut(), u32::from(c));
-                let mut pos = MaybeUninit::uninit();
-                ctru_sys::fontCalcGlyphPos(
-                    pos.as_mut_ptr(),
-                    std::ptr::null_mut(),
-                    index,
-                    0,
-                    1.0,
-                    1.0,
-                );
-                pos.assume_init()
-            };
-            let texture = self.sheets[pos.sheetIndex as usize].clone();
-            let image = Image::new_texcoord(
-                texture,
-                pos.width as _,
-                self.height as _,
-                pos.texcoord.left,
-                pos.texcoord.top,
-                pos.texcoord.right,
-                pos.texcoord.bottom,
-            );
+        if c == EMOJI_PLACEHOLDER && !self.cache.contains(&c) {
+            // emojis are drawn separately, in a square one line high
             let glyph = Glyph {
-                image,
-                x_advance: pos.xAdvance,
+                image: None,
+                slanted: None,
+                x_advance: f32::from(self.height) / self.scale,
+                scale: 1.0,
+            };
+            self.cache.put(c, glyph);
+        } else if !self.cache.contains(&c) {
+            // not in the cache, put it in
+            let glyph = match self.find_glyph(c) {
+                Some((font, index)) => {
+                    let pos = unsafe {
+                        let mut pos = MaybeUninit::uninit();
+                        ctru_sys::fontCalcGlyphPos(pos.as_mut_ptr(), font.font, index, 0, 1.0, 1.0);
+                        pos.assume_init()
+                    };
+                    let image = Image::new_texcoord(
+                        font.sheets[pos.sheetIndex as usize].clone(),
+                        pos.width as _,
+                        font.height as _,
+                        pos.texcoord.left,
+                        pos.texcoord.top,
+                        pos.texcoord.right,
+                        pos.texcoord.bottom,
+                    );
+                    let scale = f32::from(self.height) / f32::from(font.height);
+                    Glyph {
+                        image: Some(image),
+                        slanted: None,
+                        x_advance: pos.xAdvance * scale,
+                        scale,
+                    }
+                }
+                // no font has it, so we'll draw a box instead
+                None => Glyph {
+                    image: None,
+                    slanted: None,
+                    x_advance: f32::from(self.height) / 2.0,
+                    scale: 1.0,
+                },
             };
             self.cache.put(c, glyph);
         }
-        self.cache.get(&c).unwrap()
+        self.cache.get_mut(&c).unwrap()
+    }
+
+    /// Print a line of text. Returns the x position after the end of the line.
+    pub fn print(
+        &mut self,
+        ctx: &Scene2d,
+        line: &str,
+        x: f32,
+        y: f32,
+        scale: f32,
+        color: u32,
+    ) -> f32 {
+        self.print_styled(ctx, line, x, y, scale, color, TextStyle::Normal)
+    }
+
+    /// Print a line of bold text, by drawing each glyph twice a pixel apart.
+    pub fn print_bold(
+        &mut self,
+        ctx: &Scene2d,
+        line: &str,
+        x: f32,
+        y: f32,
+        scale: f32,
+        color: u32,
+    ) -> f32 {
+        self.print_styled(ctx, line, x, y, scale, color, TextStyle::Bold)
     }
 
-    pub fn print(&mut self, ctx: &Scene2d, line: &str, mut x: f32, y: f32, scale: f32, color: u32) {
+    /// Print a line of italic text, by shifting the top half of each glyph to
+    /// the right.
+    pub fn print_italic(
+        &mut self,
+        ctx: &Scene2d,
+        line: &str,
+        x: f32,
+        y: f32,
+        scale: f32,
+        color: u32,
+    ) -> f32 {
+        self.print_styled(ctx, line, x, y, scale, color, TextStyle::Italic)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn print_styled(
+        &mut self,
+        ctx: &Scene2d,
+        line: &str,
+        mut x: f32,
+        y: f32,
+        scale: f32,
+        color: u32,
+        style: TextStyle,
+    ) -> f32 {
         // avoid printing line if it won't be visible
         if y + f32::from(self.height) < 0.0 || y > 240.0 {
-            return;
+            return x;
         }
+        let height = self.line_height(scale);
+        let slant = height / 2.0 * ITALIC_SLANT;
         // otherwise, print it
         for c in line.chars() {
+            let width = self.x_advance(c) * self.scale * scale;
             let glyph = self.get_glyph(c);
-            glyph.image.draw_tint(ctx, x, y, scale, scale, color);
-            x += glyph.x_advance * self.scale * scale;
+            let glyph_scale = scale * glyph.scale;
+            match &glyph.image {
+                Some(image) if style == TextStyle::Italic => {
+                    // most glyphs are never drawn in italics, so they are
+                    // only split when they are
+                    let slanted = glyph
+                        .slanted
+                        .get_or_insert_with(|| SlantedGlyph::new(image));
+                    let bottom_y = y + slanted.top_height * glyph_scale;
+                    slanted
+                        .top
+                        .draw_tint(ctx, x + slant, y, glyph_scale, glyph_scale, color);
+                    slanted
+                        .bottom
+                        .draw_tint(ctx, x, bottom_y, glyph_scale, glyph_scale, color);
+                }
+                Some(image) => {
+                    if style == TextStyle::Bold {
+                        image.draw_tint(ctx, x + 1.0, y, glyph_scale, glyph_scale, color);
+                    }
+                    image.draw_tint(ctx, x, y, glyph_scale, glyph_scale, color);
+                }
+                // leave room for the emoji to be drawn over
+                None if c == EMOJI_PLACEHOLDER => {}
+                None => {
+                    let (left, top) = (x + 1.0, y + 2.0);
+                    let (w, h) = (width - 2.0, height - 4.0);
+                    ctx.rect_solid(left, top, w, 1.0, color);
+                    ctx.rect_solid(left, top + h - 1.0, w, 1.0, color);
+                    ctx.rect_solid(left, top, 1.0, h, color);
+                    ctx.rect_solid(left + w - 1.0, top, 1.0, h, color);
+                }
+            }
+            x += width;
         }
+        x
     }
 
-    fn text_width(&mut self, word: &str, scale: f32) -> f32 {
+    /// The height of a line of text at the given scale.
+    pub fn line_height(&self, scale: f32) -> f32 {
+        (self.height as f32) * scale
+    }
+
+    pub fn text_width(&mut self, word: &str, scale: f32) -> f32 {
         let mut result = 0.0;
         for c in word.chars() {
-            let glyph = self.get_glyph(c);
-            result += glyph.x_advance * self.scale * scale;
+            result += self.x_advance(c) * self.scale * scale;
         }
         result
     }
@@ -165,9 +424,89 @@ impl<'gfx> TextRenderer<'gfx> {
     }
 }
 
+/// Which of the theme's colors a run of text is drawn in. The color itself
+/// isn't saved, so that wrapped text follows changes to the theme.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+enum RunColor {
+    Code,
+    Strikethrough,
+    Mention,
+    Link,
+}
+
+impl RunColor {
+    fn resolve(self, colors: &ThemeColors) -> u32 {
+        match self {
+            RunColor::Code => colors.code,
+            RunColor::Strikethrough => colors.strikethrough,
+            RunColor::Mention => colors.mention,
+            RunColor::Link => colors.link,
+        }
+    }
+}
+
+/// Part of a line drawn in a single color.
+#[derive(Clone, Deserialize, Serialize)]
+struct TextRun {
+    text: String,
+    /// The color of the text, or None to use the color given when rendering.
+    color: Option<RunColor>,
+    /// The shortcode of the custom emoji this run stands in for, if any.
+    emoji: Option<String>,
+    /// The mention or hashtag this run is part of, if any.
+    target: Option<SpanKind>,
+    style: TextStyle,
+}
+
+/// A piece of text to be wrapped, and how to draw it.
+#[derive(Clone, Copy, Default)]
+struct UnwrappedRun<'a> {
+    text: &'a str,
+    color: Option<RunColor>,
+    emoji: Option<&'a str>,
+    target: Option<&'a SpanKind>,
+    style: TextStyle,
+}
+
+/// A single wrapped line.
+#[derive(Clone, Deserialize, Serialize)]
+struct TextLine {
+    text: String,
+    runs: Vec<TextRun>,
+}
+
+/// How to draw a span of HTML, apart from its color.
+fn span_style(kind: &SpanKind) -> TextStyle {
+    match kind {
+        SpanKind::Bold => TextStyle::Bold,
+        SpanKind::Italic => TextStyle::Italic,
+        SpanKind::Code => TextStyle::Code,
+        _ => TextStyle::Normal,
+    }
+}
+
+/// The color to draw a span of HTML in, or None for the default color.
+fn span_color(kind: &SpanKind) -> Option<RunColor> {
+    match kind {
+        SpanKind::Normal
+        | SpanKind::Bold
+        | SpanKind::Italic
+        | SpanKind::Invisible
+        | SpanKind::Emoji(_) => None,
+        SpanKind::Code => Some(RunColor::Code),
+        SpanKind::Strikethrough => Some(RunColor::Strikethrough),
+        SpanKind::Mention(_) | SpanKind::Hashtag(_) => Some(RunColor::Mention),
+        SpanKind::Link(_) => Some(RunColor::Link),
+    }
+}
+
+/// Wrapped text, ready to draw. It can be saved and loaded again to skip
+/// wrapping, as long as the font scale is the same.
+#[derive(Clone, Deserialize, Serialize)]
 pub struct TextLines {
-    lines: Vec<String>,
+    lines: Vec<TextLine>,
     height: f32,
+    line_height: f32,
     scale: f32,
 }
 
@@ -178,30 +517,187 @@ impl TextLines {
         width: f32,
         scale: f32,
     ) -> Self {
-        let lines = renderer.create_lines(text, width, scale);
-        let height = (lines.len() as f32) * (renderer.height as f32) * scale;
+        let run = UnwrappedRun {
+            text,
+            ..Default::default()
+        };
+        Self::from_runs(&[run], renderer, width, scale)
+    }
+
+    /// Wrap styled text, coloring each span according to its kind.
+    pub fn from_html_spans<'gfx>(
+        spans: &[HtmlSpan],
+        renderer: &mut TextRenderer<'gfx>,
+        width: f32,
+        scale: f32,
+    ) -> Self {
+        let runs = spans
+            .iter()
+            .filter(|span| span.kind != SpanKind::Invisible)
+            .map(|span| {
+                let emoji = match &span.kind {
+                    SpanKind::Emoji(shortcode) => Some(shortcode.as_str()),
+                    _ => None,
+                };
+                let target = match &span.kind {
+                    SpanKind::Mention(_) | SpanKind::Hashtag(_) => Some(&span.kind),
+                    _ => None,
+                };
+                UnwrappedRun {
+                    text: &span.text,
+                    color: span_color(&span.kind),
+                    emoji,
+                    target,
+                    style: span_style(&span.kind),
+                }
+            })
+            .collect::<Vec<_>>();
+        Self::from_runs(&runs, renderer, width, scale)
+    }
+
+    fn from_runs<'gfx>(
+        runs: &[UnwrappedRun],
+        renderer: &mut TextRenderer<'gfx>,
+        width: f32,
+        scale: f32,
+    ) -> Self {
+        let text = runs.iter().map(|run| run.text).collect::<String>();
+        // wrapping only removes newlines, so the styles of the remaining
+        // characters can be matched up with the wrapped lines in order
+        let mut styles = runs
+            .iter()
+            .flat_map(|run| run.text.chars().filter(|&c| c != '\n').map(move |_| run));
+        let lines = renderer
+            .create_lines(&text, width, scale)
+            .into_iter()
+            .map(|line| {
+                let mut runs: Vec<TextRun> = vec![];
+                for c in line.chars() {
+                    let source = styles.next().copied().unwrap_or_default();
+                    match runs.last_mut() {
+                        // each emoji gets a run of its own
+                        Some(run)
+                            if run.color == source.color
+                                && run.style == source.style
+                                && run.emoji.is_none()
+                                && source.emoji.is_none()
+                                && run.target.as_ref() == source.target =>
+                        {
+                            run.text.push(c)
+                        }
+                        _ => runs.push(TextRun {
+                            text: c.into(),
+                            color: source.color,
+                            emoji: source.emoji.map(String::from),
+                            target: source.target.cloned(),
+                            style: source.style,
+                        }),
+                    }
+                }
+                TextLine { text: line, runs }
+            })
+            .collect::<Vec<_>>();
+        let line_height = renderer.line_height(scale);
+        let height = (lines.len() as f32) * line_height;
         Self {
             lines,
             height,
+            line_height,
             scale,
         }
     }
 
-    pub fn render<'gfx>(
+    /// Draw the text. Plain text is drawn in the given color, and the rest in
+    /// the theme's colors. draw_emoji is called with the shortcode, position,
+    /// and size of each custom emoji. If a viewport is given as (top, bottom),
+    /// lines entirely outside of it are skipped.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render<'gfx, F>(
         &self,
         renderer: &mut TextRenderer<'gfx>,
         ctx: &Scene2d,
         x: f32,
-        mut y: f32,
+        y: f32,
+        colors: &ThemeColors,
         color: u32,
-    ) {
-        for line in &self.lines {
-            renderer.print(&ctx, &line, x, y, self.scale, color);
-            y += (renderer.height as f32) * self.scale;
+        viewport: Option<(f32, f32)>,
+        mut draw_emoji: F,
+    ) where
+        F: FnMut(&str, f32, f32, f32),
+    {
+        let size = renderer.line_height(self.scale);
+        let (first, bottom) = match viewport {
+            Some((top, bottom)) => (self.first_visible_line(top, y), bottom),
+            None => (0, f32::INFINITY),
+        };
+        let mut y = y + (first as f32) * size;
+        for line in &self.lines[first..] {
+            // the remaining lines are below the viewport
+            if y >= bottom {
+                break;
+            }
+            let mut line_x = x;
+            for run in &line.runs {
+                if let Some(shortcode) = &run.emoji {
+                    draw_emoji(shortcode, line_x, y, size);
+                }
+                let color = run.color.map_or(color, |color| color.resolve(colors));
+                line_x = match run.style {
+                    TextStyle::Normal => {
+                        renderer.print(&ctx, &run.text, line_x, y, self.scale, color)
+                    }
+                    TextStyle::Bold => {
+                        renderer.print_bold(&ctx, &run.text, line_x, y, self.scale, color)
+                    }
+                    TextStyle::Italic => {
+                        renderer.print_italic(&ctx, &run.text, line_x, y, self.scale, color)
+                    }
+                    TextStyle::Code => {
+                        let width = renderer.text_width(&run.text, self.scale);
+                        ctx.rect_solid(line_x, y, width, size, colors.code_background);
+                        renderer.print(&ctx, &run.text, line_x, y, self.scale, color)
+                    }
+                };
+            }
+            y += size;
         }
     }
 
     pub fn height(&self) -> f32 {
         self.height
     }
+
+    /// The index of the first line that is at least partly below
+    /// viewport_top, if the first line is drawn at render_y. This is the
+    /// number of lines if none are.
+    pub fn first_visible_line(&self, viewport_top: f32, render_y: f32) -> usize {
+        if self.line_height <= 0.0 {
+            return 0;
+        }
+        let hidden = ((viewport_top - render_y) / self.line_height).floor();
+        (hidden.max(0.0) as usize).min(self.lines.len())
+    }
+
+    /// The top and bottom of each line, and its text, if the first line is
+    /// drawn at start_y.
+    pub fn line_y_ranges(&self, start_y: f32) -> Vec<(f32, f32, &str)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let top = start_y + (i as f32) * self.line_height;
+                let bottom = start_y + ((i + 1) as f32) * self.line_height;
+                (top, bottom, line.text.as_str())
+            })
+            .collect()
+    }
+
+    /// The first mention or hashtag on the given line, if any.
+    pub fn line_target(&self, index: usize) -> Option<&SpanKind> {
+        self.lines
+            .get(index)?
+            .runs
+            .iter()
+            .find_map(|run| run.target.as_ref())
+    }
 }
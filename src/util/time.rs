@@ -0,0 +1,28 @@
+use chrono::{DateTime, Datelike, FixedOffset, Utc};
+
+use super::tz::now_utc;
+
+/// Describe how long ago something happened, briefly enough to fit beside a
+/// name: "just now", "5m", "2h", "3d", "Jan 15", or "2023-01-15". Dates are
+/// given in the time zone of the offset.
+pub fn readable_relative_time(dt: &DateTime<Utc>, offset: FixedOffset) -> String {
+    let now = now_utc(offset);
+    let elapsed = now.signed_duration_since(*dt);
+    // the clock may be slightly behind the server
+    if elapsed.num_minutes() < 1 {
+        "just now".into()
+    } else if elapsed.num_hours() < 1 {
+        format!("{}m", elapsed.num_minutes())
+    } else if elapsed.num_days() < 1 {
+        format!("{}h", elapsed.num_hours())
+    } else if elapsed.num_days() < 7 {
+        format!("{}d", elapsed.num_days())
+    } else {
+        let dt = dt.with_timezone(&offset);
+        if dt.year() == now.with_timezone(&offset).year() {
+            dt.format("%b %-d").to_string()
+        } else {
+            dt.format("%Y-%m-%d").to_string()
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+
+/// The time zone that an offset from UTC, in minutes east, stands for.
+/// Offsets of a day or more are clamped, as chrono doesn't allow them.
+pub fn utc_offset(minutes: i32) -> FixedOffset {
+    let max_minutes = 24 * 60 - 1;
+    let minutes = minutes.clamp(-max_minutes, max_minutes);
+    FixedOffset::east_opt(minutes * 60).unwrap()
+}
+
+/// The current time. The 3DS clock is set to local time with no time zone,
+/// so the time it reports as UTC is ahead of the real UTC by the user's
+/// offset.
+pub fn now_utc(offset: FixedOffset) -> DateTime<Utc> {
+    Utc::now() - Duration::seconds(offset.local_minus_utc().into())
+}
+
+/// Describe an offset from UTC in minutes, such as "UTC", "UTC-8", or
+/// "UTC+5:30".
+pub fn format_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let hours = minutes.abs() / 60;
+    let minutes = minutes.abs() % 60;
+    match (hours, minutes) {
+        (0, 0) => "UTC".into(),
+        (hours, 0) => format!("UTC{}{}", sign, hours),
+        (hours, minutes) => format!("UTC{}{}:{:02}", sign, hours, minutes),
+    }
+}